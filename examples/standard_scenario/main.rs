@@ -16,14 +16,14 @@ use sim::core::runner;
 use sim::net::router;
 
 fn register_needed_types(id_reg: &mut IdRegistrar) {
-    simple_module::register(id_reg);
-    sink::register(id_reg);
+    simple_module::SimpleModule::register(id_reg);
+    sink::Sink::register(id_reg);
     text_event::register(id_reg);
     text_message::register(id_reg);
     simple_connection::register(id_reg);
-    echo_module::register(id_reg);
-    container::register(id_reg);
-    router::router::register(id_reg);
+    echo_module::EchoModule::register(id_reg);
+    container::ModuleContainer::register(id_reg);
+    router::router::Router::register_all(id_reg);
 }
 
 fn setup_group(r: &mut runner::Runner, id_reg: &mut IdRegistrar) -> ModuleId {
@@ -171,6 +171,7 @@ fn main() {
         last_type_id: 0,
         type_ids: std::collections::HashMap::new(),
         type_ids_reverse: std::collections::HashMap::new(),
+        rust_type_ids: std::collections::HashMap::new(),
     };
 
     setup_modules(&mut r, &mut id_reg);
@@ -0,0 +1,170 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Ident, ImplItem, ItemImpl, LitInt, LitStr, Path};
+
+struct GateAttr {
+    id: Path,
+    name: LitStr,
+    dir: proc_macro2::TokenStream,
+    size: proc_macro2::TokenStream,
+}
+
+//Fills in the boilerplate of a Module implementation. Expects the struct to have the fields
+//`type_id: ModuleTypeId`, `id: ModuleId` and `name: String`.
+//
+//#[sim_module(
+//    type_str = "QueueModule",
+//    gate(id = OUT_GATE, name = "out", dir = output),
+//    gate(id = IN_GATE, name = "in", dir = input, size = 1),
+//)]
+//impl Module for Queue {
+//    fn handle_message(...) -> ... { ... }
+//}
+//
+//dir is one of input, output or inout. size is the vector size of the gate, if it is left out
+//the gate has as many ports as get connected.
+//
+//generates module_type_id(), module_id(), name() and gates() (unless they are written by
+//hand) and an inherent `Queue::TYPE_STR` and `Queue::register(id_reg)`.
+#[proc_macro_attribute]
+pub fn sim_module(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut type_str: Option<LitStr> = None;
+    let mut gates: Vec<GateAttr> = Vec::new();
+
+    let attr_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("type_str") {
+            type_str = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("gate") {
+            let mut id: Option<Path> = None;
+            let mut name: Option<LitStr> = None;
+            let mut dir = None;
+            let mut size = quote! { None };
+
+            meta.parse_nested_meta(|field| {
+                if field.path.is_ident("id") {
+                    id = Some(field.value()?.parse()?);
+                } else if field.path.is_ident("name") {
+                    name = Some(field.value()?.parse()?);
+                } else if field.path.is_ident("dir") {
+                    let d: Ident = field.value()?.parse()?;
+                    dir = Some(match d.to_string().as_str() {
+                        "input" => quote! { Input },
+                        "output" => quote! { Output },
+                        "inout" => quote! { InOut },
+                        _ => return Err(field.error("expected input, output or inout")),
+                    });
+                } else if field.path.is_ident("size") {
+                    let n: LitInt = field.value()?.parse()?;
+                    size = quote! { Some(#n) };
+                } else {
+                    return Err(field.error("expected `id`, `name`, `dir` or `size`"));
+                }
+                Ok(())
+            })?;
+
+            match (id, name, dir) {
+                (Some(id), Some(name), Some(dir)) => {
+                    gates.push(GateAttr {
+                        id,
+                        name,
+                        dir,
+                        size,
+                    });
+                    Ok(())
+                }
+                _ => Err(meta.error("a gate needs `id`, `name` and `dir`")),
+            }
+        } else {
+            Err(meta.error("expected `type_str` or `gate`"))
+        }
+    });
+    parse_macro_input!(attr with attr_parser);
+
+    let mut imp = parse_macro_input!(item as ItemImpl);
+
+    let type_str = match type_str {
+        Some(s) => s,
+        None => {
+            return syn::Error::new_spanned(
+                &imp.self_ty,
+                "sim_module needs a `type_str = \"...\"`",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    let defined: Vec<String> = imp
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(f) => Some(f.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect();
+    let is_defined = |name: &str| defined.iter().any(|d| d == name);
+
+    let mut generated: Vec<ImplItem> = Vec::new();
+    if !is_defined("module_type_id") {
+        generated.push(syn::parse_quote! {
+            fn module_type_id(&self) -> ::sim::core::id_mngmnt::id_types::ModuleTypeId {
+                self.type_id
+            }
+        });
+    }
+    if !is_defined("module_id") {
+        generated.push(syn::parse_quote! {
+            fn module_id(&self) -> ::sim::core::id_mngmnt::id_types::ModuleId {
+                self.id
+            }
+        });
+    }
+    if !is_defined("name") {
+        generated.push(syn::parse_quote! {
+            fn name(&self) -> String {
+                self.name.clone()
+            }
+        });
+    }
+    if !is_defined("gates") {
+        let descs = gates.iter().map(|g| {
+            let (id, name, dir, size) = (&g.id, &g.name, &g.dir, &g.size);
+            quote! {
+                ::sim::core::modules::gate::new_static_gate_desc(
+                    #id,
+                    #name,
+                    ::sim::core::modules::gate::GateDirection::#dir,
+                    #size,
+                )
+            }
+        });
+        //built once, gate() looks names up in here from inside handlers
+        generated.push(syn::parse_quote! {
+            fn gates(&self) -> &[::sim::core::modules::gate::GateDesc] {
+                static GATES: &[::sim::core::modules::gate::GateDesc] = &[#(#descs),*];
+                GATES
+            }
+        });
+    }
+    imp.items.extend(generated);
+
+    let self_ty = &imp.self_ty;
+    let (impl_generics, _, where_clause) = imp.generics.split_for_impl();
+
+    let expanded = quote! {
+        #imp
+
+        impl #impl_generics #self_ty #where_clause {
+            pub const TYPE_STR: &'static str = #type_str;
+
+            pub fn register(id_reg: &mut ::sim::core::id_mngmnt::id_registrar::IdRegistrar) {
+                id_reg.register_type(Self::TYPE_STR.to_owned());
+            }
+        }
+    };
+
+    expanded.into()
+}
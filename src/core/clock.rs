@@ -6,14 +6,15 @@ pub fn new() -> Clock {
     Clock { time: 0 }
 }
 
-static YEARS: u64 = 365 * DAYS;
-static WEEKS: u64 = 7 * DAYS;
-static DAYS: u64 = 24 * HOURS;
-static HOURS: u64 = 60 * MINUTES;
-static MINUTES: u64 = 60 * SECONDS;
-static SECONDS: u64 = 1000 * MILLI_SECONDS;
-static MILLI_SECONDS: u64 = 1000 * MICRO_SECONDS;
-static MICRO_SECONDS: u64 = 1000;
+pub static YEARS: u64 = 365 * DAYS;
+pub static WEEKS: u64 = 7 * DAYS;
+pub static DAYS: u64 = 24 * HOURS;
+pub static HOURS: u64 = 60 * MINUTES;
+pub static MINUTES: u64 = 60 * SECONDS;
+pub static SECONDS: u64 = 1000 * MILLI_SECONDS;
+pub static MILLI_SECONDS: u64 = 1000 * MICRO_SECONDS;
+pub static MICRO_SECONDS: u64 = 1000 * NANO_SECONDS;
+pub static NANO_SECONDS: u64 = 1;
 
 #[allow(dead_code)]
 impl Clock {
@@ -54,3 +55,80 @@ impl Clock {
         self.time / YEARS
     }
 }
+
+//parses a duration like "10ms", "1.5s" or "200" (plain numbers are nanoseconds).
+//Units: ns, us, ms, s, min, h
+pub fn parse_time(s: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let t = parse_duration(s)?;
+    if t < 0.0 {
+        return Err(format!("Time can not be negative: \"{}\"", s.trim()).into());
+    }
+    if t > u64::max_value() as f64 {
+        return Err(format!("Time is too large: \"{}\"", s.trim()).into());
+    }
+    Ok(t.round() as u64)
+}
+
+//like parse_time but allows fractions of nanoseconds and negative values, eg for the mean of a
+//normal distribution
+pub fn parse_duration(s: &str) -> Result<f64, Box<dyn std::error::Error>> {
+    let s = s.trim();
+    //two letter units first, "ms" also ends with "s"
+    let units = [
+        ("min", MINUTES),
+        ("ns", NANO_SECONDS),
+        ("us", MICRO_SECONDS),
+        ("ms", MILLI_SECONDS),
+        ("s", SECONDS),
+        ("h", HOURS),
+    ];
+    let (number, factor) = match units.iter().find(|(unit, _)| s.ends_with(unit)) {
+        Some((unit, factor)) => (&s[..s.len() - unit.len()], *factor),
+        None => (s, NANO_SECONDS),
+    };
+
+    //f64 parses "inf" and "NaN" too, neither is a time
+    match number.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(n * factor as f64),
+        _ => Err(format!("Not a valid time: \"{}\"", s).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_units() {
+        assert_eq!(parse_time("200").unwrap(), 200);
+        assert_eq!(parse_time("200ns").unwrap(), 200);
+        assert_eq!(parse_time("3us").unwrap(), 3 * MICRO_SECONDS);
+        assert_eq!(parse_time("10ms").unwrap(), 10 * MILLI_SECONDS);
+        assert_eq!(parse_time("1.5s").unwrap(), 1500 * MILLI_SECONDS);
+        assert_eq!(parse_time("2min").unwrap(), 2 * MINUTES);
+        assert_eq!(parse_time("1h").unwrap(), HOURS);
+        assert_eq!(parse_time(" 4 ms ").unwrap(), 4 * MILLI_SECONDS);
+    }
+
+    #[test]
+    fn parse_time_rejects_garbage() {
+        assert!(parse_time("").is_err());
+        assert!(parse_time("ms").is_err());
+        assert!(parse_time("10 parsecs").is_err());
+        assert!(parse_time("1m").is_err());
+        assert!(parse_time("-1s").is_err());
+        assert!(parse_time("inf").is_err());
+        assert!(parse_time("infs").is_err());
+        assert!(parse_time("NaN").is_err());
+        assert!(parse_time("1e30s").is_err());
+        assert!(parse_duration("-inf").is_err());
+    }
+
+    #[test]
+    fn parse_duration_keeps_fractions_and_signs() {
+        assert_eq!(parse_duration("0.5").unwrap(), 0.5);
+        assert_eq!(parse_duration("-2ms").unwrap(), -2e6);
+        assert_eq!(parse_duration("1.5us").unwrap(), 1500.0);
+        assert!(parse_duration("fast").is_err());
+    }
+}
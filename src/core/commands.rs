@@ -0,0 +1,56 @@
+use crate::core::connection::connection::Connection;
+use crate::core::connection::mesh::ConnectionKind;
+use crate::core::id_mngmnt::id_types::{ConnectionId, GateId, ModuleId, PortId};
+use crate::core::modules::module::Module;
+
+//Requests a module makes to the runner while handling a message or event.
+//They are applied after the handler returned and its messages were sent.
+pub enum Command {
+    //take a connection down or bring it back up. If notify is set the modules at both
+    //ends that want it (Module::wants_link_state) get a LinkStateChange event
+    SetLinkState {
+        conn: ConnectionId,
+        up: bool,
+        notify: bool,
+    },
+
+    //add a module to the simulation, below parent in the module forest (or at the top if None).
+    //It gets initialized after all commands of the same handler were applied, so it can be connected first
+    AddModule {
+        module: Box<dyn Module>,
+        parent: Option<ModuleId>,
+    },
+
+    //same as Runner::connect_modules
+    Connect {
+        conn: Box<dyn Connection>,
+        kind: ConnectionKind,
+        mod_out: ModuleId,
+        gate_out: GateId,
+        out_port: PortId,
+        mod_in: ModuleId,
+        gate_in: GateId,
+        in_port: PortId,
+    },
+
+    //remove a connection and the ports at both ends
+    Disconnect {
+        conn: ConnectionId,
+    },
+
+    //finalize and remove a module and everything below it in the module forest, with all their
+    //connections, pending timer events and messages on their way to them
+    DeleteModule {
+        module: ModuleId,
+    },
+}
+
+//payload of the event (events::ev::Ev<LinkStateChange>) the modules at both ends of a
+//connection get when it goes down or comes back up, if their wants_link_state returns true
+#[derive(Clone)]
+pub struct LinkStateChange {
+    pub conn: ConnectionId,
+    pub gate: GateId,
+    pub port: PortId,
+    pub up: bool,
+}
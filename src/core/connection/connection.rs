@@ -3,14 +3,25 @@ use crate::core::id_mngmnt::id_types::{ConnectionId, ConnectionTypeId, GateId, M
 use crate::core::messages::message::Message;
 
 pub trait Connection {
+    //returns the messages that come out at the other end and the time they arrive there.
+    //Empty if the message was dropped, more than one if it was duplicated
     fn handle_message(
         &mut self,
-        message: Box<Message>,
+        message: Box<dyn Message>,
         ctx: &mut SimulationContext,
-    ) -> Option<(u64, Box<Message>)>;
+    ) -> Vec<(u64, Box<dyn Message>)>;
 
     fn connection_id(&self) -> ConnectionId;
     fn connection_type_id(&self) -> ConnectionTypeId;
+
+    //connections that model transmission delay are busy while sending a message
+    fn is_busy(&self, _now: u64) -> bool {
+        false
+    }
+    //time at which the current transmission is finished, in the past if the connection is idle
+    fn transmission_finish_time(&self) -> u64 {
+        0
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -0,0 +1,72 @@
+use crate::core::messages::message::Message;
+use rand::Rng;
+
+//probability that at least one of bit_length bits is flipped if every bit is flipped with probability ber
+pub fn corruption_probability(ber: f64, bit_length: u64) -> f64 {
+    if ber <= 0.0 || bit_length == 0 {
+        return 0.0;
+    }
+    if ber >= 1.0 {
+        return 1.0;
+    }
+    -((bit_length as f64) * (-ber).ln_1p()).exp_m1()
+}
+
+//marks the message as corrupted with the probability given by the bit error rate and its bit_length
+pub fn apply_bit_errors<R: Rng + ?Sized>(msg: &mut dyn Message, ber: f64, rng: &mut R) {
+    let p = corruption_probability(ber, msg.meta().bit_length);
+    if p > 0.0 && rng.gen::<f64>() < p {
+        msg.meta_mut().corrupted = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+    use rand::prng::XorShiftRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn ber_to_probability() {
+        assert_eq!(corruption_probability(0.0, 1000), 0.0);
+        assert_eq!(corruption_probability(1e-3, 0), 0.0);
+        assert_eq!(corruption_probability(1.0, 1), 1.0);
+        assert_eq!(corruption_probability(1.0, 0), 0.0);
+        //a single bit is flipped with the bit error rate itself
+        assert!((corruption_probability(0.25, 1) - 0.25).abs() < 1e-12);
+        //1 - (1 - ber)^n
+        let expected = 1.0 - (1.0f64 - 1e-4).powi(12000);
+        assert!((corruption_probability(1e-4, 12000) - expected).abs() < 1e-12);
+        //stays accurate for tiny rates where 1 - ber rounds to 1
+        let tiny = corruption_probability(1e-18, 1000);
+        assert!((tiny - 1e-15).abs() < 1e-24, "{}", tiny);
+    }
+
+    #[test]
+    fn bit_errors_mark_messages() {
+        let mut env = new_test_env();
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+
+        let mut corrupted = 0;
+        for _ in 0..10000 {
+            let mut msg = env.msg(1000);
+            apply_bit_errors(msg.as_mut(), 1e-4, &mut rng);
+            if msg.meta().corrupted {
+                corrupted += 1;
+            }
+        }
+        //1 - (1 - 1e-4)^1000 is about 0.095
+        assert!(corrupted > 850 && corrupted < 1050, "{}", corrupted);
+
+        let mut empty = env.msg(0);
+        apply_bit_errors(empty.as_mut(), 1.0, &mut rng);
+        assert!(!empty.meta().corrupted);
+        let mut clean = env.msg(1000);
+        apply_bit_errors(clean.as_mut(), 0.0, &mut rng);
+        assert!(!clean.meta().corrupted);
+        let mut broken = env.msg(1);
+        apply_bit_errors(broken.as_mut(), 1.0, &mut rng);
+        assert!(broken.meta().corrupted);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::core::clock::SECONDS;
+use crate::core::connection::connection::Connection;
+use crate::core::connection::corruption::apply_bit_errors;
+use crate::core::contexts::SimulationContext;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{ConnectionId, ConnectionTypeId};
+use crate::core::messages::message::Message;
+
+use std::collections::VecDeque;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BusyPolicy {
+    //messages sent while busy are dropped
+    Reject,
+    //messages sent while busy are transmitted after the ones before them. With a queue_limit,
+    //messages that find that many messages waiting are dropped
+    Queue,
+}
+
+//Models a link with a datarate and a propagation delay. A message takes bit_length / datarate
+//to be put on the link and arrives after the propagation delay on top of that.
+//Messages without a bit_length take no time to transmit, a warning is printed for the first one.
+pub struct DatarateConnection {
+    pub id: ConnectionId,
+    pub type_id: ConnectionTypeId,
+
+    pub datarate: u64, // bits per second
+    pub delay: u64,    // propagation delay
+    pub busy_policy: BusyPolicy,
+    //messages waiting for their transmission with BusyPolicy::Queue, None means unbounded
+    pub queue_limit: Option<u64>,
+
+    //transmitted messages get marked as corrupted based on this and their bit_length
+    pub bit_error_rate: f64,
+
+    busy_until: u64,
+    //end of the transmission of the messages that are not yet completely sent
+    finish_times: VecDeque<u64>,
+    //dropped because the link was busy (Reject) or the queue was full (Queue)
+    pub msgs_rejected: u64,
+    warned_zero_length: bool,
+}
+
+pub static TYPE_STR: &str = "DatarateConnection";
+pub fn register(id_reg: &mut IdRegistrar) {
+    id_reg.register_type(TYPE_STR.to_owned());
+}
+//panics on a datarate of 0, connection_factory::datarate_connection_from_params returns an Err
+pub fn new_datarate_connection(
+    id_reg: &mut IdRegistrar,
+    datarate: u64,
+    delay: u64,
+    busy_policy: BusyPolicy,
+) -> DatarateConnection {
+    if datarate == 0 {
+        panic!("A DatarateConnection needs a datarate > 0");
+    }
+
+    DatarateConnection {
+        id: id_reg.new_connection_id(),
+        type_id: id_reg.lookup_connection_id(TYPE_STR.to_owned()).unwrap(),
+        datarate: datarate,
+        delay: delay,
+        busy_policy: busy_policy,
+        queue_limit: None,
+
+        bit_error_rate: 0.0,
+
+        busy_until: 0,
+        finish_times: VecDeque::new(),
+        msgs_rejected: 0,
+        warned_zero_length: false,
+    }
+}
+
+impl DatarateConnection {
+    pub fn transmission_duration(&self, bit_length: u64) -> u64 {
+        //round up, a message that is not completely sent has not been sent
+        let nanos = bit_length as u128 * SECONDS as u128;
+        let rate = self.datarate as u128;
+        nanos.div_ceil(rate) as u64
+    }
+
+    //messages that wait for the current transmission to finish
+    pub fn queued(&self) -> u64 {
+        (self.finish_times.len() as u64).saturating_sub(1)
+    }
+}
+
+impl Connection for DatarateConnection {
+    fn handle_message(
+        &mut self,
+        mut message: Box<dyn Message>,
+        ctx: &mut SimulationContext,
+    ) -> Vec<(u64, Box<dyn Message>)> {
+        let now = ctx.time.now();
+        while self.finish_times.front().map_or(false, |t| *t <= now) {
+            self.finish_times.pop_front();
+        }
+
+        let start = if self.is_busy(now) {
+            match self.busy_policy {
+                BusyPolicy::Reject => {
+                    self.msgs_rejected += 1;
+                    return Vec::new();
+                }
+                BusyPolicy::Queue => {
+                    if let Some(limit) = self.queue_limit {
+                        if self.queued() >= limit {
+                            self.msgs_rejected += 1;
+                            return Vec::new();
+                        }
+                    }
+                    self.busy_until
+                }
+            }
+        } else {
+            now
+        };
+
+        let bit_length = message.meta().bit_length;
+        if bit_length == 0 && !self.warned_zero_length {
+            self.warned_zero_length = true;
+            println!(
+                "Warning: DatarateConnection {} got a message without bit_length, it takes no time to transmit",
+                self.id.raw()
+            );
+        }
+        self.busy_until = start + self.transmission_duration(bit_length);
+        self.finish_times.push_back(self.busy_until);
+        apply_bit_errors(message.as_mut(), self.bit_error_rate, ctx.prng());
+
+        vec![(self.busy_until + self.delay, message)]
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        self.id
+    }
+    fn connection_type_id(&self) -> ConnectionTypeId {
+        self.type_id
+    }
+
+    fn is_busy(&self, now: u64) -> bool {
+        self.busy_until > now
+    }
+    fn transmission_finish_time(&self) -> u64 {
+        self.busy_until
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    #[test]
+    fn transmission_duration_rounds_up() {
+        let mut env = new_test_env();
+        register(&mut env.id_reg);
+        let conn = new_datarate_connection(&mut env.id_reg, 1_000_000, 0, BusyPolicy::Queue);
+        //1 Mbit/s: one bit takes 1us
+        assert_eq!(conn.transmission_duration(1), 1000);
+        assert_eq!(conn.transmission_duration(8000), 8_000_000);
+        let conn = new_datarate_connection(&mut env.id_reg, 3, 0, BusyPolicy::Queue);
+        assert_eq!(conn.transmission_duration(1), 333_333_334);
+    }
+
+    #[test]
+    fn queued_messages_are_sent_back_to_back() {
+        let mut env = new_test_env();
+        register(&mut env.id_reg);
+        let mut conn = new_datarate_connection(&mut env.id_reg, 1_000_000, 500, BusyPolicy::Queue);
+
+        let m = env.msg(1000);
+        let first = conn.handle_message(m, &mut env.ctx());
+        let m = env.msg(2000);
+        let second = conn.handle_message(m, &mut env.ctx());
+        assert_eq!(first[0].0, 1_000_000 + 500);
+        assert_eq!(second[0].0, 3_000_000 + 500);
+        assert!(conn.is_busy(2_999_999));
+        assert!(!conn.is_busy(3_000_000));
+        assert_eq!(conn.queued(), 1);
+    }
+
+    #[test]
+    fn reject_and_queue_limit_drop_messages() {
+        let mut env = new_test_env();
+        register(&mut env.id_reg);
+        let mut conn = new_datarate_connection(&mut env.id_reg, 1_000_000, 0, BusyPolicy::Reject);
+        let m = env.msg(1000);
+        assert_eq!(conn.handle_message(m, &mut env.ctx()).len(), 1);
+        let m = env.msg(1000);
+        assert!(conn.handle_message(m, &mut env.ctx()).is_empty());
+        assert_eq!(conn.msgs_rejected, 1);
+
+        let mut conn = new_datarate_connection(&mut env.id_reg, 1_000_000, 0, BusyPolicy::Queue);
+        conn.queue_limit = Some(1);
+        let sent: usize = (0..3)
+            .map(|_| {
+                let m = env.msg(1000);
+                conn.handle_message(m, &mut env.ctx()).len()
+            })
+            .sum();
+        assert_eq!(sent, 2);
+        assert_eq!(conn.msgs_rejected, 1);
+
+        //once the first transmission is done there is room again
+        env.set_time(1_000_000);
+        let m = env.msg(1000);
+        assert_eq!(conn.handle_message(m, &mut env.ctx()).len(), 1);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::core::connection::connection::Connection;
+use crate::core::contexts::SimulationContext;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{ConnectionId, ConnectionTypeId};
+use crate::core::messages::message::Message;
+use crate::core::random::distribution::{check_probability, Distribution};
+use rand::Rng;
+
+//Wraps another connection. Every message that comes out of the inner connection is
+//duplicated with the given probability, the copy arrives extra_delay after the original.
+//The copy is a clone_msg of the original: it keeps the message id and all meta data, so it is the
+//same message arriving twice. Receivers can detect it that way, the Sink counts a Packet whose
+//sequence number it has seen already as a duplicate.
+pub struct DuplicatingConnection {
+    pub id: ConnectionId,
+    pub type_id: ConnectionTypeId,
+
+    pub inner: Box<dyn Connection>,
+    pub probability: f64,
+    pub extra_delay: Distribution,
+
+    pub msgs_duplicated: u64,
+}
+
+pub static TYPE_STR: &str = "DuplicatingConnection";
+pub fn register(id_reg: &mut IdRegistrar) {
+    id_reg.register_type(TYPE_STR.to_owned());
+}
+//panics on a probability outside of [0, 1], see connection_factory for a generator that returns an Err
+pub fn new_duplicating_connection(
+    id_reg: &mut IdRegistrar,
+    inner: Box<dyn Connection>,
+    probability: f64,
+    extra_delay: Distribution,
+) -> DuplicatingConnection {
+    if let Err(e) = check_probability("Duplication probability", probability) {
+        panic!("{}", e);
+    }
+    DuplicatingConnection {
+        id: id_reg.new_connection_id(),
+        type_id: id_reg.lookup_connection_id(TYPE_STR.to_owned()).unwrap(),
+        inner: inner,
+        probability: probability,
+        extra_delay: extra_delay,
+
+        msgs_duplicated: 0,
+    }
+}
+
+impl Connection for DuplicatingConnection {
+    fn handle_message(
+        &mut self,
+        message: Box<dyn Message>,
+        ctx: &mut SimulationContext,
+    ) -> Vec<(u64, Box<dyn Message>)> {
+        let mut out = self.inner.handle_message(message, ctx);
+
+        let mut copies = Vec::new();
+        for (time, msg) in &out {
+            if ctx.prng().gen::<f64>() < self.probability {
+                copies.push((
+                    time + self.extra_delay.sample_time(ctx.prng()),
+                    msg.clone_msg(),
+                ));
+                self.msgs_duplicated += 1;
+            }
+        }
+        out.append(&mut copies);
+
+        out
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        self.id
+    }
+    fn connection_type_id(&self) -> ConnectionTypeId {
+        self.type_id
+    }
+
+    fn is_busy(&self, now: u64) -> bool {
+        self.inner.is_busy(now)
+    }
+    fn transmission_finish_time(&self) -> u64 {
+        self.inner.transmission_finish_time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::simple_connection;
+    use crate::core::testing::new_test_env;
+
+    #[test]
+    fn messages_are_duplicated_with_the_probability() {
+        let mut env = new_test_env();
+        simple_connection::register(&mut env.id_reg);
+        register(&mut env.id_reg);
+        let inner = simple_connection::new_simple_connection(&mut env.id_reg, 100, 0, 0);
+        let mut conn = new_duplicating_connection(
+            &mut env.id_reg,
+            Box::new(inner),
+            0.3,
+            Distribution::Constant(50.0),
+        );
+
+        let mut copies = 0;
+        for _ in 0..10000 {
+            let msg = env.msg(8);
+            let id = msg.msg_id();
+            let out = conn.handle_message(msg, &mut env.ctx());
+            assert_eq!(out[0].0, 100);
+            if out.len() == 2 {
+                //the copy is the same message, later
+                assert_eq!(out[1].0, 150);
+                assert!(out[1].1.msg_id() == id);
+                copies += 1;
+            }
+        }
+        assert_eq!(conn.msgs_duplicated, copies);
+        assert!(copies > 2800 && copies < 3200, "{}", copies);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nan_probabilities_are_rejected() {
+        let mut env = new_test_env();
+        simple_connection::register(&mut env.id_reg);
+        register(&mut env.id_reg);
+        let inner = simple_connection::new_simple_connection(&mut env.id_reg, 100, 0, 0);
+        new_duplicating_connection(
+            &mut env.id_reg,
+            Box::new(inner),
+            std::f64::NAN,
+            Distribution::Constant(0.0),
+        );
+    }
+}
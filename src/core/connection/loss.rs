@@ -0,0 +1,312 @@
+use crate::core::contexts::SimulationContext;
+use crate::core::messages::message::Message;
+use crate::core::random::distribution::check_probability;
+use rand::Rng;
+
+//Decides whether a message is lost on a connection. Implementations may keep state
+//across messages, they live as long as the connection does.
+pub trait LossModel {
+    fn is_lost(&mut self, msg: &dyn Message, ctx: &mut SimulationContext) -> bool;
+}
+
+pub struct NoLoss {}
+
+impl LossModel for NoLoss {
+    fn is_lost(&mut self, _msg: &dyn Message, _ctx: &mut SimulationContext) -> bool {
+        false
+    }
+}
+
+//every message is lost independently with the same probability
+pub struct BernoulliLoss {
+    pub probability: f64,
+}
+
+//panics on a probability outside of [0, 1], parse_loss returns an Err
+pub fn new_bernoulli_loss(probability: f64) -> BernoulliLoss {
+    if let Err(e) = check_probability("Loss probability", probability) {
+        panic!("{}", e);
+    }
+    BernoulliLoss {
+        probability: probability,
+    }
+}
+
+impl LossModel for BernoulliLoss {
+    fn is_lost(&mut self, _msg: &dyn Message, ctx: &mut SimulationContext) -> bool {
+        ctx.prng().gen::<f64>() < self.probability
+    }
+}
+
+//Two state markov model for bursty loss. Before each message the state may change,
+//then the message is lost with the loss probability of the current state.
+pub struct GilbertElliottLoss {
+    pub p_good_to_bad: f64,
+    pub p_bad_to_good: f64,
+    pub loss_good: f64,
+    pub loss_bad: f64,
+
+    bad: bool,
+}
+
+//panics on probabilities outside of [0, 1], parse_loss returns an Err
+pub fn new_gilbert_elliott_loss(
+    p_good_to_bad: f64,
+    p_bad_to_good: f64,
+    loss_good: f64,
+    loss_bad: f64,
+) -> GilbertElliottLoss {
+    if let Err(e) = check_gilbert_elliott(&[p_good_to_bad, p_bad_to_good, loss_good, loss_bad]) {
+        panic!("{}", e);
+    }
+
+    GilbertElliottLoss {
+        p_good_to_bad: p_good_to_bad,
+        p_bad_to_good: p_bad_to_good,
+        loss_good: loss_good,
+        loss_bad: loss_bad,
+
+        bad: false,
+    }
+}
+
+fn check_gilbert_elliott(probabilities: &[f64]) -> Result<(), Box<dyn std::error::Error>> {
+    for p in probabilities {
+        check_probability("Gilbert-Elliott probabilities", *p)?;
+    }
+    Ok(())
+}
+
+impl GilbertElliottLoss {
+    pub fn in_bad_state(&self) -> bool {
+        self.bad
+    }
+}
+
+impl LossModel for GilbertElliottLoss {
+    fn is_lost(&mut self, _msg: &dyn Message, ctx: &mut SimulationContext) -> bool {
+        let switch = if self.bad {
+            self.p_bad_to_good
+        } else {
+            self.p_good_to_bad
+        };
+        if ctx.prng().gen::<f64>() < switch {
+            self.bad = !self.bad;
+        }
+
+        let loss = if self.bad {
+            self.loss_bad
+        } else {
+            self.loss_good
+        };
+        ctx.prng().gen::<f64>() < loss
+    }
+}
+
+//Replays a recorded loss pattern, one entry per message. When the pattern is used up
+//it starts over if repeat is set, otherwise no more messages are lost.
+pub struct TraceLoss {
+    pattern: Vec<bool>,
+    pos: usize,
+    pub repeat: bool,
+}
+
+pub fn new_trace_loss_from_pattern(pattern: Vec<bool>, repeat: bool) -> TraceLoss {
+    TraceLoss {
+        pattern: pattern,
+        pos: 0,
+        repeat: repeat,
+    }
+}
+
+//The file contains one 0 (delivered) or 1 (lost) per line. Empty lines and lines starting with # are ignored
+pub fn new_trace_loss(path: &str, repeat: bool) -> Result<TraceLoss, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut pattern = Vec::new();
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line {
+            "0" => pattern.push(false),
+            "1" => pattern.push(true),
+            _ => {
+                return Err(
+                    format!("{}:{}: expected 0 or 1 but found: {}", path, idx + 1, line).into(),
+                );
+            }
+        }
+    }
+
+    Ok(new_trace_loss_from_pattern(pattern, repeat))
+}
+
+impl LossModel for TraceLoss {
+    fn is_lost(&mut self, _msg: &dyn Message, _ctx: &mut SimulationContext) -> bool {
+        if self.pos >= self.pattern.len() {
+            if !self.repeat || self.pattern.is_empty() {
+                return false;
+            }
+            self.pos = 0;
+        }
+
+        let lost = self.pattern[self.pos];
+        self.pos += 1;
+        lost
+    }
+}
+
+//Parses "none", "bernoulli(p)", "gilbert_elliott(p_good_to_bad, p_bad_to_good, loss_good, loss_bad)",
+//"trace(file)" or "trace(file, repeat)"
+pub fn parse_loss(s: &str) -> Result<Box<dyn LossModel>, Box<dyn std::error::Error>> {
+    let s = s.trim();
+    let (name, args) = match (s.find('('), s.ends_with(')')) {
+        (Some(open), true) => (
+            s[..open].trim(),
+            s[open + 1..s.len() - 1]
+                .split(',')
+                .map(|a| a.trim())
+                .collect(),
+        ),
+        (None, false) => (s, Vec::new()),
+        _ => return Err(format!("Not a valid loss model: \"{}\"", s).into()),
+    };
+
+    //the constructors panic on these, so they are checked here first
+    match (name, args.len()) {
+        ("none", 0) => Ok(Box::new(NoLoss {})),
+        ("bernoulli", 1) => {
+            let p: f64 = args[0].parse()?;
+            check_probability("Loss probability", p)?;
+            Ok(Box::new(new_bernoulli_loss(p)))
+        }
+        ("gilbert_elliott", 4) => {
+            let p = args
+                .iter()
+                .map(|a| a.parse())
+                .collect::<Result<Vec<f64>, _>>()?;
+            check_gilbert_elliott(&p)?;
+            Ok(Box::new(new_gilbert_elliott_loss(p[0], p[1], p[2], p[3])))
+        }
+        ("trace", 1) => Ok(Box::new(new_trace_loss(args[0], false)?)),
+        ("trace", 2) if args[1] == "repeat" => Ok(Box::new(new_trace_loss(args[0], true)?)),
+        _ => Err(format!("Unknown loss model or wrong number of arguments: \"{}\"", s).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    #[test]
+    fn bernoulli_loss_rate() {
+        let mut env = new_test_env();
+        let msg = env.msg(0);
+        let mut loss = new_bernoulli_loss(0.3);
+        let lost = (0..10000)
+            .filter(|_| loss.is_lost(msg.as_ref(), &mut env.ctx()))
+            .count();
+        assert!(lost > 2800 && lost < 3200, "lost {}", lost);
+
+        let mut never = new_bernoulli_loss(0.0);
+        let mut always = new_bernoulli_loss(1.0);
+        for _ in 0..100 {
+            assert!(!never.is_lost(msg.as_ref(), &mut env.ctx()));
+            assert!(always.is_lost(msg.as_ref(), &mut env.ctx()));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn bernoulli_loss_rejects_probabilities_above_one() {
+        new_bernoulli_loss(1.5);
+    }
+
+    #[test]
+    fn parse_loss_checks_the_probability() {
+        let mut env = new_test_env();
+        let msg = env.msg(0);
+        let mut always = parse_loss("bernoulli(1)").unwrap();
+        assert!(always.is_lost(msg.as_ref(), &mut env.ctx()));
+        let mut never = parse_loss("none").unwrap();
+        assert!(!never.is_lost(msg.as_ref(), &mut env.ctx()));
+        let mut bursty = parse_loss("gilbert_elliott(1, 0, 0, 1)").unwrap();
+        assert!(bursty.is_lost(msg.as_ref(), &mut env.ctx()));
+
+        for bad in &[
+            "bernoulli(NaN)",
+            "bernoulli(1.5)",
+            "bernoulli(-0.1)",
+            "bernoulli(0.1, 0.2)",
+            "bernoulli",
+            "bernoulli(0.1",
+            "sometimes(0.1)",
+            "gilbert_elliott(0.1, 0.2, 0.0, NaN)",
+            "gilbert_elliott(0.1, 1.2, 0.0, 1.0)",
+            "gilbert_elliott(0.1, 0.2, 0.0)",
+            "trace(/nonexistent/loss.txt)",
+            "trace(loss.txt, sometimes)",
+        ] {
+            assert!(parse_loss(bad).is_err(), "{} was accepted", bad);
+        }
+    }
+
+    #[test]
+    fn gilbert_elliott_loses_in_bad_state() {
+        let mut env = new_test_env();
+        let msg = env.msg(0);
+        let mut loss = new_gilbert_elliott_loss(0.1, 0.3, 0.0, 1.0);
+        let mut lost = 0;
+        for _ in 0..20000 {
+            let l = loss.is_lost(msg.as_ref(), &mut env.ctx());
+            assert_eq!(l, loss.in_bad_state());
+            if l {
+                lost += 1;
+            }
+        }
+        //the chain is in the bad state p_good_to_bad / (p_good_to_bad + p_bad_to_good) of the time
+        assert!(lost > 4500 && lost < 5500, "lost {}", lost);
+    }
+
+    #[test]
+    fn trace_loss_replays_the_pattern() {
+        let mut env = new_test_env();
+        let msg = env.msg(0);
+
+        let mut once = new_trace_loss_from_pattern(vec![true, false, true], false);
+        let seen: Vec<bool> = (0..5)
+            .map(|_| once.is_lost(msg.as_ref(), &mut env.ctx()))
+            .collect();
+        assert_eq!(seen, vec![true, false, true, false, false]);
+
+        let mut repeated = new_trace_loss_from_pattern(vec![true, false, true], true);
+        let seen: Vec<bool> = (0..5)
+            .map(|_| repeated.is_lost(msg.as_ref(), &mut env.ctx()))
+            .collect();
+        assert_eq!(seen, vec![true, false, true, true, false]);
+
+        let mut empty = new_trace_loss_from_pattern(Vec::new(), true);
+        assert!(!empty.is_lost(msg.as_ref(), &mut env.ctx()));
+    }
+
+    #[test]
+    fn trace_loss_file() {
+        let path = std::env::temp_dir().join(format!("trace_loss_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_owned();
+
+        std::fs::write(&path, "# recorded loss\n1\n\n0\n 1 \n").unwrap();
+        let loss = new_trace_loss(&path, false).unwrap();
+        assert_eq!(loss.pattern, vec![true, false, true]);
+        assert!(parse_loss(&format!("trace({}, repeat)", path)).is_ok());
+
+        std::fs::write(&path, "1\n0\nx\n").unwrap();
+        let err = new_trace_loss(&path, false).err().unwrap().to_string();
+        assert!(err.ends_with(":3: expected 0 or 1 but found: x"), "{}", err);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(new_trace_loss(&path, false).is_err());
+    }
+}
@@ -1,18 +1,47 @@
 use crate::core::connection::connection::*;
 use crate::core::contexts::SimulationContext;
 use crate::core::id_mngmnt::id_types::{ConnectionId, GateId, ModuleId, PortId};
-use crate::core::messages::message::{Message, TimedMessage};
+use crate::core::messages::message::{Message, TimedMessage, Transit};
+use crate::core::modules::gate::GateDesc;
 
 pub enum ConnectionKind {
     Onedirectional,
     Bidrectional,
 }
 
+//a chain of connections through one or more containers that ends at a non-container module
+pub struct ResolvedPath {
+    pub hops: Vec<ConnectionId>,
+    //where each hop ends, the last one is the receiving module
+    pub stops: Vec<(ModuleId, GateId, PortId)>,
+
+    pub rcv_mod: ModuleId,
+    pub rcv_gate: GateId,
+    pub rcv_port: PortId,
+}
+
 pub struct ConnectionMesh {
     //all connections are in here and are referenced in the other two maps
-    pub connections: std::collections::HashMap<ConnectionId, Box<Connection>>,
+    pub connections: std::collections::BTreeMap<ConnectionId, Box<Connection>>,
+
+    pub gates: std::collections::BTreeMap<(ModuleId, GateId, PortId), Port>,
+
+    //out-going ports whose messages pass through containers, mapped to the path they take.
+    //Built by resolve_paths so containers don't need to redirect every message at runtime
+    pub paths: std::collections::BTreeMap<(ModuleId, GateId, PortId), ResolvedPath>,
+
+    //connections that are currently down. Messages that would be sent over them are dropped
+    pub links_down: std::collections::BTreeSet<ConnectionId>,
+    //how many messages were dropped because a connection was down
+    pub dropped_link_down: std::collections::BTreeMap<ConnectionId, u64>,
 
-    pub gates: std::collections::HashMap<(ModuleId, GateId, PortId), Port>,
+    //ports whose connection was removed while the simulation ran. Messages sent on them are dropped
+    pub disconnected_ports: std::collections::BTreeSet<(ModuleId, GateId, PortId)>,
+    pub dropped_disconnected: u64,
+
+    //gates every module declared, connections are validated against these. Taken from
+    //Module::gates when the module is added and not updated after that
+    pub declared_gates: std::collections::BTreeMap<ModuleId, Vec<GateDesc>>,
 
     //messages that will be handled in the future
     pub messages: std::collections::BinaryHeap<TimedMessage>,
@@ -36,14 +65,33 @@ impl ConnectionMesh {
         gate_in: GateId,
         in_port: PortId,
     ) -> Result<(), Box<std::error::Error>> {
-        {
-            match self.gates.get(&(mod_in, gate_in, in_port)) {
-                Some(_) => {
-                    panic!("Tried to overwrite in-going port");
-                }
-                None => {}
-            }
+        let (need_out, need_in) = match con_kind {
+            ConnectionKind::Onedirectional => ((true, false), (false, true)),
+            ConnectionKind::Bidrectional => ((true, true), (true, true)),
+        };
+        self.check_gate(mod_out, gate_out, out_port, need_out)?;
+        self.check_gate(mod_in, gate_in, in_port, need_in)?;
+        if self.gates.contains_key(&(mod_in, gate_in, in_port)) {
+            return Err(format!(
+                "Tried to overwrite in-going port {} of gate {} of module {}",
+                in_port.0,
+                gate_in.0,
+                mod_in.raw()
+            )
+            .into());
+        }
+        if self.gates.contains_key(&(mod_out, gate_out, out_port)) {
+            return Err(format!(
+                "Tried to overwrite out-going port {} of gate {} of module {}",
+                out_port.0,
+                gate_out.0,
+                mod_out.raw()
+            )
+            .into());
+        }
 
+        {
+            self.disconnected_ports.remove(&(mod_in, gate_in, in_port));
             self.gates.insert(
                 (mod_in, gate_in, in_port),
                 Port {
@@ -62,13 +110,8 @@ impl ConnectionMesh {
             );
         }
 
-        match self.gates.get(&(mod_out, gate_out, out_port)) {
-            Some(_) => {
-                panic!("Tried to overwrite out-going port");
-            }
-            None => {}
-        }
-
+        self.disconnected_ports
+            .remove(&(mod_out, gate_out, out_port));
         self.gates.insert(
             (mod_out, gate_out, out_port),
             Port {
@@ -88,12 +131,248 @@ impl ConnectionMesh {
 
         self.connections.insert(conn.connection_id(), conn);
 
+        //the new connection might change where paths lead. Until resolve_paths is called again
+        //containers redirect messages themselves
+        self.paths.clear();
+
+        Ok(())
+    }
+
+    //passthroughs maps container -> (gate -> gate messages are passed on to, on the same port)
+    pub fn resolve_paths(
+        &mut self,
+        passthroughs: &std::collections::BTreeMap<
+            ModuleId,
+            std::collections::BTreeMap<GateId, GateId>,
+        >,
+    ) {
+        self.paths.clear();
+
+        for (key, port) in &self.gates {
+            match port.kind {
+                PortKind::In => continue,
+                PortKind::Out | PortKind::InOut => {}
+            }
+
+            let mut hops = vec![port.conn_id];
+            let mut stops = vec![(port.rcv_mod, port.rcv_gate, port.rcv_port)];
+            let mut current = port;
+            let mut resolved = true;
+
+            loop {
+                let next_gate = match passthroughs.get(&current.rcv_mod) {
+                    Some(mapping) => mapping.get(&current.rcv_gate),
+                    None => None,
+                };
+                let next_gate = match next_gate {
+                    Some(gate) => *gate,
+                    None => break,
+                };
+
+                //unconnected mappings and cycles are left to the runtime redirection
+                let next = self
+                    .gates
+                    .get(&(current.rcv_mod, next_gate, current.rcv_port));
+                match next {
+                    Some(next) if hops.len() <= self.gates.len() => match next.kind {
+                        PortKind::Out | PortKind::InOut => {
+                            hops.push(next.conn_id);
+                            stops.push((next.rcv_mod, next.rcv_gate, next.rcv_port));
+                            current = next;
+                        }
+                        PortKind::In => {
+                            resolved = false;
+                            break;
+                        }
+                    },
+                    _ => {
+                        resolved = false;
+                        break;
+                    }
+                }
+            }
+
+            if resolved && hops.len() > 1 {
+                self.paths.insert(
+                    *key,
+                    ResolvedPath {
+                        hops: hops,
+                        stops: stops,
+                        rcv_mod: current.rcv_mod,
+                        rcv_gate: current.rcv_gate,
+                        rcv_port: current.rcv_port,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn declare_gates(&mut self, module: ModuleId, gates: Vec<GateDesc>) {
+        self.declared_gates.insert(module, gates);
+    }
+
+    //checks that the module declared the gate, that the port fits the vector size
+    //and that the gate can (send, receive) as needed
+    fn check_gate(
+        &self,
+        module: ModuleId,
+        gate: GateId,
+        port: PortId,
+        (send, receive): (bool, bool),
+    ) -> Result<(), Box<std::error::Error>> {
+        let desc = match self.declared_gates.get(&module) {
+            Some(gates) => gates.iter().find(|g| g.id == gate),
+            None => None,
+        };
+        let desc = match desc {
+            Some(desc) => desc,
+            None => {
+                return Err(
+                    format!("Module {} does not declare gate {}", module.raw(), gate.0).into(),
+                );
+            }
+        };
+
+        if !desc.has_port(port.0) {
+            return Err(format!(
+                "Port {} is out of range for gate {} ({}) of module {}",
+                port.0,
+                desc.name,
+                gate.0,
+                module.raw()
+            )
+            .into());
+        }
+        if (send && !desc.direction.can_send()) || (receive && !desc.direction.can_receive()) {
+            return Err(format!(
+                "Gate {} ({}) of module {} is declared {:?} and can not be connected this way",
+                desc.name,
+                gate.0,
+                module.raw(),
+                desc.direction
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    //messages already on the link are not affected, only messages that reach it while it is down are dropped
+    pub fn set_link_state(
+        &mut self,
+        conn: ConnectionId,
+        up: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.connections.contains_key(&conn) {
+            return Err(format!(
+                "Tried to change state of connection that does not exist: {}",
+                conn.raw()
+            )
+            .into());
+        }
+        if up {
+            self.links_down.remove(&conn);
+        } else {
+            self.links_down.insert(conn);
+        }
+        Ok(())
+    }
+
+    pub fn is_link_up(&self, conn: ConnectionId) -> bool {
+        !self.links_down.contains(&conn)
+    }
+
+    pub fn disconnect(&mut self, conn: ConnectionId) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.connections.contains_key(&conn) {
+            return Err(format!(
+                "Tried to remove connection that does not exist: {}",
+                conn.raw()
+            )
+            .into());
+        }
+        for triple in self.endpoints(conn) {
+            self.gates.remove(&triple);
+            self.disconnected_ports.insert(triple);
+        }
+        self.connections.remove(&conn);
+        self.links_down.remove(&conn);
+        self.paths.clear();
         Ok(())
     }
 
+    //removes the module with all its connections and the messages that are on their way to it
+    pub fn remove_module(&mut self, module: ModuleId) {
+        let mut conns: Vec<ConnectionId> = self
+            .gates
+            .iter()
+            .filter(|((m, _, _), port)| *m == module || port.rcv_mod == module)
+            .map(|(_, port)| port.conn_id)
+            .collect();
+        conns.sort();
+        conns.dedup();
+        for conn in conns {
+            //the connections were just looked up, so they exist
+            self.disconnect(conn).unwrap();
+        }
+
+        self.declared_gates.remove(&module);
+        self.disconnected_ports.retain(|(m, _, _)| *m != module);
+        self.messages.retain(|tmsg| tmsg.recipient != module);
+        self.messages_now.retain(|tmsg| tmsg.recipient != module);
+    }
+
+    //the (module, gate, port) triples at both ends of a connection
+    pub fn endpoints(&self, conn: ConnectionId) -> Vec<(ModuleId, GateId, PortId)> {
+        self.gates
+            .iter()
+            .filter(|(_, port)| port.conn_id == conn)
+            .map(|(triple, _)| *triple)
+            .collect()
+    }
+
+    //both ends of every resolved path the connection is part of. For a connection that ends at a
+    //container these are the modules that actually send and receive over it
+    pub fn path_endpoints(&self, conn: ConnectionId) -> Vec<(ModuleId, GateId, PortId)> {
+        let mut ends = Vec::new();
+        for (key, path) in &self.paths {
+            if path.hops.contains(&conn) {
+                ends.push(*key);
+                ends.push((path.rcv_mod, path.rcv_gate, path.rcv_port));
+            }
+        }
+        ends
+    }
+
+    //the connections a message sent on this port passes through, in order
+    fn connections_on(&self, module: ModuleId, gate: GateId, port: PortId) -> Vec<ConnectionId> {
+        match self.paths.get(&(module, gate, port)) {
+            Some(path) => path.hops.clone(),
+            None => match self.gates.get(&(module, gate, port)) {
+                Some(p) => vec![p.conn_id],
+                None => Vec::new(),
+            },
+        }
+    }
+
+    //true if any connection a message sent on this port would pass through is still transmitting
+    pub fn is_busy(&self, module: ModuleId, gate: GateId, port: PortId, now: u64) -> bool {
+        self.connections_on(module, gate, port)
+            .iter()
+            .any(|id| self.connections.get(id).unwrap().is_busy(now))
+    }
+
+    //latest time at which a connection on the path from this port finishes its current transmission
+    pub fn transmission_finish_time(&self, module: ModuleId, gate: GateId, port: PortId) -> u64 {
+        self.connections_on(module, gate, port)
+            .iter()
+            .map(|id| self.connections.get(id).unwrap().transmission_finish_time())
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn send_message(
         &mut self,
-        msg: Box<Message>,
+        mut msg: Box<Message>,
         sender_mod_id: ModuleId,
         gate_id: GateId,
         port: PortId,
@@ -102,7 +381,13 @@ impl ConnectionMesh {
         let triple = (sender_mod_id, gate_id, port);
         let out_port = match self.gates.get(&triple) {
             Some(port) => port,
-            None => panic!("illegal port {}", port.0),
+            None => {
+                if self.disconnected_ports.contains(&triple) {
+                    self.dropped_disconnected += 1;
+                    return;
+                }
+                panic!("illegal port {}", port.0)
+            }
         };
 
         match out_port.kind {
@@ -113,29 +398,471 @@ impl ConnectionMesh {
             PortKind::InOut => { /* OK */ }
         }
 
-        let conn = self.connections.get_mut(&out_port.conn_id).unwrap();
-
-        match conn.handle_message(msg, ctx) {
-            Some((time, msg)) => {
-                if time == ctx.time.now() {
-                    self.messages_now.push_back(TimedMessage {
-                        time: time,
-                        msg: msg,
-                        recipient: out_port.rcv_mod,
-                        recp_gate: out_port.rcv_gate,
-                        recp_port: out_port.rcv_port,
-                    });
-                } else {
-                    self.messages.push(TimedMessage {
-                        time: time,
-                        msg: msg,
-                        recipient: out_port.rcv_mod,
-                        recp_gate: out_port.rcv_gate,
-                        recp_port: out_port.rcv_port,
-                    });
+        msg.meta_mut().stamp_send(sender_mod_id, ctx.time.now());
+
+        match self.paths.get(&triple) {
+            Some(_) => {
+                let now = ctx.time.now();
+                self.forward(triple, 0, now, msg, ctx);
+            }
+            None => {
+                let (rcv_mod, rcv_gate, rcv_port) =
+                    (out_port.rcv_mod, out_port.rcv_gate, out_port.rcv_port);
+                if self.links_down.contains(&out_port.conn_id) {
+                    *self.dropped_link_down.entry(out_port.conn_id).or_insert(0) += 1;
+                    return;
+                }
+                let conn = self.connections.get_mut(&out_port.conn_id).unwrap();
+                //connections draw from their own stream, not the one of the sending module
+                let mut conn_ctx = SimulationContext {
+                    time: ctx.time,
+                    id_reg: ctx.id_reg,
+                    stream: ctx.rngs.connection_stream(out_port.conn_id),
+                    rngs: ctx.rngs,
+                };
+
+                for (time, msg) in conn.handle_message(msg, &mut conn_ctx) {
+                    self.enqueue(
+                        time,
+                        msg,
+                        (rcv_mod, rcv_gate, rcv_port),
+                        ctx.time.now(),
+                        None,
+                    );
                 }
             }
-            None => {}
         }
     }
+
+    //lets the connections of a resolved path handle the message from hop on. Every connection
+    //handles it when it gets there, so connections with state (like the busy time of a datarate
+    //connection) see the messages in the order they arrive, also when paths share a connection.
+    //Messages wait in the message queue for the next hop, as if the containers in between would
+    //forward them. The same goes for the state of the links: a link that is down when the message
+    //reaches it drops the message
+    fn forward(
+        &mut self,
+        key: (ModuleId, GateId, PortId),
+        hop: usize,
+        time: u64,
+        msg: Box<dyn Message>,
+        ctx: &mut SimulationContext,
+    ) {
+        let now = ctx.time.now();
+        //connections may drop or duplicate messages, so there can be any number in flight
+        let mut in_flight = std::collections::VecDeque::new();
+        in_flight.push_back((hop, time, msg));
+
+        while let Some((hop, time, mut msg)) = in_flight.pop_front() {
+            let path = self.paths.get(&key).unwrap();
+            if hop == path.hops.len() {
+                let rcv = (path.rcv_mod, path.rcv_gate, path.rcv_port);
+                self.enqueue(time, msg, rcv, now, None);
+                continue;
+            }
+            if time > now {
+                let stop = path.stops[hop - 1];
+                let transit = Transit {
+                    path: key,
+                    hop: hop,
+                };
+                self.enqueue(time, msg, stop, now, Some(transit));
+                continue;
+            }
+
+            let conn_id = path.hops[hop];
+            if self.links_down.contains(&conn_id) {
+                *self.dropped_link_down.entry(conn_id).or_insert(0) += 1;
+                continue;
+            }
+            //count every connection as a hop, as if the containers in between had forwarded
+            //the message themselves
+            if hop > 0 {
+                let meta = msg.meta_mut();
+                meta.hop_count += 1;
+                meta.send_time = now;
+            }
+            let conn = self.connections.get_mut(&conn_id).unwrap();
+            let mut conn_ctx = SimulationContext {
+                time: ctx.time,
+                id_reg: ctx.id_reg,
+                stream: ctx.rngs.connection_stream(conn_id),
+                rngs: ctx.rngs,
+            };
+            for (time, msg) in conn.handle_message(msg, &mut conn_ctx) {
+                in_flight.push_back((hop + 1, time, msg));
+            }
+        }
+    }
+
+    //called by the runner when a message in transit reaches the next connection of its path.
+    //If the path changed since it was sent the message is handed back, to be delivered to the
+    //container it is passing through, which redirects it itself
+    pub fn continue_path(
+        &mut self,
+        mut tmsg: TimedMessage,
+        ctx: &mut SimulationContext,
+    ) -> Option<TimedMessage> {
+        let transit = tmsg.transit.take().unwrap();
+        let at = (tmsg.recipient, tmsg.recp_gate, tmsg.recp_port);
+        let still_there = match self.paths.get(&transit.path) {
+            Some(path) => {
+                transit.hop < path.hops.len() && path.stops.get(transit.hop - 1) == Some(&at)
+            }
+            None => false,
+        };
+        if !still_there {
+            return Some(tmsg);
+        }
+
+        let now = ctx.time.now();
+        self.forward(transit.path, transit.hop, now, tmsg.msg, ctx);
+        None
+    }
+
+    fn enqueue(
+        &mut self,
+        time: u64,
+        msg: Box<dyn Message>,
+        (rcv_mod, rcv_gate, rcv_port): (ModuleId, GateId, PortId),
+        now: u64,
+        transit: Option<Transit>,
+    ) {
+        let tmsg = TimedMessage {
+            time: time,
+            msg: msg,
+            recipient: rcv_mod,
+            recp_gate: rcv_gate,
+            recp_port: rcv_port,
+            transit: transit,
+        };
+
+        if time == now {
+            self.messages_now.push_back(tmsg);
+        } else {
+            self.messages.push(tmsg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::datarate_connection::{self, BusyPolicy};
+    use crate::core::connection::simple_connection;
+    use crate::core::modules::container::{self, ModuleContainer};
+    use crate::core::modules::echo_module::{self, EchoModule};
+    use crate::core::modules::module::Module;
+    use crate::core::modules::simple_module::{self, SimpleModule};
+    use crate::core::modules::sink::{self, Sink};
+    use crate::core::runner::{self, Runner};
+    use crate::core::testing::{new_test_env, TestEnv};
+
+    const OUTER: GateId = GateId(0);
+    const INNER: GateId = GateId(1);
+
+    fn setup(env: &mut TestEnv) -> Runner {
+        SimpleModule::register(&mut env.id_reg);
+        Sink::register(&mut env.id_reg);
+        EchoModule::register(&mut env.id_reg);
+        ModuleContainer::register(&mut env.id_reg);
+        simple_connection::register(&mut env.id_reg);
+        datarate_connection::register(&mut env.id_reg);
+        runner::new_runner([7; 16])
+    }
+
+    fn add(r: &mut Runner, module: Box<dyn Module>) -> ModuleId {
+        let id = module.module_id();
+        r.add_module(module).unwrap();
+        id
+    }
+
+    fn add_container(r: &mut Runner, env: &mut TestEnv) -> ModuleId {
+        let c =
+            container::new_module_container(&mut env.id_reg, "C".to_owned(), vec![(OUTER, INNER)]);
+        add(r, Box::new(c))
+    }
+
+    fn delay(env: &mut TestEnv, delay: u64) -> Box<dyn Connection> {
+        Box::new(simple_connection::new_simple_connection(
+            &mut env.id_reg,
+            delay,
+            0,
+            0,
+        ))
+    }
+
+    fn link(
+        r: &mut Runner,
+        conn: Box<dyn Connection>,
+        kind: ConnectionKind,
+        from: (ModuleId, GateId),
+        to: (ModuleId, GateId),
+    ) -> ConnectionId {
+        let id = conn.connection_id();
+        r.connect_modules(conn, kind, from.0, from.1, PortId(0), to.0, to.1, PortId(0))
+            .unwrap();
+        id
+    }
+
+    fn next(r: &mut Runner) -> TimedMessage {
+        r.connections.messages.pop().unwrap()
+    }
+
+    #[test]
+    fn every_hop_runs_when_the_message_gets_there() {
+        let mut env = new_test_env();
+        let mut r = setup(&mut env);
+        let a = add(
+            &mut r,
+            Box::new(simple_module::new_simple_module(
+                &mut env.id_reg,
+                "A".to_owned(),
+            )),
+        );
+        let s = add(
+            &mut r,
+            Box::new(sink::new_sink(&mut env.id_reg, "S".to_owned())),
+        );
+        let c = add_container(&mut r, &mut env);
+        let first = delay(&mut env, 10);
+        let first = link(
+            &mut r,
+            first,
+            ConnectionKind::Onedirectional,
+            (a, simple_module::OUT_GATE),
+            (c, OUTER),
+        );
+        let second = delay(&mut env, 20);
+        let second = link(
+            &mut r,
+            second,
+            ConnectionKind::Onedirectional,
+            (c, INNER),
+            (s, sink::IN_GATE),
+        );
+        r.resolve_paths();
+
+        let path = &r.connections.paths[&(a, simple_module::OUT_GATE, PortId(0))];
+        assert!(path.hops == vec![first, second]);
+        assert!(path.stops == vec![(c, OUTER, PortId(0)), (s, sink::IN_GATE, PortId(0))]);
+
+        let msg = env.msg(8);
+        r.connections
+            .send_message(msg, a, simple_module::OUT_GATE, PortId(0), &mut env.ctx());
+        let tmsg = next(&mut r);
+        assert_eq!(tmsg.time, 10);
+        assert!(tmsg.recipient == c && tmsg.transit.map(|t| t.hop) == Some(1));
+
+        env.set_time(10);
+        assert!(r.connections.continue_path(tmsg, &mut env.ctx()).is_none());
+        let tmsg = next(&mut r);
+        assert_eq!(tmsg.time, 30);
+        assert!(tmsg.recipient == s && tmsg.transit.is_none());
+        let meta = tmsg.msg.meta();
+        assert_eq!(
+            (meta.hop_count, meta.send_time, meta.creation_time),
+            (2, 10, Some(0))
+        );
+
+        //a link that is down when the message reaches it drops the message
+        let msg = env.msg(8);
+        r.connections
+            .send_message(msg, a, simple_module::OUT_GATE, PortId(0), &mut env.ctx());
+        r.connections.set_link_state(second, false).unwrap();
+        env.set_time(20);
+        let tmsg = next(&mut r);
+        assert!(r.connections.continue_path(tmsg, &mut env.ctx()).is_none());
+        assert!(r.connections.messages.is_empty());
+        assert_eq!(r.connections.dropped_link_down[&second], 1);
+    }
+
+    #[test]
+    fn a_changed_path_hands_the_message_to_the_container() {
+        let mut env = new_test_env();
+        let mut r = setup(&mut env);
+        let a = add(
+            &mut r,
+            Box::new(simple_module::new_simple_module(
+                &mut env.id_reg,
+                "A".to_owned(),
+            )),
+        );
+        let s = add(
+            &mut r,
+            Box::new(sink::new_sink(&mut env.id_reg, "S".to_owned())),
+        );
+        let c = add_container(&mut r, &mut env);
+        let first = delay(&mut env, 10);
+        link(
+            &mut r,
+            first,
+            ConnectionKind::Onedirectional,
+            (a, simple_module::OUT_GATE),
+            (c, OUTER),
+        );
+        let second = delay(&mut env, 20);
+        link(
+            &mut r,
+            second,
+            ConnectionKind::Onedirectional,
+            (c, INNER),
+            (s, sink::IN_GATE),
+        );
+        r.resolve_paths();
+
+        let msg = env.msg(8);
+        r.connections
+            .send_message(msg, a, simple_module::OUT_GATE, PortId(0), &mut env.ctx());
+        r.connections.paths.clear();
+        env.set_time(10);
+        let tmsg = next(&mut r);
+        let tmsg = r.connections.continue_path(tmsg, &mut env.ctx()).unwrap();
+        assert!(tmsg.recipient == c && tmsg.recp_gate == OUTER && tmsg.transit.is_none());
+    }
+
+    #[test]
+    fn cycles_open_mappings_and_receiving_ends_are_not_resolved() {
+        let mut env = new_test_env();
+        let mut r = setup(&mut env);
+
+        //two containers connected to each other in a circle
+        let c = add_container(&mut r, &mut env);
+        let d = add_container(&mut r, &mut env);
+        let conn = delay(&mut env, 1);
+        link(
+            &mut r,
+            conn,
+            ConnectionKind::Bidrectional,
+            (c, INNER),
+            (d, OUTER),
+        );
+        let conn = delay(&mut env, 1);
+        link(
+            &mut r,
+            conn,
+            ConnectionKind::Bidrectional,
+            (d, INNER),
+            (c, OUTER),
+        );
+
+        //the inner gate of the container is not connected
+        let a = add(
+            &mut r,
+            Box::new(simple_module::new_simple_module(
+                &mut env.id_reg,
+                "A".to_owned(),
+            )),
+        );
+        let e = add_container(&mut r, &mut env);
+        let conn = delay(&mut env, 1);
+        link(
+            &mut r,
+            conn,
+            ConnectionKind::Onedirectional,
+            (a, simple_module::OUT_GATE),
+            (e, OUTER),
+        );
+
+        //the inner gate of the container only receives
+        let b = add(
+            &mut r,
+            Box::new(simple_module::new_simple_module(
+                &mut env.id_reg,
+                "B".to_owned(),
+            )),
+        );
+        let x = add(
+            &mut r,
+            Box::new(simple_module::new_simple_module(
+                &mut env.id_reg,
+                "X".to_owned(),
+            )),
+        );
+        let f = add_container(&mut r, &mut env);
+        let conn = delay(&mut env, 1);
+        link(
+            &mut r,
+            conn,
+            ConnectionKind::Onedirectional,
+            (b, simple_module::OUT_GATE),
+            (f, OUTER),
+        );
+        let conn = delay(&mut env, 1);
+        link(
+            &mut r,
+            conn,
+            ConnectionKind::Onedirectional,
+            (x, simple_module::OUT_GATE),
+            (f, INNER),
+        );
+
+        r.resolve_paths();
+        assert!(r.connections.paths.is_empty());
+    }
+
+    //a link both ways through a container, both paths share the datarate connection behind it
+    #[test]
+    fn shared_connections_see_messages_in_arrival_order() {
+        let mut env = new_test_env();
+        let mut r = setup(&mut env);
+        let x = add(
+            &mut r,
+            Box::new(echo_module::new_echo_module(
+                &mut env.id_reg,
+                "X".to_owned(),
+            )),
+        );
+        let y = add(
+            &mut r,
+            Box::new(echo_module::new_echo_module(
+                &mut env.id_reg,
+                "Y".to_owned(),
+            )),
+        );
+        let c = add_container(&mut r, &mut env);
+        let conn = delay(&mut env, 100);
+        link(
+            &mut r,
+            conn,
+            ConnectionKind::Bidrectional,
+            (x, echo_module::IN_GATE),
+            (c, OUTER),
+        );
+        //1000 bits take 1000ns
+        let conn = datarate_connection::new_datarate_connection(
+            &mut env.id_reg,
+            1_000_000_000,
+            0,
+            BusyPolicy::Queue,
+        );
+        link(
+            &mut r,
+            Box::new(conn),
+            ConnectionKind::Bidrectional,
+            (c, INNER),
+            (y, echo_module::IN_GATE),
+        );
+        r.resolve_paths();
+        assert_eq!(r.connections.paths.len(), 2);
+
+        let msg = env.msg(1000);
+        r.connections
+            .send_message(msg, x, echo_module::IN_GATE, PortId(0), &mut env.ctx());
+        //the message of y reaches the datarate connection first, at 50 instead of 100
+        env.set_time(50);
+        let msg = env.msg(1000);
+        r.connections
+            .send_message(msg, y, echo_module::IN_GATE, PortId(0), &mut env.ctx());
+
+        env.set_time(100);
+        let tmsg = next(&mut r);
+        assert!(tmsg.time == 100 && tmsg.recipient == c);
+        assert!(r.connections.continue_path(tmsg, &mut env.ctx()).is_none());
+
+        let from_y = next(&mut r);
+        assert!(from_y.time == 1050 && from_y.recp_gate == INNER && from_y.transit.is_some());
+        let from_x = next(&mut r);
+        assert!(from_x.time == 2050 && from_x.recipient == y && from_x.transit.is_none());
+    }
 }
@@ -1,3 +1,8 @@
 pub mod connection;
+pub mod corruption;
+pub mod datarate_connection;
+pub mod duplicating_connection;
+pub mod loss;
 pub mod mesh;
+pub mod reordering_connection;
 pub mod simple_connection;
@@ -0,0 +1,128 @@
+use crate::core::connection::connection::Connection;
+use crate::core::contexts::SimulationContext;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{ConnectionId, ConnectionTypeId};
+use crate::core::messages::message::Message;
+use crate::core::random::distribution::{check_probability, Distribution};
+use rand::Rng;
+
+//Wraps another connection. Every message that comes out of the inner connection is held
+//back by extra_delay with the given probability, so messages sent after it can overtake it.
+pub struct ReorderingConnection {
+    pub id: ConnectionId,
+    pub type_id: ConnectionTypeId,
+
+    pub inner: Box<dyn Connection>,
+    pub probability: f64,
+    pub extra_delay: Distribution,
+
+    pub msgs_held_back: u64,
+}
+
+pub static TYPE_STR: &str = "ReorderingConnection";
+pub fn register(id_reg: &mut IdRegistrar) {
+    id_reg.register_type(TYPE_STR.to_owned());
+}
+//panics on a probability outside of [0, 1], see connection_factory for a generator that returns an Err
+pub fn new_reordering_connection(
+    id_reg: &mut IdRegistrar,
+    inner: Box<dyn Connection>,
+    probability: f64,
+    extra_delay: Distribution,
+) -> ReorderingConnection {
+    if let Err(e) = check_probability("Reordering probability", probability) {
+        panic!("{}", e);
+    }
+    ReorderingConnection {
+        id: id_reg.new_connection_id(),
+        type_id: id_reg.lookup_connection_id(TYPE_STR.to_owned()).unwrap(),
+        inner: inner,
+        probability: probability,
+        extra_delay: extra_delay,
+
+        msgs_held_back: 0,
+    }
+}
+
+impl Connection for ReorderingConnection {
+    fn handle_message(
+        &mut self,
+        message: Box<dyn Message>,
+        ctx: &mut SimulationContext,
+    ) -> Vec<(u64, Box<dyn Message>)> {
+        let mut out = self.inner.handle_message(message, ctx);
+
+        for (time, _) in &mut out {
+            if ctx.prng().gen::<f64>() < self.probability {
+                *time += self.extra_delay.sample_time(ctx.prng());
+                self.msgs_held_back += 1;
+            }
+        }
+
+        out
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        self.id
+    }
+    fn connection_type_id(&self) -> ConnectionTypeId {
+        self.type_id
+    }
+
+    fn is_busy(&self, now: u64) -> bool {
+        self.inner.is_busy(now)
+    }
+    fn transmission_finish_time(&self) -> u64 {
+        self.inner.transmission_finish_time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::simple_connection;
+    use crate::core::testing::new_test_env;
+
+    #[test]
+    fn held_back_messages_get_the_extra_delay() {
+        let mut env = new_test_env();
+        simple_connection::register(&mut env.id_reg);
+        register(&mut env.id_reg);
+        let inner = simple_connection::new_simple_connection(&mut env.id_reg, 100, 0, 0);
+        let mut conn = new_reordering_connection(
+            &mut env.id_reg,
+            Box::new(inner),
+            0.5,
+            Distribution::Constant(1000.0),
+        );
+
+        let mut held_back = 0;
+        for _ in 0..10000 {
+            let msg = env.msg(8);
+            let out = conn.handle_message(msg, &mut env.ctx());
+            assert_eq!(out.len(), 1);
+            match out[0].0 {
+                100 => {}
+                1100 => held_back += 1,
+                t => panic!("arrived at {}", t),
+            }
+        }
+        assert_eq!(conn.msgs_held_back, held_back);
+        assert!(held_back > 4800 && held_back < 5200, "{}", held_back);
+    }
+
+    #[test]
+    #[should_panic]
+    fn probabilities_above_one_are_rejected() {
+        let mut env = new_test_env();
+        simple_connection::register(&mut env.id_reg);
+        register(&mut env.id_reg);
+        let inner = simple_connection::new_simple_connection(&mut env.id_reg, 100, 0, 0);
+        new_reordering_connection(
+            &mut env.id_reg,
+            Box::new(inner),
+            1.5,
+            Distribution::Constant(0.0),
+        );
+    }
+}
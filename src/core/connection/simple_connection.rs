@@ -1,37 +1,62 @@
 use crate::core::connection::connection::Connection;
+use crate::core::connection::corruption::apply_bit_errors;
+use crate::core::connection::loss::{new_bernoulli_loss, LossModel, NoLoss};
 use crate::core::contexts::SimulationContext;
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{ConnectionId, ConnectionTypeId};
 use crate::core::messages::message::Message;
-use rand::RngCore;
+use crate::core::random::distribution::Distribution;
 
 pub struct SimpleConnection {
     pub buf: Vec<(u64, Box<Message>)>,
     pub id: ConnectionId,
     pub type_id: ConnectionTypeId,
 
-    pub delay: u64,
-    pub delay_max_add: u64,
+    pub delay: Distribution,
+    pub loss: Box<dyn LossModel>,
 
-    pub drop_chance: u64, // chance in percent * 100 (eg dropchance should be 50% ==> 5000)
+    //messages that are not lost get marked as corrupted based on this and their bit_length
+    pub bit_error_rate: f64,
 }
 
 pub static TYPE_STR: &str = "SimpleConnection";
 pub fn register(id_reg: &mut IdRegistrar) {
     id_reg.register_type(TYPE_STR.to_owned());
 }
+
+//delay is uniformly distributed in [delay, delay + delay_max_add)
+//drop_chance is in percent * 100 (eg dropchance should be 50% ==> 5000)
 pub fn new_simple_connection(
     id_reg: &mut IdRegistrar,
     delay: u64,
     delay_max_add: u64,
     drop_chance: u64,
+) -> SimpleConnection {
+    let delay = if delay_max_add > 0 {
+        Distribution::Uniform(delay as f64, (delay + delay_max_add) as f64)
+    } else {
+        Distribution::Constant(delay as f64)
+    };
+    let loss: Box<dyn LossModel> = if drop_chance > 0 {
+        Box::new(new_bernoulli_loss(drop_chance as f64 / 10000.0))
+    } else {
+        Box::new(NoLoss {})
+    };
+
+    new_distributed_connection(id_reg, delay, loss)
+}
+
+pub fn new_distributed_connection(
+    id_reg: &mut IdRegistrar,
+    delay: Distribution,
+    loss: Box<dyn LossModel>,
 ) -> SimpleConnection {
     SimpleConnection {
         id: id_reg.new_connection_id(),
         type_id: id_reg.lookup_connection_id(TYPE_STR.to_owned()).unwrap(),
         delay: delay,
-        delay_max_add: delay_max_add,
-        drop_chance: drop_chance,
+        loss: loss,
+        bit_error_rate: 0.0,
         buf: Vec::new(),
     }
 }
@@ -39,23 +64,15 @@ pub fn new_simple_connection(
 impl Connection for SimpleConnection {
     fn handle_message(
         &mut self,
-        message: Box<Message>,
+        mut message: Box<dyn Message>,
         ctx: &mut SimulationContext,
-    ) -> Option<(u64, Box<Message>)> {
-        if ctx.prng.next_u64() & 10000 < self.drop_chance {
-            return None;
+    ) -> Vec<(u64, Box<dyn Message>)> {
+        if self.loss.is_lost(message.as_ref(), ctx) {
+            return Vec::new();
         }
+        apply_bit_errors(message.as_mut(), self.bit_error_rate, ctx.prng());
 
-        Some((
-            ctx.time.now()
-                + self.delay
-                + if self.delay_max_add > 0 {
-                    ctx.prng.next_u64() % self.delay_max_add
-                } else {
-                    0
-                },
-            message,
-        ))
+        vec![(ctx.time.now() + self.delay.sample_time(ctx.prng()), message)]
     }
 
     fn connection_id(&self) -> ConnectionId {
@@ -65,3 +82,45 @@ impl Connection for SimpleConnection {
         self.type_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::loss::new_bernoulli_loss;
+    use crate::core::testing::new_test_env;
+
+    #[test]
+    fn delay_is_drawn_from_the_distribution() {
+        let mut env = new_test_env();
+        register(&mut env.id_reg);
+        let mut conn = new_simple_connection(&mut env.id_reg, 100, 50, 0);
+        env.set_time(1000);
+        for _ in 0..100 {
+            let m = env.msg(0);
+            let out = conn.handle_message(m, &mut env.ctx());
+            assert_eq!(out.len(), 1);
+            assert!(out[0].0 >= 1100 && out[0].0 <= 1150);
+        }
+    }
+
+    #[test]
+    fn lost_messages_are_not_delivered() {
+        let mut env = new_test_env();
+        register(&mut env.id_reg);
+        let mut lossy = new_distributed_connection(
+            &mut env.id_reg,
+            Distribution::Constant(5.0),
+            Box::new(new_bernoulli_loss(1.0)),
+        );
+        let m = env.msg(0);
+        assert!(lossy.handle_message(m, &mut env.ctx()).is_empty());
+
+        let mut lossless = new_distributed_connection(
+            &mut env.id_reg,
+            Distribution::Constant(5.0),
+            Box::new(new_bernoulli_loss(0.0)),
+        );
+        let m = env.msg(0);
+        assert_eq!(lossless.handle_message(m, &mut env.ctx())[0].0, 5);
+    }
+}
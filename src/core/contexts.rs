@@ -1,8 +1,14 @@
 use crate::core::clock::Clock;
+use crate::core::commands::Command;
+use crate::core::connection::connection::Connection;
+use crate::core::connection::mesh::{ConnectionKind, ConnectionMesh};
 use crate::core::events::event::TimerEvent;
+use crate::core::factory::module_factory::ModuleFactory;
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
-use crate::core::id_mngmnt::id_types::{GateId, PortId};
+use crate::core::id_mngmnt::id_types::{ConnectionId, GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
+use crate::core::random::streams::RngStreams;
+use crate::core::random::variates::Random;
 use std::collections::{BinaryHeap, VecDeque};
 
 pub struct EventHandleContext<'a> {
@@ -11,10 +17,138 @@ pub struct EventHandleContext<'a> {
     //output variables
     pub timer_queue: &'a mut BinaryHeap<TimerEvent>,
     pub msgs_to_send: &'a mut VecDeque<(Box<Message>, GateId, PortId)>,
+    pub commands: &'a mut VecDeque<Command>,
+
+    //read-only view on the connections, eg to check if an out-going channel is busy
+    pub mesh: &'a ConnectionMesh,
+    //used to create modules at runtime
+    pub factory: &'a ModuleFactory,
+}
+
+impl<'a> EventHandleContext<'a> {
+    //random variates drawn from the stream of the module that is handled,
+    //eg: ctx.random().exponential(10.0 * clock::MILLI_SECONDS as f64)
+    pub fn random(&mut self) -> Random<'_> {
+        Random {
+            rng: self.mctx.prng(),
+        }
+    }
+
+    //create a module with the factory. It is added (and initialized) after the current handler returned
+    pub fn create_module(
+        &mut self,
+        type_id: ModuleTypeId,
+        parameters: &std::collections::HashMap<String, String>,
+        parent: Option<ModuleId>,
+    ) -> Result<ModuleId, Box<dyn std::error::Error>> {
+        let module = self
+            .factory
+            .generate(self.mctx.id_reg, type_id, parameters)?;
+        let id = module.module_id();
+        self.commands.push_back(Command::AddModule {
+            module: module,
+            parent: parent,
+        });
+        Ok(id)
+    }
+
+    pub fn delete_module(&mut self, module: ModuleId) {
+        self.commands
+            .push_back(Command::DeleteModule { module: module });
+    }
+
+    pub fn connect_modules(
+        &mut self,
+        conn: Box<dyn Connection>,
+        kind: ConnectionKind,
+        mod_out: ModuleId,
+        gate_out: GateId,
+        out_port: PortId,
+        mod_in: ModuleId,
+        gate_in: GateId,
+        in_port: PortId,
+    ) {
+        self.commands.push_back(Command::Connect {
+            conn: conn,
+            kind: kind,
+            mod_out: mod_out,
+            gate_out: gate_out,
+            out_port: out_port,
+            mod_in: mod_in,
+            gate_in: gate_in,
+            in_port: in_port,
+        });
+    }
+
+    //remove the connection behind this port of the module
+    pub fn disconnect(
+        &mut self,
+        module: ModuleId,
+        gate: GateId,
+        port: PortId,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let conn = self.conn_at(module, gate, port)?;
+        self.commands.push_back(Command::Disconnect { conn: conn });
+        Ok(())
+    }
+
+    fn conn_at(
+        &self,
+        module: ModuleId,
+        gate: GateId,
+        port: PortId,
+    ) -> Result<ConnectionId, Box<dyn std::error::Error>> {
+        match self.mesh.gates.get(&(module, gate, port)) {
+            Some(p) => Ok(p.conn_id),
+            None => Err(format!(
+                "Port {} of gate {} of module {} is not connected",
+                port.0,
+                gate.0,
+                module.raw()
+            )
+            .into()),
+        }
+    }
+
+    //is the channel behind this port of the module still transmitting
+    pub fn is_busy(&self, module: ModuleId, gate: GateId, port: PortId) -> bool {
+        self.mesh.is_busy(module, gate, port, self.mctx.time.now())
+    }
+
+    pub fn transmission_finish_time(&self, module: ModuleId, gate: GateId, port: PortId) -> u64 {
+        self.mesh.transmission_finish_time(module, gate, port)
+    }
+
+    //take the connection behind this port of the module down or bring it back up
+    pub fn set_link_state(
+        &mut self,
+        module: ModuleId,
+        gate: GateId,
+        port: PortId,
+        up: bool,
+        notify: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let conn = self.conn_at(module, gate, port)?;
+        self.commands.push_back(Command::SetLinkState {
+            conn: conn,
+            up: up,
+            notify: notify,
+        });
+        Ok(())
+    }
 }
 
 pub struct SimulationContext<'a> {
     pub time: &'a Clock,
     pub id_reg: &'a mut IdRegistrar,
-    pub prng: &'a mut rand::prng::XorShiftRng,
+
+    //all random number streams and the one the current module or connection draws from
+    pub rngs: &'a mut RngStreams,
+    pub stream: usize,
+}
+
+impl<'a> SimulationContext<'a> {
+    pub fn prng(&mut self) -> &mut rand::prng::XorShiftRng {
+        self.rngs.get(self.stream)
+    }
 }
@@ -0,0 +1,103 @@
+use crate::core::events::event::Event;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{EventsId, EventsTypeId};
+use std::any::Any;
+
+//Generic event that carries any user struct as payload. The event type is registered
+//under the name of the payload type, so no TYPE_STR/register/Event impl is needed per type.
+//
+//ctx.timer_queue.push(TimerEvent {
+//    time: ctx.mctx.time.now() + 10,
+//    mod_id: self.id,
+//    event: Box::new(ev::new_ev(ctx.mctx.id_reg, Wakeup {})),
+//});
+//...
+//if let Some(wakeup) = ev.downcast_ref::<Wakeup>() { ... }
+//or, for an owned Box<dyn Event>, ev.downcast::<Wakeup>() like Msg::downcast
+pub struct Ev<T> {
+    pub id: EventsId,
+    pub type_id: EventsTypeId,
+    pub payload: T,
+}
+
+pub fn type_str<T: 'static>() -> &'static str {
+    std::any::type_name::<T>()
+}
+
+pub fn register<T: 'static>(id_reg: &mut IdRegistrar) -> EventsTypeId {
+    EventsTypeId(id_reg.register_rust_type::<T>())
+}
+
+pub fn new_ev<T: 'static>(id_reg: &mut IdRegistrar, payload: T) -> Ev<T> {
+    Ev {
+        id: id_reg.new_event_id(),
+        //registering is idempotent, so this registers the type on first use
+        type_id: register::<T>(id_reg),
+        payload: payload,
+    }
+}
+
+impl<T: 'static> Event for Ev<T> {
+    fn event_type_id(&self) -> EventsTypeId {
+        self.type_id
+    }
+    fn event_id(&self) -> EventsId {
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl<'a> dyn Event + 'a {
+    pub fn is<T: 'static>(&self) -> bool {
+        self.as_any().is::<Ev<T>>()
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&Ev<T>> {
+        self.as_any().downcast_ref::<Ev<T>>()
+    }
+
+    //hands back the event unchanged if it does not carry a T, so the next type can be tried
+    pub fn downcast<T: 'static>(self: Box<Self>) -> Result<Box<Ev<T>>, Box<dyn Event + 'a>> {
+        if self.is::<T>() {
+            Ok(self.into_any().downcast::<Ev<T>>().unwrap())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    struct Wakeup {
+        nr: u64,
+    }
+
+    #[test]
+    fn downcast_by_reference_and_by_value() {
+        let mut env = new_test_env();
+        let ev: Box<dyn Event> = Box::new(new_ev(&mut env.id_reg, Wakeup { nr: 3 }));
+        assert!(ev.is::<Wakeup>() && !ev.is::<u64>());
+        assert_eq!(ev.downcast_ref::<Wakeup>().unwrap().payload.nr, 3);
+
+        let ev = ev.downcast::<u64>().err().unwrap();
+        assert_eq!(ev.downcast::<Wakeup>().ok().unwrap().payload.nr, 3);
+    }
+
+    #[test]
+    fn payload_types_are_registered_once() {
+        let mut env = new_test_env();
+        let first = new_ev(&mut env.id_reg, Wakeup { nr: 0 });
+        let second = new_ev(&mut env.id_reg, Wakeup { nr: 1 });
+        assert!(first.type_id == second.type_id);
+        assert!(first.id != second.id);
+        assert_eq!(env.id_reg.rust_type_ids.len(), 1);
+    }
+}
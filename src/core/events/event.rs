@@ -7,7 +7,9 @@ pub trait Event {
 
     //To downcast use this:
     //let tev: &TextEvent = event.as_any().downcast_ref::<TextEvent>().unwrap();
+    //or for events::ev::Ev<T> the helpers ev.downcast_ref::<T>() and ev.downcast::<T>()
     fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
 }
 
 pub struct TimerEvent {
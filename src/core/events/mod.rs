@@ -1,2 +1,3 @@
+pub mod ev;
 pub mod event;
 pub mod text_event;
@@ -0,0 +1,251 @@
+use crate::core::clock;
+use crate::core::connection::connection::Connection;
+use crate::core::connection::datarate_connection::{self, BusyPolicy};
+use crate::core::connection::duplicating_connection;
+use crate::core::connection::loss::{parse_loss, LossModel, NoLoss};
+use crate::core::connection::reordering_connection;
+use crate::core::connection::simple_connection;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::random::distribution::{check_probability, Distribution};
+use std::collections::HashMap;
+
+//Connections from string parameters, eg from a config file. Unlike the constructors, which panic
+//on values that make no sense, these check the parameters and return an Err.
+//Unknown parameters are an error as well, so typos don't go unnoticed
+
+pub type ConnectionGeneratorResult = Result<Box<dyn Connection>, Box<dyn std::error::Error>>;
+
+fn check_known(
+    kind: &str,
+    parameters: &HashMap<String, String>,
+    known: &[&str],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut keys: Vec<&String> = parameters.keys().collect();
+    keys.sort();
+    for key in keys {
+        if !known.contains(&key.as_str()) {
+            return Err(format!("A {} has no parameter {}", kind, key).into());
+        }
+    }
+    Ok(())
+}
+
+//delay is a distribution like "uniform(1ms, 2ms)" and loss a loss model like "bernoulli(0.01)",
+//see loss::parse_loss. Both are optional, without them messages arrive at once and are not lost
+pub fn simple_connection_from_params(
+    id_reg: &mut IdRegistrar,
+    parameters: &HashMap<String, String>,
+) -> ConnectionGeneratorResult {
+    check_known("SimpleConnection", parameters, &["delay", "loss"])?;
+    let delay = match parameters.get("delay") {
+        Some(delay) => delay.parse()?,
+        None => Distribution::Constant(0.0),
+    };
+    let loss: Box<dyn LossModel> = match parameters.get("loss") {
+        Some(loss) => parse_loss(loss)?,
+        None => Box::new(NoLoss {}),
+    };
+
+    Ok(Box::new(simple_connection::new_distributed_connection(
+        id_reg, delay, loss,
+    )))
+}
+
+//probability and extra_delay of a wrapping connection, extra_delay defaults to no delay
+fn wrapper_params(
+    kind: &str,
+    parameters: &HashMap<String, String>,
+) -> Result<(f64, Distribution), Box<dyn std::error::Error>> {
+    check_known(kind, parameters, &["probability", "extra_delay"])?;
+    let probability: f64 = match parameters.get("probability") {
+        Some(p) => p.trim().parse()?,
+        None => return Err(format!("A {} needs a probability", kind).into()),
+    };
+    check_probability("probability", probability)?;
+    let extra_delay = match parameters.get("extra_delay") {
+        Some(delay) => delay.parse()?,
+        None => Distribution::Constant(0.0),
+    };
+    Ok((probability, extra_delay))
+}
+
+//wraps inner, needs a probability, extra_delay is a distribution like "constant(1ms)"
+pub fn duplicating_connection_from_params(
+    id_reg: &mut IdRegistrar,
+    inner: Box<dyn Connection>,
+    parameters: &HashMap<String, String>,
+) -> ConnectionGeneratorResult {
+    let (probability, extra_delay) = wrapper_params("DuplicatingConnection", parameters)?;
+    Ok(Box::new(
+        duplicating_connection::new_duplicating_connection(id_reg, inner, probability, extra_delay),
+    ))
+}
+
+//wraps inner, needs a probability, extra_delay is a distribution like "uniform(1ms, 5ms)"
+pub fn reordering_connection_from_params(
+    id_reg: &mut IdRegistrar,
+    inner: Box<dyn Connection>,
+    parameters: &HashMap<String, String>,
+) -> ConnectionGeneratorResult {
+    let (probability, extra_delay) = wrapper_params("ReorderingConnection", parameters)?;
+    Ok(Box::new(reordering_connection::new_reordering_connection(
+        id_reg,
+        inner,
+        probability,
+        extra_delay,
+    )))
+}
+
+//needs datarate in bits per second. delay (a time), busy ("queue" or "reject") and queue_limit
+//are optional, see DatarateConnection
+pub fn datarate_connection_from_params(
+    id_reg: &mut IdRegistrar,
+    parameters: &HashMap<String, String>,
+) -> ConnectionGeneratorResult {
+    check_known(
+        "DatarateConnection",
+        parameters,
+        &["datarate", "delay", "busy", "queue_limit"],
+    )?;
+    let datarate: u64 = match parameters.get("datarate") {
+        Some(rate) => rate.trim().parse()?,
+        None => return Err("A DatarateConnection needs a datarate".into()),
+    };
+    if datarate == 0 {
+        return Err("A DatarateConnection needs a datarate > 0".into());
+    }
+    let delay = match parameters.get("delay") {
+        Some(delay) => clock::parse_time(delay)?,
+        None => 0,
+    };
+    let busy_policy = match parameters.get("busy").map(|b| b.trim()) {
+        None | Some("queue") => BusyPolicy::Queue,
+        Some("reject") => BusyPolicy::Reject,
+        Some(other) => {
+            return Err(format!("busy has to be queue or reject, was: {}", other).into());
+        }
+    };
+
+    let mut conn =
+        datarate_connection::new_datarate_connection(id_reg, datarate, delay, busy_policy);
+    if let Some(limit) = parameters.get("queue_limit") {
+        conn.queue_limit = Some(limit.trim().parse()?);
+    }
+    Ok(Box::new(conn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn simple_connections_are_checked() {
+        let mut env = new_test_env();
+        simple_connection::register(&mut env.id_reg);
+        let mut conn = simple_connection_from_params(
+            &mut env.id_reg,
+            &params(&[("delay", "2ms"), ("loss", "bernoulli(0)")]),
+        )
+        .unwrap();
+        let msg = env.msg(8);
+        assert_eq!(conn.handle_message(msg, &mut env.ctx())[0].0, 2_000_000);
+        assert!(simple_connection_from_params(&mut env.id_reg, &params(&[])).is_ok());
+
+        for bad in &[
+            vec![("loss", "bernoulli(NaN)")],
+            vec![("loss", "bernoulli(2)")],
+            vec![("delay", "exponential(-1ms)")],
+            vec![("jitter", "1ms")],
+        ] {
+            assert!(
+                simple_connection_from_params(&mut env.id_reg, &params(bad)).is_err(),
+                "{:?} was accepted",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn wrapping_connections_are_checked() {
+        let mut env = new_test_env();
+        simple_connection::register(&mut env.id_reg);
+        duplicating_connection::register(&mut env.id_reg);
+        reordering_connection::register(&mut env.id_reg);
+
+        for bad in &[
+            vec![("probability", "NaN")],
+            vec![("probability", "1.01")],
+            vec![("probability", "-0.5")],
+            vec![],
+            vec![("probability", "0.5"), ("extra_delay", "uniform(2ms, 1ms)")],
+            vec![("probability", "0.5"), ("delay", "1ms")],
+        ] {
+            let inner = simple_connection_from_params(&mut env.id_reg, &params(&[])).unwrap();
+            assert!(
+                duplicating_connection_from_params(&mut env.id_reg, inner, &params(bad)).is_err(),
+                "{:?} was accepted",
+                bad
+            );
+            let inner = simple_connection_from_params(&mut env.id_reg, &params(&[])).unwrap();
+            assert!(
+                reordering_connection_from_params(&mut env.id_reg, inner, &params(bad)).is_err(),
+                "{:?} was accepted",
+                bad
+            );
+        }
+
+        let inner = simple_connection_from_params(&mut env.id_reg, &params(&[])).unwrap();
+        let mut conn = duplicating_connection_from_params(
+            &mut env.id_reg,
+            inner,
+            &params(&[("probability", "1"), ("extra_delay", "1ms")]),
+        )
+        .unwrap();
+        let msg = env.msg(8);
+        let out = conn.handle_message(msg, &mut env.ctx());
+        assert_eq!(
+            out.iter().map(|(t, _)| *t).collect::<Vec<_>>(),
+            vec![0, 1_000_000]
+        );
+    }
+
+    #[test]
+    fn datarate_connections_are_checked() {
+        let mut env = new_test_env();
+        datarate_connection::register(&mut env.id_reg);
+        let gen = |env: &mut crate::core::testing::TestEnv, pairs: &[(&str, &str)]| {
+            datarate_connection_from_params(&mut env.id_reg, &params(pairs))
+        };
+
+        let conn = gen(&mut env, &[("datarate", "1000000"), ("delay", "1ms")]).unwrap();
+        assert_eq!(conn.transmission_finish_time(), 0);
+        assert!(gen(
+            &mut env,
+            &[
+                ("datarate", "1000"),
+                ("busy", "reject"),
+                ("queue_limit", "3")
+            ]
+        )
+        .is_ok());
+
+        for bad in &[
+            vec![("datarate", "0")],
+            vec![],
+            vec![("datarate", "-5")],
+            vec![("datarate", "1000"), ("busy", "sometimes")],
+            vec![("datarate", "1000"), ("delay", "-1ms")],
+            vec![("datarate", "1000"), ("datarat", "1000")],
+        ] {
+            assert!(gen(&mut env, bad).is_err(), "{:?} was accepted", bad);
+        }
+    }
+}
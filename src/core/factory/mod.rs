@@ -1 +1,2 @@
+pub mod connection_factory;
 pub mod module_factory;
@@ -3,53 +3,194 @@
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleTypeId};
 use crate::core::modules::container;
+use crate::core::modules::echo_module;
 use crate::core::modules::module::Module;
+use crate::core::modules::sink;
+use crate::core::modules::source;
+use crate::core::modules::trace_source;
+use crate::core::random::distribution::parse_unitless;
 use std::collections::HashMap;
 
+//needs gates as "outer>inner,outer>inner", name is optional
 pub fn container_from_params(
     id_reg: &mut IdRegistrar,
-    parameters: &HashMap<&str, &str>,
+    parameters: &HashMap<String, String>,
 ) -> GeneratorResult {
-    let name = (*parameters.get(&"name").unwrap()).to_owned();
-    let gates = parameters
-        .get(&"gates")
-        .unwrap()
-        .split(",")
-        .map(|gate_tuple| {
-            let gates: Vec<&str> = gate_tuple.split(">").collect();
-            let outer: u64 = gates[0].parse().unwrap();
-            let inner: u64 = gates[1].parse().unwrap();
-            (GateId(outer), GateId(inner))
-        })
-        .collect();
+    let name = match parameters.get("name") {
+        Some(name) => name.clone(),
+        None => "Container".to_owned(),
+    };
+    let gates = match parameters.get("gates") {
+        Some(gates) => gates,
+        None => return Err("A container needs gates".into()),
+    };
+    let mut pairs = Vec::new();
+    for gate_tuple in gates.split(",") {
+        let gates: Vec<&str> = gate_tuple.split(">").collect();
+        if gates.len() != 2 {
+            return Err(format!("expected outer>inner but found: {}", gate_tuple).into());
+        }
+        let outer: u64 = gates[0].trim().parse()?;
+        let inner: u64 = gates[1].trim().parse()?;
+        pairs.push((GateId(outer), GateId(inner)));
+    }
 
     Ok(Box::new(container::new_module_container(
-        id_reg, name, gates,
+        id_reg, name, pairs,
     )))
 }
 
-type GeneratorFunction =
+//name is optional, the other parameters are passed to Sink::set_parameter
+pub fn sink_from_params(
+    id_reg: &mut IdRegistrar,
+    parameters: &HashMap<String, String>,
+) -> GeneratorResult {
+    let name = match parameters.get("name") {
+        Some(name) => name.clone(),
+        None => "Sink".to_owned(),
+    };
+
+    let mut sink = sink::new_sink(id_reg, name);
+    set_parameters(&mut sink, parameters, &["name"])?;
+    Ok(Box::new(sink))
+}
+
+//name is optional
+pub fn echo_from_params(
+    id_reg: &mut IdRegistrar,
+    parameters: &HashMap<String, String>,
+) -> GeneratorResult {
+    let name = match parameters.get("name") {
+        Some(name) => name.clone(),
+        None => "Echo".to_owned(),
+    };
+
+    let mut echo = echo_module::new_echo_module(id_reg, name);
+    set_parameters(&mut echo, parameters, &["name"])?;
+    Ok(Box::new(echo))
+}
+
+//hands the parameters the generator did not use itself to set_parameter, in a fixed order
+fn set_parameters(
+    module: &mut dyn Module,
+    parameters: &HashMap<String, String>,
+    used: &[&str],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut keys: Vec<&String> = parameters.keys().collect();
+    keys.sort();
+    for key in keys {
+        if !used.contains(&key.as_str()) {
+            module.set_parameter(key, &parameters[key])?;
+        }
+    }
+    Ok(())
+}
+
+//needs interarrival and size, the other parameters are optional. See Source::set_parameter
+pub fn source_from_params(
+    id_reg: &mut IdRegistrar,
+    parameters: &HashMap<String, String>,
+) -> GeneratorResult {
+    let name = match parameters.get("name") {
+        Some(name) => name.clone(),
+        None => "Source".to_owned(),
+    };
+    let interarrival = match parameters.get("interarrival") {
+        Some(d) => d.parse()?,
+        None => return Err("A source needs an interarrival distribution".into()),
+    };
+    let size = match parameters.get("size") {
+        Some(d) => parse_unitless(d)?,
+        None => return Err("A source needs a size distribution".into()),
+    };
+
+    let mut src = source::new_source(id_reg, name, interarrival, size);
+    set_parameters(&mut src, parameters, &["name", "interarrival", "size"])?;
+
+    Ok(Box::new(src))
+}
+
+//needs the file to replay, offset is optional
+pub fn trace_source_from_params(
+    id_reg: &mut IdRegistrar,
+    parameters: &HashMap<String, String>,
+) -> GeneratorResult {
+    let name = match parameters.get("name") {
+        Some(name) => name.clone(),
+        None => "TraceSource".to_owned(),
+    };
+    let path = match parameters.get("file") {
+        Some(path) => path,
+        None => return Err("A trace source needs a file".into()),
+    };
+
+    let mut src = trace_source::new_trace_source(id_reg, name, path)?;
+    if let Some(offset) = parameters.get("offset") {
+        src.set_parameter("offset", offset)?;
+    }
+
+    Ok(Box::new(src))
+}
+
+pub type GeneratorFunction =
     fn(id_reg: &mut IdRegistrar, parameters: &HashMap<String, String>) -> GeneratorResult;
-type GeneratorResult = Result<Box<Module>, Box<std::error::Error>>;
+pub type GeneratorResult = Result<Box<Module>, Box<std::error::Error>>;
 
 pub struct ModuleFactory {
     generators: HashMap<ModuleTypeId, GeneratorFunction>,
+
+    //generators for the module types of this crate by their type string. Type ids are only known
+    //once the types are registered, so these are looked up when a type has no generator by id
+    builtin: HashMap<&'static str, GeneratorFunction>,
 }
 
 pub fn new() -> ModuleFactory {
+    let mut builtin: HashMap<&'static str, GeneratorFunction> = HashMap::new();
+    builtin.insert(container::ModuleContainer::TYPE_STR, container_from_params);
+    builtin.insert(sink::Sink::TYPE_STR, sink_from_params);
+    builtin.insert(echo_module::EchoModule::TYPE_STR, echo_from_params);
+    builtin.insert(source::Source::TYPE_STR, source_from_params);
+    builtin.insert(
+        trace_source::TraceSource::TYPE_STR,
+        trace_source_from_params,
+    );
+
     ModuleFactory {
         generators: HashMap::new(),
+        builtin: builtin,
     }
 }
 
 impl ModuleFactory {
+    //the type id of a module type by its type string. Built-in types are registered on first use,
+    //so scripts can create them without the simulation registering them up front
+    pub fn type_id(&self, id_reg: &mut IdRegistrar, type_str: &str) -> Option<ModuleTypeId> {
+        if self.builtin.contains_key(type_str) {
+            return Some(ModuleTypeId(id_reg.register_type(type_str.to_owned())));
+        }
+        id_reg.lookup_module_id(type_str.to_owned())
+    }
+
+    pub fn add_generator(&mut self, id: ModuleTypeId, gen: GeneratorFunction) {
+        self.generators.insert(id, gen);
+    }
+
     pub fn generate(
         &self,
         id_reg: &mut IdRegistrar,
         id: ModuleTypeId,
         parameters: &HashMap<String, String>,
     ) -> GeneratorResult {
-        let gen = self.generators.get(&id).unwrap();
-        gen(id_reg, parameters)
+        let gen = match self.generators.get(&id) {
+            Some(gen) => Some(*gen),
+            None => match id_reg.lookup_id_reverse(id.0) {
+                Some(type_str) => self.builtin.get(type_str.as_str()).cloned(),
+                None => None,
+            },
+        };
+        match gen {
+            Some(gen) => gen(id_reg, parameters),
+            None => Err(format!("No generator for module type {}", id.0).into()),
+        }
     }
 }
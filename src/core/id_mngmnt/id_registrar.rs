@@ -5,6 +5,9 @@ pub struct IdRegistrar {
     pub last_type_id: u64,
     pub type_ids: std::collections::HashMap<String, u64>,
     pub type_ids_reverse: std::collections::HashMap<u64, String>,
+    //type ids of payload types of msg::Msg and ev::Ev, so they are not looked up by name for
+    //every message
+    pub rust_type_ids: std::collections::HashMap<std::any::TypeId, u64>,
 }
 
 impl IdRegistrar {
@@ -33,6 +36,17 @@ impl IdRegistrar {
         new_id
     }
 
+    //registers T under its type name, see register_type
+    pub fn register_rust_type<T: 'static>(&mut self) -> u64 {
+        let key = std::any::TypeId::of::<T>();
+        if let Some(id) = self.rust_type_ids.get(&key) {
+            return *id;
+        }
+        let id = self.register_type(std::any::type_name::<T>().to_owned());
+        self.rust_type_ids.insert(key, id);
+        id
+    }
+
     pub fn lookup_id(&mut self, type_id: String) -> Option<&u64> {
         self.type_ids.get(&type_id)
     }
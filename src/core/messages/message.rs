@@ -11,7 +11,89 @@ pub trait Message {
 
     //To downcast use this:
     //let tev: &TextMsg = event.as_any().downcast_ref::<TextMsg>().unwrap();
+    //or for messages::msg::Msg<T> the helpers msg.downcast::<T>() and msg.downcast_ref::<T>()
     fn as_any(&self) -> &dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+
+    //copy of the message, including id and meta data. Needed by connections that duplicate messages
+    fn clone_msg(&self) -> Box<dyn Message>;
+
+    //bookkeeping data that is maintained by the connection mesh and the runner
+    fn meta(&self) -> &MessageMeta;
+    fn meta_mut(&mut self) -> &mut MessageMeta;
+}
+
+#[derive(Clone)]
+pub struct MessageMeta {
+    //time the message was first sent, unless the creator set it already
+    pub creation_time: Option<u64>,
+    //time the message was last handed to a connection
+    pub send_time: u64,
+    //time the message was last delivered to a module
+    pub arrival_time: u64,
+    //how many connections this message has been sent over
+    pub hop_count: u64,
+    //the module that sent this message first
+    pub source: Option<ModuleId>,
+
+    //size of the message in bits, set by whoever creates the message.
+    //Used by connections that model transmission delay
+    pub bit_length: u64,
+
+    //set by connections with a bit error rate. The message is still delivered,
+    //it is up to the receiver to check this (eg to model a checksum)
+    pub corrupted: bool,
+
+    //explicit congestion notification. Queues with active queue management set ecn_ce instead of
+    //dropping messages that are ecn_capable
+    pub ecn_capable: bool,
+    pub ecn_ce: bool,
+
+    //pushed by a Fork on all copies of a message, (fork, number of the forked message). A Join puts
+    //the copies back together by the last token and pops it, the message id is no good for that
+    //since copies made by connections share it as well. Being a stack, nested Forks and Joins work
+    pub fork_token: Vec<(ModuleId, u64)>,
+}
+
+pub fn new_meta() -> MessageMeta {
+    MessageMeta {
+        creation_time: None,
+        send_time: 0,
+        arrival_time: 0,
+        hop_count: 0,
+        source: None,
+        bit_length: 0,
+        corrupted: false,
+        ecn_capable: false,
+        ecn_ce: false,
+        fork_token: Vec::new(),
+    }
+}
+
+impl MessageMeta {
+    //called by the connection mesh each time the message is sent
+    pub fn stamp_send(&mut self, sender: ModuleId, now: u64) {
+        if self.source.is_none() {
+            self.source = Some(sender);
+        }
+        if self.creation_time.is_none() {
+            self.creation_time = Some(now);
+        }
+        self.send_time = now;
+        self.hop_count += 1;
+    }
+
+    //None for messages that were never sent nor given a creation time. A creation time after the
+    //arrival counts as no delay
+    pub fn end_to_end_delay(&self) -> Option<u64> {
+        self.creation_time
+            .map(|created| self.arrival_time.saturating_sub(created))
+    }
+
+    //bit_length in whole bytes, a started byte counts as a full one
+    pub fn byte_length(&self) -> u64 {
+        self.bit_length.div_ceil(8)
+    }
 }
 
 pub struct TimedMessage {
@@ -20,6 +102,17 @@ pub struct TimedMessage {
     pub recipient: ModuleId,
     pub recp_port: PortId,
     pub recp_gate: GateId,
+
+    //set while the message is on its way along a resolved path of the connection mesh. The
+    //recipient is then the container it passes through, see ConnectionMesh::continue_path
+    pub transit: Option<Transit>,
+}
+
+//the message reaches connection hop of the path that starts at this out-going port
+#[derive(Copy, Clone)]
+pub struct Transit {
+    pub path: (ModuleId, GateId, PortId),
+    pub hop: usize,
 }
 
 impl Ord for TimedMessage {
@@ -46,3 +139,45 @@ impl PartialEq for TimedMessage {
 }
 
 impl Eq for TimedMessage {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_length_rounds_up() {
+        let mut meta = new_meta();
+        for (bits, bytes) in &[(0, 0), (1, 1), (8, 1), (9, 2), (12000, 1500)] {
+            meta.bit_length = *bits;
+            assert_eq!(meta.byte_length(), *bytes);
+        }
+    }
+
+    #[test]
+    fn stamp_send_counts_hops_and_keeps_the_origin() {
+        let mut meta = new_meta();
+        assert_eq!(meta.end_to_end_delay(), None);
+
+        meta.stamp_send(ModuleId(3), 100);
+        meta.stamp_send(ModuleId(4), 250);
+        assert_eq!(meta.hop_count, 2);
+        assert_eq!(meta.source.map(|s| s.raw()), Some(3));
+        assert_eq!((meta.creation_time, meta.send_time), (Some(100), 250));
+
+        meta.arrival_time = 300;
+        assert_eq!(meta.end_to_end_delay(), Some(200));
+    }
+
+    #[test]
+    fn creation_time_set_by_the_creator_is_kept() {
+        let mut meta = new_meta();
+        meta.creation_time = Some(40);
+        meta.stamp_send(ModuleId(3), 100);
+        assert_eq!(meta.creation_time, Some(40));
+
+        //a creation time in the future is no delay and does not underflow
+        meta.creation_time = Some(500);
+        meta.arrival_time = 300;
+        assert_eq!(meta.end_to_end_delay(), Some(0));
+    }
+}
@@ -1,2 +1,4 @@
 pub mod message;
+pub mod msg;
+pub mod packet;
 pub mod text_message;
@@ -0,0 +1,126 @@
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{MessageId, MessageTypeId};
+use crate::core::messages::message::{new_meta, Message, MessageMeta};
+use std::any::Any;
+
+//Generic message that carries a user struct as payload. The message type is registered
+//under the name of the payload type, so no TYPE_STR/register/Message impl is needed per type.
+//
+//The payload has to implement Clone: Message::clone_msg is what duplicating connections use to
+//copy messages, so Msg<T> is only a Message if T: Clone. Payloads that can not be cloned
+//(file handles, channels, ..) can be wrapped in an Rc/Arc, or get their own Message impl.
+//
+//let msg = Box::new(msg::new_msg(ctx.mctx.id_reg, MyPayload { .. }));
+//...
+//match msg.downcast::<MyPayload>() {
+//    Ok(my_msg) => println!("{}", my_msg.payload.field),
+//    Err(msg) => { /* some other message type */ }
+//}
+#[derive(Clone)]
+pub struct Msg<T> {
+    pub id: MessageId,
+    pub type_id: MessageTypeId,
+    pub meta: MessageMeta,
+    pub payload: T,
+}
+
+pub fn type_str<T: 'static>() -> &'static str {
+    std::any::type_name::<T>()
+}
+
+pub fn register<T: 'static>(id_reg: &mut IdRegistrar) -> MessageTypeId {
+    MessageTypeId(id_reg.register_rust_type::<T>())
+}
+
+pub fn new_msg<T: 'static>(id_reg: &mut IdRegistrar, payload: T) -> Msg<T> {
+    Msg {
+        id: id_reg.new_message_id(),
+        //registering is idempotent, so this registers the type on first use
+        type_id: register::<T>(id_reg),
+        meta: new_meta(),
+        payload: payload,
+    }
+}
+
+impl<T: Clone + 'static> Message for Msg<T> {
+    fn msg_type_id(&self) -> MessageTypeId {
+        self.type_id
+    }
+    fn msg_id(&self) -> MessageId {
+        self.id
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+    fn clone_msg(&self) -> Box<dyn Message> {
+        Box::new(self.clone())
+    }
+    fn meta(&self) -> &MessageMeta {
+        &self.meta
+    }
+    fn meta_mut(&mut self) -> &mut MessageMeta {
+        &mut self.meta
+    }
+}
+
+impl dyn Message {
+    pub fn is<T: 'static>(&self) -> bool {
+        self.as_any().is::<Msg<T>>()
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&Msg<T>> {
+        self.as_any().downcast_ref::<Msg<T>>()
+    }
+
+    //hands back the message unchanged if it does not carry a T, so the next type can be tried
+    pub fn downcast<T: 'static>(self: Box<Self>) -> Result<Box<Msg<T>>, Box<dyn Message>> {
+        if self.is::<T>() {
+            Ok(self.into_any().downcast::<Msg<T>>().unwrap())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    #[derive(Clone)]
+    struct Ping {
+        nr: u64,
+    }
+
+    #[test]
+    fn downcast_by_reference_and_by_value() {
+        let mut env = new_test_env();
+        let msg: Box<dyn Message> = Box::new(new_msg(&mut env.id_reg, Ping { nr: 3 }));
+        assert!(msg.is::<Ping>() && !msg.is::<u64>());
+        assert_eq!(msg.downcast_ref::<Ping>().unwrap().payload.nr, 3);
+
+        //the wrong type hands the message back
+        let msg = msg.downcast::<u64>().err().unwrap();
+        assert_eq!(msg.downcast::<Ping>().ok().unwrap().payload.nr, 3);
+    }
+
+    #[test]
+    fn payload_types_are_registered_once() {
+        let mut env = new_test_env();
+        let first = new_msg(&mut env.id_reg, Ping { nr: 0 });
+        let second = new_msg(&mut env.id_reg, Ping { nr: 1 });
+        let other = new_msg(&mut env.id_reg, 5u64);
+        assert!(first.type_id == second.type_id);
+        assert!(first.type_id != other.type_id);
+        assert!(first.id != second.id);
+        assert_eq!(
+            env.id_reg
+                .lookup_message_id(type_str::<Ping>().to_owned())
+                .map(|t| t.0),
+            Some(first.type_id.0)
+        );
+    }
+}
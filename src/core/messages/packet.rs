@@ -0,0 +1,9 @@
+//Payload of the messages traffic sources generate, sent as Msg<Packet>. The size of a packet is
+//in meta().bit_length
+#[derive(Clone, Debug)]
+pub struct Packet {
+    //flow the packet belongs to, by default the id of the generating source
+    pub flow: u64,
+    //counts up from 0 per flow, so receivers can detect losses and reordering
+    pub seq: u64,
+}
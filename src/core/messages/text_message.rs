@@ -1,12 +1,14 @@
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{MessageId, MessageTypeId};
-use crate::core::messages::message::Message;
+use crate::core::messages::message::{new_meta, Message, MessageMeta};
 use std::any::Any;
 
+#[derive(Clone)]
 pub struct TextMsg {
     pub id: MessageId,
     pub type_id: MessageTypeId,
     pub data: String,
+    pub meta: MessageMeta,
 }
 
 pub static TYPE_STR: &str = "TextMessage";
@@ -18,6 +20,7 @@ pub fn new_text_msg(id_reg: &mut IdRegistrar, data: String) -> TextMsg {
         id: id_reg.new_message_id(),
         type_id: id_reg.lookup_message_id(TYPE_STR.to_owned()).unwrap(),
         data: data,
+        meta: new_meta(),
     }
 }
 
@@ -31,4 +34,16 @@ impl Message for TextMsg {
     fn as_any(&self) -> &dyn Any {
         self
     }
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+    fn clone_msg(&self) -> Box<dyn Message> {
+        Box::new(self.clone())
+    }
+    fn meta(&self) -> &MessageMeta {
+        &self.meta
+    }
+    fn meta_mut(&mut self) -> &mut MessageMeta {
+        &mut self.meta
+    }
 }
@@ -1,4 +1,5 @@
 pub mod clock;
+pub mod commands;
 pub mod connection;
 pub mod contexts;
 pub mod events;
@@ -7,4 +8,11 @@ pub mod id_mngmnt;
 pub mod messages;
 pub mod modules;
 pub mod ned_parser;
+pub mod random;
 pub mod runner;
+pub mod scenario;
+pub mod statistics;
+#[cfg(test)]
+pub mod testing;
+pub mod trace;
+pub mod validation;
@@ -1,24 +1,20 @@
-use crate::core::connection::connection::Port;
 use crate::core::contexts::EventHandleContext;
 use crate::core::events::event::Event;
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::modules::gate::{new_gate_desc, GateDesc, GateDirection};
+use crate::core::modules::module::{sim_module, HandleResult, Module};
 
 pub struct ModuleContainer {
     pub type_id: ModuleTypeId,
     pub id: ModuleId,
-    pub inner_to_outer_gates: std::collections::HashMap<GateId, GateId>,
-    pub outer_to_inner_gates: std::collections::HashMap<GateId, GateId>,
+    pub inner_to_outer_gates: std::collections::BTreeMap<GateId, GateId>,
+    pub outer_to_inner_gates: std::collections::BTreeMap<GateId, GateId>,
 
     name: String,
-}
-
-pub static TYPE_STR: &str = "ModuleContainer";
-
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
+    //built from the gate pairs when the container is made
+    gates: Vec<GateDesc>,
 }
 
 pub fn new_module_container(
@@ -28,17 +24,34 @@ pub fn new_module_container(
 ) -> ModuleContainer {
     let mut container = ModuleContainer {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
-        inner_to_outer_gates: std::collections::HashMap::new(),
-        outer_to_inner_gates: std::collections::HashMap::new(),
+        type_id: id_reg
+            .lookup_module_id(ModuleContainer::TYPE_STR.to_owned())
+            .unwrap(),
+        inner_to_outer_gates: std::collections::BTreeMap::new(),
+        outer_to_inner_gates: std::collections::BTreeMap::new(),
 
         name: name,
+        gates: Vec::with_capacity(gates.len() * 2),
     };
 
     for (outer, inner) in gates {
         container.inner_to_outer_gates.insert(inner, outer);
         container.outer_to_inner_gates.insert(outer, inner);
     }
+    for (i, o) in &container.inner_to_outer_gates {
+        container.gates.push(new_gate_desc(
+            *i,
+            &format!("inner{}", i.0),
+            GateDirection::InOut,
+            None,
+        ));
+        container.gates.push(new_gate_desc(
+            *o,
+            &format!("outer{}", o.0),
+            GateDirection::InOut,
+            None,
+        ));
+    }
 
     container
 }
@@ -71,19 +84,17 @@ impl ModuleContainer {
     }
 }
 
+#[sim_module(type_str = "ModuleContainer")]
 impl Module for ModuleContainer {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        let mut gates = Vec::with_capacity(self.inner_to_outer_gates.len() * 2);
-        for (i, o) in &self.inner_to_outer_gates {
-            gates.push(*i);
-            gates.push(*o);
-        }
-
-        gates
+    fn gates(&self) -> &[GateDesc] {
+        &self.gates
     }
 
-    fn name(&self) -> String {
-        self.name.clone()
+    fn passthrough_gates(&self) -> Vec<(GateId, GateId)> {
+        self.outer_to_inner_gates
+            .iter()
+            .map(|(o, i)| (*o, *i))
+            .collect()
     }
 
     fn handle_message(
@@ -104,24 +115,4 @@ impl Module for ModuleContainer {
     ) -> Result<HandleResult, Box<std::error::Error>> {
         panic!("Should never receive timer events");
     }
-
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
-    }
-
-    fn module_id(&self) -> ModuleId {
-        self.id
-    }
-
-    fn initialize(
-        &mut self,
-        _gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
-        _ctx: &mut EventHandleContext,
-    ) {
-    }
-
-    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
-        //println!("Finalized: {}, {}", &self.name, self.id.raw());
-        None
-    }
 }
@@ -1,10 +1,8 @@
-use crate::core::connection::connection::Port;
 use crate::core::contexts::EventHandleContext;
-use crate::core::events::event::Event;
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
 
 pub struct EchoModule {
     pub type_id: ModuleTypeId,
@@ -14,29 +12,27 @@ pub struct EchoModule {
     msgs_echoed: u64,
 }
 
-pub static OUT_GATE: GateId = GateId(0);
-pub static IN_GATE: GateId = GateId(1);
-pub static TYPE_STR: &str = "EchoModule";
-
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
-}
+pub const OUT_GATE: GateId = GateId(0);
+pub const IN_GATE: GateId = GateId(1);
 
 pub fn new_echo_module(id_reg: &mut IdRegistrar, name: String) -> EchoModule {
     EchoModule {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
+        type_id: id_reg
+            .lookup_module_id(EchoModule::TYPE_STR.to_owned())
+            .unwrap(),
         name: name,
 
         msgs_echoed: 0,
     }
 }
 
+#[sim_module(
+    type_str = "EchoModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = inout),
+)]
 impl Module for EchoModule {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        vec![OUT_GATE, IN_GATE]
-    }
-
     fn handle_message(
         &mut self,
         msg: Box<Message>,
@@ -55,39 +51,6 @@ impl Module for EchoModule {
         Ok(HandleResult {})
     }
 
-    fn handle_timer_event(
-        &mut self,
-        _ev: &Event,
-        _ctx: &mut EventHandleContext,
-    ) -> Result<HandleResult, Box<std::error::Error>> {
-        //println!(
-        //    "EchoModule with ID: {} swallowed event with ID: {}!",
-        //    self.id.raw(),
-        //    ev.event_id().raw(),
-        //);
-
-        Ok(HandleResult {})
-    }
-
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
-    }
-
-    fn module_id(&self) -> ModuleId {
-        self.id
-    }
-
-    fn name(&self) -> String {
-        self.name.clone()
-    }
-
-    fn initialize(
-        &mut self,
-        _gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
-        _ctx: &mut EventHandleContext,
-    ) {
-    }
-
     fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
         //println!("Finalize Echo: {}", self.id.raw());
         Some(FinalizeResult {
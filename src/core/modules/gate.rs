@@ -0,0 +1,192 @@
+use crate::core::id_mngmnt::id_types::GateId;
+
+use std::borrow::Cow;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GateDirection {
+    Input,
+    Output,
+    InOut,
+}
+
+#[derive(Clone)]
+pub struct GateDesc {
+    pub id: GateId,
+    //borrowed for gates declared with sim_module, owned for gates named at runtime
+    pub name: Cow<'static, str>,
+    pub direction: GateDirection,
+
+    //number of ports (vector size) this gate has. None means the gate grows with the connections made
+    pub size: Option<u64>,
+}
+
+pub fn new_gate_desc(
+    id: GateId,
+    name: &str,
+    direction: GateDirection,
+    size: Option<u64>,
+) -> GateDesc {
+    GateDesc {
+        id: id,
+        name: Cow::Owned(name.to_owned()),
+        direction: direction,
+        size: size,
+    }
+}
+
+//for gates known at compile time, so they can live in a static table
+pub const fn new_static_gate_desc(
+    id: GateId,
+    name: &'static str,
+    direction: GateDirection,
+    size: Option<u64>,
+) -> GateDesc {
+    GateDesc {
+        id: id,
+        name: Cow::Borrowed(name),
+        direction: direction,
+        size: size,
+    }
+}
+
+impl GateDirection {
+    pub fn can_send(&self) -> bool {
+        match self {
+            GateDirection::Input => false,
+            GateDirection::Output | GateDirection::InOut => true,
+        }
+    }
+
+    pub fn can_receive(&self) -> bool {
+        match self {
+            GateDirection::Output => false,
+            GateDirection::Input | GateDirection::InOut => true,
+        }
+    }
+}
+
+impl GateDesc {
+    pub fn has_port(&self, idx: u64) -> bool {
+        match self.size {
+            Some(size) => idx < size,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::mesh::ConnectionKind;
+    use crate::core::connection::simple_connection;
+    use crate::core::contexts::EventHandleContext;
+    use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+    use crate::core::id_mngmnt::id_types::{ModuleId, ModuleTypeId, PortId};
+    use crate::core::messages::message::Message;
+    use crate::core::modules::module::{sim_module, HandleResult, Module};
+    use crate::core::runner;
+    use crate::core::testing::new_test_env;
+
+    struct Gated {
+        type_id: ModuleTypeId,
+        id: ModuleId,
+        name: String,
+    }
+
+    const OUT: GateId = GateId(0);
+    const IN: GateId = GateId(1);
+    const BOTH: GateId = GateId(2);
+
+    #[sim_module(
+        type_str = "GatedModule",
+        gate(id = OUT, name = "out", dir = output),
+        gate(id = IN, name = "in", dir = input, size = 1),
+        gate(id = BOTH, name = "both", dir = inout, size = 2),
+    )]
+    impl Module for Gated {
+        fn handle_message(
+            &mut self,
+            _msg: Box<dyn Message>,
+            _gate: GateId,
+            _port: PortId,
+            _ctx: &mut EventHandleContext,
+        ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+            Ok(HandleResult {})
+        }
+    }
+
+    fn new_gated(id_reg: &mut IdRegistrar, name: &str) -> Gated {
+        Gated {
+            id: id_reg.new_module_id(),
+            type_id: id_reg.lookup_module_id(Gated::TYPE_STR.to_owned()).unwrap(),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn declared_gates_are_listed_and_found_by_name() {
+        let mut env = new_test_env();
+        Gated::register(&mut env.id_reg);
+        let m = new_gated(&mut env.id_reg, "Gated");
+
+        let table: Vec<(u64, &str, GateDirection, Option<u64>)> = m
+            .gates()
+            .iter()
+            .map(|g| (g.id.0, g.name.as_ref(), g.direction, g.size))
+            .collect();
+        assert_eq!(
+            table,
+            vec![
+                (0, "out", GateDirection::Output, None),
+                (1, "in", GateDirection::Input, Some(1)),
+                (2, "both", GateDirection::InOut, Some(2)),
+            ]
+        );
+        assert_eq!(m.get_gate_ids().len(), 3);
+
+        let found = |name: &str, idx: u64| m.gate(name, idx).map(|(g, p)| (g.0, p.0));
+        assert_eq!(found("out", 17), Some((0, 17)));
+        assert_eq!(found("both", 1), Some((2, 1)));
+        //beyond the vector size or an unknown name
+        assert_eq!(found("in", 1), None);
+        assert_eq!(found("sideways", 0), None);
+    }
+
+    #[test]
+    fn connections_are_checked_against_the_declared_gates() {
+        let mut env = new_test_env();
+        Gated::register(&mut env.id_reg);
+        simple_connection::register(&mut env.id_reg);
+        let mut r = runner::new_runner([7; 16]);
+        let (a, b) = (
+            new_gated(&mut env.id_reg, "A"),
+            new_gated(&mut env.id_reg, "B"),
+        );
+        let (a_id, b_id) = (a.id, b.id);
+        r.add_module(Box::new(a)).unwrap();
+        r.add_module(Box::new(b)).unwrap();
+
+        let mut connect = |from: (GateId, u64), to: (GateId, u64)| {
+            let conn = simple_connection::new_simple_connection(&mut env.id_reg, 1, 0, 0);
+            r.connect_modules(
+                Box::new(conn),
+                ConnectionKind::Onedirectional,
+                a_id,
+                from.0,
+                PortId(from.1),
+                b_id,
+                to.0,
+                PortId(to.1),
+            )
+        };
+
+        //sending on an input, receiving on an output, a port beyond the size, an undeclared gate
+        assert!(connect((IN, 0), (IN, 0)).is_err());
+        assert!(connect((OUT, 0), (OUT, 0)).is_err());
+        assert!(connect((OUT, 0), (IN, 1)).is_err());
+        assert!(connect((GateId(9), 0), (IN, 0)).is_err());
+
+        assert!(connect((OUT, 0), (IN, 0)).is_ok());
+        assert!(connect((BOTH, 1), (BOTH, 0)).is_ok());
+    }
+}
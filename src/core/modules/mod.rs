@@ -1,5 +1,8 @@
 pub mod container;
 pub mod echo_module;
+pub mod gate;
 pub mod module;
 pub mod simple_module;
 pub mod sink;
+pub mod source;
+pub mod trace_source;
@@ -3,8 +3,12 @@ use crate::core::contexts::EventHandleContext;
 use crate::core::events::event::Event;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
+use crate::core::modules::gate::GateDesc;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+//fills in module_type_id/module_id/name/gates and adds TYPE_STR/register for a module
+pub use sim_macros::sim_module;
 
 pub struct HandleResult {}
 
@@ -24,19 +28,61 @@ pub trait Module {
 
     fn handle_timer_event(
         &mut self,
-        ev: &Event,
-        ctx: &mut EventHandleContext,
-    ) -> Result<HandleResult, Box<std::error::Error>>;
+        _ev: &Event,
+        _ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<std::error::Error>> {
+        Ok(HandleResult {})
+    }
 
     fn module_type_id(&self) -> ModuleTypeId;
     fn module_id(&self) -> ModuleId;
     fn name(&self) -> String;
 
-    fn get_gate_ids(&self) -> Vec<GateId>;
+    //all gates this module can be connected on. Read once when the module is added to the runner,
+    //connections are checked against that copy, so the gates can not change at runtime
+    fn gates(&self) -> &[GateDesc];
+
+    fn get_gate_ids(&self) -> Vec<GateId> {
+        self.gates().iter().map(|g| g.id).collect()
+    }
+
+    //look up port idx of the gate with the given name, eg: self.gate("out", 3)
+    fn gate(&self, name: &str, idx: u64) -> Option<(GateId, PortId)> {
+        self.gates()
+            .iter()
+            .find(|g| g.name == name && g.has_port(idx))
+            .map(|g| (g.id, PortId(idx)))
+    }
+
+    //(outer, inner) gate pairs a container passes messages through between, on the same port
+    fn passthrough_gates(&self) -> Vec<(GateId, GateId)> {
+        Vec::new()
+    }
+
+    //modules that return true get an Ev<LinkStateChange> (see commands::LinkStateChange) as timer
+    //event when a connection they send or receive over goes down or comes back up
+    fn wants_link_state(&self) -> bool {
+        false
+    }
+
+    //how many times initialize gets called. All modules finish a stage before any module starts
+    //the next one, within a stage modules are initialized in order of their ids
+    fn num_init_stages(&self) -> u32 {
+        1
+    }
     fn initialize(
         &mut self,
-        gates: &HashMap<GateId, HashMap<PortId, Port>>,
-        ctx: &mut EventHandleContext,
-    );
-    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult>;
+        _stage: u32,
+        _gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        _ctx: &mut EventHandleContext,
+    ) {
+    }
+
+    //change a parameter while the simulation runs, eg from a scenario script
+    fn set_parameter(&mut self, key: &str, _value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Err(format!("Module {} has no parameter {}", self.name(), key).into())
+    }
+    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        None
+    }
 }
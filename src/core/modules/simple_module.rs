@@ -5,7 +5,7 @@ use crate::core::events::text_event::{new_text_event, TextEvent};
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
 use crate::core::messages::text_message;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
 
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 
@@ -21,18 +21,15 @@ pub struct SimpleModule {
     pub messages_sent: u64,
 }
 
-pub static OUT_GATE: GateId = GateId(0);
-pub static IN_GATE: GateId = GateId(1);
-pub static TYPE_STR: &str = "SimpleModule";
-
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
-}
+pub const OUT_GATE: GateId = GateId(0);
+pub const IN_GATE: GateId = GateId(1);
 
 pub fn new_simple_module(id_reg: &mut IdRegistrar, name: String) -> SimpleModule {
     SimpleModule {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
+        type_id: id_reg
+            .lookup_module_id(SimpleModule::TYPE_STR.to_owned())
+            .unwrap(),
         name: name,
 
         msg_counter: 0,
@@ -57,11 +54,12 @@ impl SimpleModule {
     }
 }
 
+#[sim_module(
+    type_str = "SimpleModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
 impl Module for SimpleModule {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        vec![OUT_GATE, IN_GATE]
-    }
-
     fn handle_message(
         &mut self,
         _msg: Box<Message>,
@@ -139,21 +137,10 @@ impl Module for SimpleModule {
         Ok(HandleResult {})
     }
 
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
-    }
-
-    fn module_id(&self) -> ModuleId {
-        self.id
-    }
-
-    fn name(&self) -> String {
-        self.name.clone()
-    }
-
     fn initialize(
         &mut self,
-        gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
+        _stage: u32,
+        gates: &std::collections::BTreeMap<GateId, std::collections::BTreeMap<PortId, Port>>,
         ctx: &mut EventHandleContext,
     ) {
         self.ports = gates.get(&OUT_GATE).unwrap().keys().map(|id| *id).collect();
@@ -1,48 +1,122 @@
-use crate::core::connection::connection::Port;
+use crate::core::clock;
 use crate::core::contexts::EventHandleContext;
-use crate::core::events::event::Event;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::messages::packet::Packet;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::core::statistics::{
+    new_log_histogram, new_summary, new_time_bins, LogHistogram, Summary, TimeBins,
+};
 
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 
+use std::collections::BTreeMap;
+
 pub struct Sink {
     pub type_id: ModuleTypeId,
     pub id: ModuleId,
     pub name: String,
 
     messages_sunk: u64,
-}
+    bits_sunk: u64,
+    //messages that arrived with the ECN congestion experienced flag set
+    ecn_marked: u64,
 
-pub static IN_GATE: GateId = GateId(0);
-pub static TYPE_STR: &str = "SinkModule";
+    delay: Summary,
+    delay_hist: LogHistogram,
+    //bits received per bin of simulation time
+    throughput: TimeBins,
+    //hop count -> number of messages that arrived with that hop count
+    hop_counts: BTreeMap<u64, u64>,
+    //sending module -> number of messages from it
+    per_source: BTreeMap<u64, u64>,
 
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
+    //per flow of Msg<Packet>: the next expected sequence number
+    next_seq: BTreeMap<u64, u64>,
+    //per flow: the ranges [start, end) of sequence numbers that were skipped and did not arrive yet
+    holes: BTreeMap<u64, BTreeMap<u64, u64>>,
+    //packets that arrived after one with a higher sequence number and fill a hole
+    out_of_order: u64,
+    //packets with a sequence number that arrived before, eg from a duplicating connection
+    duplicates: u64,
+    //jumps in the sequence numbers, and how many of the skipped numbers never arrived. Packets
+    //that arrive late fill their hole again and are counted as out of order
+    gaps: u64,
+    missing: u64,
 }
 
+pub const IN_GATE: GateId = GateId(0);
+
 pub fn new_sink(id_reg: &mut IdRegistrar, name: String) -> Sink {
     Sink {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
+        type_id: id_reg.lookup_module_id(Sink::TYPE_STR.to_owned()).unwrap(),
         name: name,
 
         messages_sunk: 0,
+        bits_sunk: 0,
+        ecn_marked: 0,
+
+        delay: new_summary(),
+        delay_hist: new_log_histogram(),
+        throughput: new_time_bins(clock::MILLI_SECONDS),
+        hop_counts: BTreeMap::new(),
+        per_source: BTreeMap::new(),
+
+        next_seq: BTreeMap::new(),
+        holes: BTreeMap::new(),
+        out_of_order: 0,
+        duplicates: 0,
+        gaps: 0,
+        missing: 0,
     }
 }
 
-impl Module for Sink {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        vec![IN_GATE]
+impl Sink {
+    fn check_sequence(&mut self, packet: &Packet) {
+        let expected = self.next_seq.entry(packet.flow).or_insert(0);
+        let holes = self.holes.entry(packet.flow).or_insert_with(BTreeMap::new);
+        if packet.seq < *expected {
+            //the hole this packet falls into, duplicates don't fall into any
+            let hole = match holes.range(..=packet.seq).next_back() {
+                Some((start, end)) if packet.seq < *end => Some((*start, *end)),
+                _ => None,
+            };
+            if let Some((start, end)) = hole {
+                holes.remove(&start);
+                if start < packet.seq {
+                    holes.insert(start, packet.seq);
+                }
+                if packet.seq + 1 < end {
+                    holes.insert(packet.seq + 1, end);
+                }
+                self.missing -= 1;
+                self.out_of_order += 1;
+            } else {
+                self.duplicates += 1;
+            }
+            return;
+        }
+        if packet.seq > *expected {
+            self.gaps += 1;
+            self.missing += packet.seq - *expected;
+            holes.insert(*expected, packet.seq);
+        }
+        *expected = packet.seq + 1;
     }
+}
 
+#[sim_module(
+    type_str = "SinkModule",
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
+impl Module for Sink {
     fn handle_message(
         &mut self,
-        _msg: Box<Message>,
+        msg: Box<Message>,
         _gate: GateId,
         _port: PortId,
-        _ctx: &mut EventHandleContext,
+        ctx: &mut EventHandleContext,
     ) -> Result<HandleResult, Box<std::error::Error>> {
         //println!(
         //    "Sink with ID: {} swallowed message with ID: {}!",
@@ -51,50 +125,184 @@ impl Module for Sink {
         //);
         self.messages_sunk += 1;
 
-        Ok(HandleResult {})
-    }
+        let meta = msg.meta();
+        if let Some(delay) = meta.end_to_end_delay() {
+            self.delay.add(delay as f64);
+            self.delay_hist.add(delay);
+        }
+        self.bits_sunk += meta.bit_length;
+        self.throughput.add(ctx.mctx.time.now(), meta.bit_length);
+        if meta.ecn_ce {
+            self.ecn_marked += 1;
+        }
+        *self.hop_counts.entry(meta.hop_count).or_insert(0) += 1;
+        if let Some(source) = meta.source {
+            *self.per_source.entry(source.raw()).or_insert(0) += 1;
+        }
 
-    fn handle_timer_event(
-        &mut self,
-        _ev: &Event,
-        _ctx: &mut EventHandleContext,
-    ) -> Result<HandleResult, Box<std::error::Error>> {
-        //println!(
-        //    "Sink with ID: {} swallowed event with ID: {}!",
-        //    self.id.raw(),
-        //    ev.event_id().raw(),
-        //);
+        if let Some(packet) = msg.downcast_ref::<Packet>() {
+            self.check_sequence(&packet.payload);
+        }
 
         Ok(HandleResult {})
     }
 
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
+    //throughput_bin is the width of the bins throughput is reported for, eg "10ms"
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "throughput_bin" => {
+                let width = clock::parse_time(value)?;
+                if width == 0 {
+                    return Err("throughput_bin must be greater than 0".into());
+                }
+                self.throughput = new_time_bins(width);
+            }
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
     }
 
-    fn module_id(&self) -> ModuleId {
-        self.id
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        //println!("Finalize Sink: {}", self.id.raw());
+        let name = self.name();
+        let now = ctx.mctx.time.now();
+        let mut results = vec![
+            (
+                name.clone(),
+                "sunk_msgs".to_owned(),
+                self.messages_sunk.to_string(),
+            ),
+            (
+                name.clone(),
+                "sunk_bits".to_owned(),
+                self.bits_sunk.to_string(),
+            ),
+            (
+                name.clone(),
+                "ecn_marked_msgs".to_owned(),
+                self.ecn_marked.to_string(),
+            ),
+        ];
+
+        if self.messages_sunk > 0 {
+            let delays = vec![
+                ("mean_delay", self.delay.mean().round() as u64),
+                ("min_delay", self.delay.min as u64),
+                ("max_delay", self.delay.max as u64),
+                ("stddev_delay", self.delay.stddev().round() as u64),
+                ("p50_delay", self.delay_hist.percentile(0.5)),
+                ("p90_delay", self.delay_hist.percentile(0.9)),
+                ("p99_delay", self.delay_hist.percentile(0.99)),
+            ];
+            for (field, value) in delays {
+                results.push((name.clone(), field.to_owned(), value.to_string()));
+            }
+            for (lower, count) in self.delay_hist.bins() {
+                results.push((
+                    name.clone(),
+                    format!("delay_hist_{}", lower),
+                    count.to_string(),
+                ));
+            }
+        }
+
+        //in bits per second, bins without messages in between are reported as 0. The bin the
+        //simulation ended in is only divided by the time it covered
+        for (start, _, bits) in self.throughput.contiguous_bins() {
+            let width = match now.saturating_sub(start) {
+                0 => self.throughput.width,
+                covered => std::cmp::min(covered, self.throughput.width),
+            };
+            let bps = bits as f64 * clock::SECONDS as f64 / width as f64;
+            results.push((
+                name.clone(),
+                format!("throughput_{}", start),
+                bps.round().to_string(),
+            ));
+        }
+
+        for (hops, count) in &self.hop_counts {
+            results.push((name.clone(), format!("hops_{}", hops), count.to_string()));
+        }
+        for (source, count) in &self.per_source {
+            results.push((name.clone(), format!("from_{}", source), count.to_string()));
+        }
+
+        if !self.next_seq.is_empty() {
+            results.push((
+                name.clone(),
+                "out_of_order".to_owned(),
+                self.out_of_order.to_string(),
+            ));
+            results.push((
+                name.clone(),
+                "duplicate_msgs".to_owned(),
+                self.duplicates.to_string(),
+            ));
+            results.push((name.clone(), "gaps".to_owned(), self.gaps.to_string()));
+            results.push((
+                name.clone(),
+                "missing_msgs".to_owned(),
+                self.missing.to_string(),
+            ));
+        }
+
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    fn sink_after(seqs: &[u64]) -> Sink {
+        let mut env = new_test_env();
+        Sink::register(&mut env.id_reg);
+        let mut sink = new_sink(&mut env.id_reg, "Sink".to_owned());
+        for seq in seqs {
+            sink.check_sequence(&Packet { flow: 1, seq: *seq });
+        }
+        sink
     }
 
-    fn name(&self) -> String {
-        self.name.clone()
+    #[test]
+    fn late_packets_are_not_missing() {
+        let sink = sink_after(&[0, 3, 1, 5]);
+        assert_eq!(sink.gaps, 2);
+        assert_eq!(sink.out_of_order, 1);
+        //2 and 4 never arrived
+        assert_eq!(sink.missing, 2);
+
+        let sink = sink_after(&[0, 4, 2, 1, 3]);
+        assert_eq!((sink.gaps, sink.out_of_order, sink.missing), (1, 3, 0));
     }
 
-    fn initialize(
-        &mut self,
-        _gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
-        _ctx: &mut EventHandleContext,
-    ) {
+    #[test]
+    fn duplicates_do_not_fill_holes() {
+        let sink = sink_after(&[0, 1, 3, 1, 0]);
+        assert_eq!(
+            (sink.gaps, sink.out_of_order, sink.duplicates, sink.missing),
+            (1, 0, 2, 1)
+        );
 
+        //the second 1 is a duplicate of the one that filled the hole
+        let sink = sink_after(&[0, 2, 1, 1, 2]);
+        assert_eq!(
+            (sink.gaps, sink.out_of_order, sink.duplicates, sink.missing),
+            (1, 1, 2, 0)
+        );
     }
-    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
-        //println!("Finalize Sink: {}", self.id.raw());
-        Some(FinalizeResult {
-            results: vec![(
-                self.name(),
-                "sunk_msgs".to_owned(),
-                self.messages_sunk.to_string(),
-            )],
-        })
+
+    #[test]
+    fn throughput_bin_width_is_configurable() {
+        let mut env = new_test_env();
+        Sink::register(&mut env.id_reg);
+        let mut sink = new_sink(&mut env.id_reg, "Sink".to_owned());
+        assert_eq!(sink.throughput.width, clock::MILLI_SECONDS);
+        sink.set_parameter("throughput_bin", "10us").unwrap();
+        assert_eq!(sink.throughput.width, 10 * clock::MICRO_SECONDS);
+        assert!(sink.set_parameter("throughput_bin", "0").is_err());
+        assert!(sink.set_parameter("throughput_bin", "wide").is_err());
     }
 }
@@ -0,0 +1,406 @@
+use crate::core::contexts::EventHandleContext;
+use crate::core::events::ev;
+use crate::core::events::event::{Event, TimerEvent};
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::messages::msg;
+use crate::core::messages::packet::Packet;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::core::random::distribution::{parse_unitless, Distribution};
+
+use crate::core::connection::connection::Port;
+
+use std::collections::BTreeMap;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DestinationPolicy {
+    //cycle through the connected ports of the out gate
+    RoundRobin,
+    //pick one of the connected ports at random for every message
+    Random,
+}
+
+//Generates Msg<Packet> messages. The time between two messages and their size (in bytes) are
+//drawn from distributions, on the random stream of this module.
+//Sends the first message at start and stops at stop or after max_count messages.
+pub struct Source {
+    pub type_id: ModuleTypeId,
+    pub id: ModuleId,
+    pub name: String,
+
+    pub interarrival: Distribution,
+    pub size: Distribution,
+    pub start: u64,
+    pub stop: Option<u64>,
+    pub max_count: Option<u64>,
+    pub destinations: DestinationPolicy,
+    pub flow: u64,
+    //sets ecn_capable on the generated messages
+    pub ecn: bool,
+
+    ports: Vec<PortId>,
+    next_port: usize,
+    //the first message is scheduled in initialize, start can't be moved after that
+    initialized: bool,
+
+    msgs_generated: u64,
+    bytes_generated: u64,
+    first_send: Option<u64>,
+    last_send: u64,
+}
+
+pub const OUT_GATE: GateId = GateId(0);
+
+//marks the timer event for the next message
+struct NextArrival {}
+
+pub fn new_source(
+    id_reg: &mut IdRegistrar,
+    name: String,
+    interarrival: Distribution,
+    size: Distribution,
+) -> Source {
+    let id = id_reg.new_module_id();
+    Source {
+        id: id,
+        type_id: id_reg
+            .lookup_module_id(Source::TYPE_STR.to_owned())
+            .unwrap(),
+        name: name,
+
+        interarrival: interarrival,
+        size: size,
+        start: 0,
+        stop: None,
+        max_count: None,
+        destinations: DestinationPolicy::RoundRobin,
+        flow: id.raw(),
+        ecn: false,
+
+        ports: Vec::new(),
+        next_port: 0,
+        initialized: false,
+
+        msgs_generated: 0,
+        bytes_generated: 0,
+        first_send: None,
+        last_send: 0,
+    }
+}
+
+impl Source {
+    fn is_done(&self, now: u64) -> bool {
+        let stopped = match self.stop {
+            Some(stop) => now >= stop,
+            None => false,
+        };
+        let enough = match self.max_count {
+            Some(max) => self.msgs_generated >= max,
+            None => false,
+        };
+        stopped || enough || self.ports.is_empty()
+    }
+
+    fn pick_port(&mut self, ctx: &mut EventHandleContext) -> PortId {
+        match self.destinations {
+            DestinationPolicy::RoundRobin => {
+                let port = self.ports[self.next_port];
+                self.next_port = (self.next_port + 1) % self.ports.len();
+                port
+            }
+            DestinationPolicy::Random => {
+                let idx = ctx.random().uniform_int(0, self.ports.len() as u64);
+                self.ports[idx as usize]
+            }
+        }
+    }
+
+    fn schedule(&self, time: u64, ctx: &mut EventHandleContext) {
+        ctx.timer_queue.push(TimerEvent {
+            time: time,
+            mod_id: self.id,
+            event: Box::new(ev::new_ev(ctx.mctx.id_reg, NextArrival {})),
+        });
+    }
+}
+
+#[sim_module(
+    type_str = "SourceModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+)]
+impl Module for Source {
+    fn handle_message(
+        &mut self,
+        _msg: Box<dyn Message>,
+        _gate: GateId,
+        _port: PortId,
+        _ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        Err(format!("Source {} has no in-going gates", self.name).into())
+    }
+
+    fn handle_timer_event(
+        &mut self,
+        _ev: &dyn Event,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        let now = ctx.mctx.time.now();
+        if self.is_done(now) {
+            return Ok(HandleResult {});
+        }
+
+        let bytes = ctx.random().sample(&self.size).max(0.0).round() as u64;
+        let mut packet = msg::new_msg(
+            ctx.mctx.id_reg,
+            Packet {
+                flow: self.flow,
+                seq: self.msgs_generated,
+            },
+        );
+        packet.meta.bit_length = bytes * 8;
+        packet.meta.ecn_capable = self.ecn;
+
+        let port = self.pick_port(ctx);
+        ctx.msgs_to_send
+            .push_back((Box::new(packet), OUT_GATE, port));
+
+        self.msgs_generated += 1;
+        self.bytes_generated += bytes;
+        if self.first_send.is_none() {
+            self.first_send = Some(now);
+        }
+        self.last_send = now;
+
+        let next = now + ctx.random().sample_time(&self.interarrival);
+        if !self.is_done(next) {
+            self.schedule(next, ctx);
+        }
+
+        Ok(HandleResult {})
+    }
+
+    fn initialize(
+        &mut self,
+        _stage: u32,
+        gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        ctx: &mut EventHandleContext,
+    ) {
+        self.ports = match gates.get(&OUT_GATE) {
+            Some(ports) => ports.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+
+        self.initialized = true;
+        let start = std::cmp::max(self.start, ctx.mctx.time.now());
+        if !self.is_done(start) {
+            self.schedule(start, ctx);
+        }
+    }
+
+    //interarrival takes a distribution like "exponential(1ms)", size one without time units like
+    //"uniform(64, 1500)", start/stop times like "10s", count a number, ecn true or false and
+    //destinations "round-robin" or "random".
+    //While the simulation runs start can not be changed anymore. A lower stop or count takes effect
+    //with the next message, but a source that already stopped is not started again
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "interarrival" => self.interarrival = value.parse()?,
+            "size" => self.size = parse_unitless(value)?,
+            "start" => {
+                if self.initialized {
+                    return Err(format!(
+                        "Source {} is already running, start can not be changed",
+                        self.name
+                    )
+                    .into());
+                }
+                self.start = crate::core::clock::parse_time(value)?
+            }
+            "stop" => self.stop = Some(crate::core::clock::parse_time(value)?),
+            "count" => self.max_count = Some(value.parse()?),
+            "flow" => self.flow = value.parse()?,
+            "ecn" => self.ecn = value.parse()?,
+            "destinations" => {
+                self.destinations = match value {
+                    "round-robin" => DestinationPolicy::RoundRobin,
+                    "random" => DestinationPolicy::Random,
+                    _ => return Err(format!("Unknown destination policy {}", value).into()),
+                }
+            }
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let mut results = vec![
+            (
+                self.name(),
+                "generated_msgs".to_owned(),
+                self.msgs_generated.to_string(),
+            ),
+            (
+                self.name(),
+                "generated_bytes".to_owned(),
+                self.bytes_generated.to_string(),
+            ),
+        ];
+
+        if let Some(first) = self.first_send {
+            results.push((self.name(), "first_send".to_owned(), first.to_string()));
+            results.push((
+                self.name(),
+                "last_send".to_owned(),
+                self.last_send.to_string(),
+            ));
+        }
+
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::mesh::ConnectionKind;
+    use crate::core::connection::simple_connection;
+    use crate::core::modules::sink::{self, Sink};
+    use crate::core::runner::{self, Runner, Tree};
+    use crate::core::testing::new_test_env;
+
+    //a source with a constant interarrival time of 10 sending to n sinks, run until 1000
+    fn run(configure: impl FnOnce(&mut Source), sinks: u64) -> Runner {
+        let mut env = new_test_env();
+        Source::register(&mut env.id_reg);
+        Sink::register(&mut env.id_reg);
+        simple_connection::register(&mut env.id_reg);
+        let mut r = runner::new_runner([7; 16]);
+
+        let mut src = new_source(
+            &mut env.id_reg,
+            "Source".to_owned(),
+            Distribution::Constant(10.0),
+            Distribution::Constant(100.0),
+        );
+        configure(&mut src);
+        let src_id = src.id;
+        r.add_module(Box::new(src)).unwrap();
+        r.add_to_tree(Tree::Leaf(("Source".to_owned(), src_id)));
+
+        for port in 0..sinks {
+            let name = format!("Sink{}", port);
+            let snk = sink::new_sink(&mut env.id_reg, name.clone());
+            let snk_id = snk.id;
+            r.add_module(Box::new(snk)).unwrap();
+            r.add_to_tree(Tree::Leaf((name, snk_id)));
+            let conn = simple_connection::new_simple_connection(&mut env.id_reg, 0, 0, 0);
+            r.connect_modules(
+                Box::new(conn),
+                ConnectionKind::Onedirectional,
+                src_id,
+                OUT_GATE,
+                PortId(port),
+                snk_id,
+                sink::IN_GATE,
+                PortId(0),
+            )
+            .unwrap();
+        }
+
+        r.run(&mut env.id_reg, 1000).unwrap();
+        r
+    }
+
+    fn result(r: &Runner, module: &str, field: &str) -> Option<u64> {
+        r.results()
+            .iter()
+            .find(|(m, f, _)| m == module && f == field)
+            .map(|(_, _, v)| v.parse().unwrap())
+    }
+
+    #[test]
+    fn sends_between_start_and_stop() {
+        let r = run(
+            |s| {
+                s.set_parameter("start", "100").unwrap();
+                s.set_parameter("stop", "200").unwrap();
+            },
+            1,
+        );
+        assert_eq!(result(&r, "Source", "generated_msgs"), Some(10));
+        assert_eq!(result(&r, "Source", "generated_bytes"), Some(1000));
+        assert_eq!(result(&r, "Source", "first_send"), Some(100));
+        assert_eq!(result(&r, "Source", "last_send"), Some(190));
+        assert_eq!(result(&r, "Sink0", "sunk_msgs"), Some(10));
+    }
+
+    #[test]
+    fn stops_after_count() {
+        let r = run(|s| s.set_parameter("count", "3").unwrap(), 1);
+        assert_eq!(result(&r, "Source", "generated_msgs"), Some(3));
+        assert_eq!(result(&r, "Source", "last_send"), Some(20));
+        assert_eq!(result(&r, "Sink0", "missing_msgs"), Some(0));
+    }
+
+    #[test]
+    fn sends_nothing_without_connected_ports() {
+        let r = run(|_| {}, 0);
+        assert_eq!(result(&r, "Source", "generated_msgs"), Some(0));
+        assert_eq!(result(&r, "Source", "first_send"), None);
+    }
+
+    #[test]
+    fn round_robin_cycles_through_the_ports() {
+        let r = run(|s| s.set_parameter("count", "10").unwrap(), 3);
+        let counts: Vec<Option<u64>> = (0..3)
+            .map(|p| result(&r, &format!("Sink{}", p), "sunk_msgs"))
+            .collect();
+        assert_eq!(counts, vec![Some(4), Some(3), Some(3)]);
+    }
+
+    #[test]
+    fn random_destinations_spread_evenly() {
+        let r = run(
+            |s| {
+                //all at time 0
+                s.set_parameter("interarrival", "constant(0)").unwrap();
+                s.set_parameter("destinations", "random").unwrap();
+                s.set_parameter("count", "3000").unwrap();
+            },
+            3,
+        );
+        let mut total = 0;
+        for p in 0..3 {
+            let n = result(&r, &format!("Sink{}", p), "sunk_msgs").unwrap();
+            assert!(n > 900 && n < 1100, "Sink{}: {}", p, n);
+            total += n;
+        }
+        assert_eq!(total, 3000);
+    }
+
+    #[test]
+    fn messages_to_a_source_are_an_error() {
+        let mut env = new_test_env();
+        Source::register(&mut env.id_reg);
+        let r = runner::new_runner([7; 16]);
+        let mut src = new_source(
+            &mut env.id_reg,
+            "Source".to_owned(),
+            Distribution::Constant(10.0),
+            Distribution::Constant(100.0),
+        );
+        let msg = env.msg(8);
+        let mut ctx = EventHandleContext {
+            timer_queue: &mut std::collections::BinaryHeap::new(),
+            msgs_to_send: &mut std::collections::VecDeque::new(),
+            commands: &mut std::collections::VecDeque::new(),
+            mesh: &r.connections,
+            factory: &r.factory,
+            mctx: env.ctx(),
+        };
+        assert!(src
+            .handle_message(msg, GateId(0), PortId(0), &mut ctx)
+            .is_err());
+    }
+}
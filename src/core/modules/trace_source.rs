@@ -0,0 +1,444 @@
+use crate::core::clock;
+use crate::core::connection::connection::Port;
+use crate::core::contexts::EventHandleContext;
+use crate::core::events::ev;
+use crate::core::events::event::{Event, TimerEvent};
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::messages::msg;
+use crate::core::messages::packet::Packet;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufRead;
+
+//Replays a trace file. Every line is one message:
+//
+//timestamp,size,destination[,flow]
+//
+//timestamp is a time like "1.5ms" (plain numbers are nanoseconds), size is in bytes and destination
+//is the port of the out gate the message is sent on. Without a flow the id of this module is used.
+//Empty lines and lines starting with '#' are skipped, so is a header line before the first record.
+//Timestamps must not decrease. The file is read while the simulation runs, one line ahead, so traces
+//of any size work. Lines are checked as they are read: the first record when the source is created,
+//a broken line later on stops the replay with an error message and is reported in the results.
+//Records for ports of the out gate that are not connected are dropped and counted.
+pub struct TraceSource {
+    pub type_id: ModuleTypeId,
+    pub id: ModuleId,
+    pub name: String,
+
+    //added to every timestamp of the trace
+    pub offset: u64,
+
+    path: String,
+    lines: std::io::Lines<std::io::BufReader<std::fs::File>>,
+    line_nr: u64,
+    next: Option<TraceRecord>,
+    //a header may only come before the first record
+    seen_record: bool,
+    //the line the replay stopped at because it could not be read
+    broken_line: Option<u64>,
+    //unconnected ports records were dropped for, to warn only once per port
+    warned: BTreeSet<PortId>,
+
+    //next sequence number per flow
+    seqs: BTreeMap<u64, u64>,
+
+    msgs_replayed: u64,
+    bytes_replayed: u64,
+    msgs_unconnected: u64,
+}
+
+pub struct TraceRecord {
+    pub time: u64,
+    pub size: u64,
+    pub destination: PortId,
+    pub flow: Option<u64>,
+}
+
+pub const OUT_GATE: GateId = GateId(0);
+
+struct NextRecord {}
+
+fn open_trace(
+    path: &str,
+) -> Result<std::io::Lines<std::io::BufReader<std::fs::File>>, Box<dyn std::error::Error>> {
+    match std::fs::File::open(path) {
+        Ok(f) => Ok(std::io::BufReader::new(f).lines()),
+        Err(e) => Err(format!("Could not open trace {}: {}", path, e).into()),
+    }
+}
+
+//opens the trace and reads its first record, Err if that fails
+pub fn new_trace_source(
+    id_reg: &mut IdRegistrar,
+    name: String,
+    path: &str,
+) -> Result<TraceSource, Box<dyn std::error::Error>> {
+    let mut src = TraceSource {
+        id: id_reg.new_module_id(),
+        type_id: id_reg
+            .lookup_module_id(TraceSource::TYPE_STR.to_owned())
+            .unwrap(),
+        name: name,
+
+        offset: 0,
+
+        path: path.to_owned(),
+        lines: open_trace(path)?,
+        line_nr: 0,
+        next: None,
+        seen_record: false,
+        broken_line: None,
+        warned: BTreeSet::new(),
+
+        seqs: BTreeMap::new(),
+
+        msgs_replayed: 0,
+        bytes_replayed: 0,
+        msgs_unconnected: 0,
+    };
+
+    src.read_next()?;
+    Ok(src)
+}
+
+pub fn parse_record(line: &str) -> Result<TraceRecord, Box<dyn std::error::Error>> {
+    let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+    if fields.len() < 3 || fields.len() > 4 {
+        return Err("expected timestamp,size,destination[,flow]".into());
+    }
+
+    Ok(TraceRecord {
+        time: clock::parse_time(fields[0])?,
+        size: fields[1].parse()?,
+        destination: PortId(fields[2].parse()?),
+        flow: match fields.get(3) {
+            Some(flow) => Some(flow.parse()?),
+            None => None,
+        },
+    })
+}
+
+impl TraceSource {
+    //reads the next record into self.next, None at the end of the trace
+    fn read_next(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let last_time = self.next.as_ref().map(|r| r.time);
+        self.next = None;
+
+        while let Some(line) = self.lines.next() {
+            let line = line?;
+            self.line_nr += 1;
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let record = match parse_record(trimmed) {
+                Ok(r) => r,
+                //a header names the columns, so it starts with a letter
+                Err(_) if !self.seen_record && trimmed.starts_with(char::is_alphabetic) => {
+                    continue;
+                }
+                Err(e) => {
+                    return Err(format!("{} line {}: {}", self.path, self.line_nr, e).into());
+                }
+            };
+
+            if let Some(last) = last_time {
+                if record.time < last {
+                    return Err(format!(
+                        "{} line {}: timestamp goes back in time",
+                        self.path, self.line_nr
+                    )
+                    .into());
+                }
+            }
+            self.seen_record = true;
+            self.next = Some(record);
+            break;
+        }
+        Ok(())
+    }
+
+    //like read_next, but a broken line ends the replay instead of the simulation
+    fn advance(&mut self) {
+        if let Err(e) = self.read_next() {
+            println!("Error: {}, the rest of the trace is skipped", e);
+            self.broken_line = Some(self.line_nr);
+            self.next = None;
+        }
+    }
+
+    fn schedule_next(&self, ctx: &mut EventHandleContext) {
+        if let Some(record) = &self.next {
+            ctx.timer_queue.push(TimerEvent {
+                time: std::cmp::max(record.time + self.offset, ctx.mctx.time.now()),
+                mod_id: self.id,
+                event: Box::new(ev::new_ev(ctx.mctx.id_reg, NextRecord {})),
+            });
+        }
+    }
+}
+
+#[sim_module(
+    type_str = "TraceSourceModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+)]
+impl Module for TraceSource {
+    fn handle_message(
+        &mut self,
+        _msg: Box<dyn Message>,
+        _gate: GateId,
+        _port: PortId,
+        _ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        Err(format!("TraceSource {} has no in-going gates", self.name).into())
+    }
+
+    fn handle_timer_event(
+        &mut self,
+        _ev: &dyn Event,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        let now = ctx.mctx.time.now();
+
+        //everything recorded up to now, there may be several messages with the same timestamp
+        while let Some(record) = &self.next {
+            if record.time + self.offset > now {
+                break;
+            }
+            if !ctx
+                .mesh
+                .gates
+                .contains_key(&(self.id, OUT_GATE, record.destination))
+            {
+                if self.warned.insert(record.destination) {
+                    println!(
+                        "Warning: {} sends to port {} of the out gate which is not connected, these messages are dropped",
+                        self.path, record.destination.0
+                    );
+                }
+                self.msgs_unconnected += 1;
+                self.advance();
+                continue;
+            }
+
+            let flow = record.flow.unwrap_or(self.id.raw());
+            let seq = self.seqs.entry(flow).or_insert(0);
+            let mut packet = msg::new_msg(
+                ctx.mctx.id_reg,
+                Packet {
+                    flow: flow,
+                    seq: *seq,
+                },
+            );
+            *seq += 1;
+            packet.meta.bit_length = record.size * 8;
+
+            ctx.msgs_to_send
+                .push_back((Box::new(packet), OUT_GATE, record.destination));
+            self.msgs_replayed += 1;
+            self.bytes_replayed += record.size;
+
+            self.advance();
+        }
+
+        self.schedule_next(ctx);
+        Ok(HandleResult {})
+    }
+
+    fn initialize(
+        &mut self,
+        _stage: u32,
+        _gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        ctx: &mut EventHandleContext,
+    ) {
+        self.schedule_next(ctx);
+    }
+
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "offset" => self.offset = clock::parse_time(value)?,
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let mut results = vec![
+            (
+                self.name(),
+                "replayed_msgs".to_owned(),
+                self.msgs_replayed.to_string(),
+            ),
+            (
+                self.name(),
+                "replayed_bytes".to_owned(),
+                self.bytes_replayed.to_string(),
+            ),
+            (
+                self.name(),
+                "unconnected_msgs".to_owned(),
+                self.msgs_unconnected.to_string(),
+            ),
+        ];
+        if let Some(line) = self.broken_line {
+            results.push((self.name(), "broken_line".to_owned(), line.to_string()));
+        }
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::mesh::ConnectionKind;
+    use crate::core::connection::simple_connection;
+    use crate::core::modules::sink::{self, Sink};
+    use crate::core::runner;
+    use crate::core::testing::{new_test_env, TestEnv};
+    use crate::core::trace::TraceEntry;
+
+    fn write_trace(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.csv", name, std::process::id()));
+        let path = path.to_str().unwrap().to_owned();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn load(
+        env: &mut TestEnv,
+        name: &str,
+        content: &str,
+    ) -> Result<TraceSource, Box<dyn std::error::Error>> {
+        TraceSource::register(&mut env.id_reg);
+        let path = write_trace(name, content);
+        let src = new_trace_source(&mut env.id_reg, "Trace".to_owned(), &path);
+        std::fs::remove_file(&path).unwrap();
+        src
+    }
+
+    //replays the trace with ports 0 and 1 connected to a sink each, returns when the sinks got
+    //messages (time, port of the trace source) and the results of the trace source
+    fn replay(name: &str, content: &str, offset: &str) -> (Vec<(u64, u64)>, Vec<(String, String)>) {
+        let mut env = new_test_env();
+        Sink::register(&mut env.id_reg);
+        simple_connection::register(&mut env.id_reg);
+        let mut src = load(&mut env, name, content).unwrap();
+        src.set_parameter("offset", offset).unwrap();
+        let src_id = src.id;
+
+        let mut r = runner::new_runner([7; 16]);
+        r.add_module(Box::new(src)).unwrap();
+        r.add_to_tree(runner::Tree::Leaf(("Trace".to_owned(), src_id)));
+        let mut sinks = Vec::new();
+        for port in 0..2 {
+            let snk = sink::new_sink(&mut env.id_reg, format!("Sink{}", port));
+            let snk_id = snk.id;
+            r.add_module(Box::new(snk)).unwrap();
+            r.add_to_tree(runner::Tree::Leaf((format!("Sink{}", port), snk_id)));
+            let conn = simple_connection::new_simple_connection(&mut env.id_reg, 0, 0, 0);
+            r.connect_modules(
+                Box::new(conn),
+                ConnectionKind::Onedirectional,
+                src_id,
+                OUT_GATE,
+                PortId(port),
+                snk_id,
+                sink::IN_GATE,
+                PortId(0),
+            )
+            .unwrap();
+            sinks.push(snk_id.raw());
+        }
+        r.enable_trace();
+
+        r.run(&mut env.id_reg, clock::SECONDS).unwrap();
+
+        let arrivals = r
+            .trace()
+            .iter()
+            .filter_map(|e| match e {
+                TraceEntry::Message { time, module, .. } => {
+                    let port = sinks.iter().position(|s| s == module).unwrap();
+                    Some((*time, port as u64))
+                }
+                _ => None,
+            })
+            .collect();
+        let results = r
+            .results()
+            .iter()
+            .filter(|(m, _, _)| m == "Trace")
+            .map(|(_, f, v)| (f.clone(), v.clone()))
+            .collect();
+        (arrivals, results)
+    }
+
+    fn field(results: &[(String, String)], name: &str) -> Option<String> {
+        results
+            .iter()
+            .find(|(f, _)| f == name)
+            .map(|(_, v)| v.clone())
+    }
+
+    #[test]
+    fn header_after_comments_is_skipped() {
+        let mut env = new_test_env();
+        let src = load(
+            &mut env,
+            "trace_header",
+            "# recorded on link 3\n\ntime,size,port\n1ms,100,0\n2ms,200,2,7\n",
+        )
+        .unwrap();
+        let first = src.next.as_ref().unwrap();
+        assert_eq!((first.time, first.size), (1_000_000, 100));
+        assert_eq!(src.line_nr, 4);
+    }
+
+    #[test]
+    fn broken_first_records_are_rejected_on_load() {
+        let mut env = new_test_env();
+        let err = load(&mut env, "trace_broken", "time,size,port\n1ms,,0\n")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!(load(&mut env, "trace_garbage", "1ms,lots,0\n").is_err());
+    }
+
+    #[test]
+    fn records_are_replayed_at_their_time() {
+        let (arrivals, results) = replay(
+            "trace_timing",
+            "time,size,port\n1ms,100,0\n1ms,50,1\n2.5ms,10,1\n3ms,10,5\n4ms,10,0,9\n",
+            "1ms",
+        );
+        let ms = clock::MILLI_SECONDS;
+        assert_eq!(
+            arrivals,
+            vec![(2 * ms, 0), (2 * ms, 1), (3 * ms + ms / 2, 1), (5 * ms, 0)]
+        );
+        assert_eq!(field(&results, "replayed_msgs"), Some("4".to_owned()));
+        assert_eq!(field(&results, "replayed_bytes"), Some("170".to_owned()));
+        assert_eq!(field(&results, "unconnected_msgs"), Some("1".to_owned()));
+        assert_eq!(field(&results, "broken_line"), None);
+    }
+
+    #[test]
+    fn broken_lines_stop_the_replay_where_they_are() {
+        let (arrivals, results) = replay(
+            "trace_backwards",
+            "1ms,100,0\n3ms,100,0\n2ms,100,0\n4ms,100,0\n",
+            "0",
+        );
+        assert_eq!(arrivals.len(), 2);
+        assert_eq!(field(&results, "broken_line"), Some("3".to_owned()));
+
+        let (arrivals, results) = replay("trace_midway", "1ms,100,0\n2ms,lots,0\n", "0");
+        assert_eq!(arrivals.len(), 1);
+        assert_eq!(field(&results, "broken_line"), Some("2".to_owned()));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::core::clock;
+
+use rand::distributions::Distribution as RandDistribution;
+use rand::distributions::{Exp, LogNormal, Normal, Pareto};
+use rand::Rng;
+
+//A random variable that can be sampled with the simulations PRNG, so runs stay reproducible
+#[derive(Clone, Debug)]
+pub enum Distribution {
+    Constant(f64),
+    //low, high. Samples are in [low, high)
+    Uniform(f64, f64),
+    //mean
+    Exponential(f64),
+    //mean, standard deviation
+    Normal(f64, f64),
+    //mean and standard deviation of the underlying normal distribution
+    LogNormal(f64, f64),
+    //scale, shape
+    Pareto(f64, f64),
+    //observed values. Sampled by inverting the empirical cdf, interpolating between the values
+    Empirical(Vec<f64>),
+    //the cumulative probabilities of the ranks 1..=n, see new_zipf. Samples are ranks
+    Zipf(Vec<f64>),
+}
+
+pub fn new_empirical(mut values: Vec<f64>) -> Distribution {
+    if values.is_empty() {
+        panic!("An empirical distribution needs at least one value");
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Distribution::Empirical(values)
+}
+
+//the cdf of a zipf distribution takes 8 bytes per rank
+pub const MAX_ZIPF_RANKS: u64 = 10_000_000;
+
+//ranks 1..=n where rank k is drawn with a probability proportional to 1/k^exponent
+pub fn new_zipf(n: u64, exponent: f64) -> Distribution {
+    if n == 0 || n > MAX_ZIPF_RANKS {
+        panic!(
+            "A zipf distribution needs between 1 and {} ranks, got {}",
+            MAX_ZIPF_RANKS, n
+        );
+    }
+    let mut cdf = Vec::with_capacity(n as usize);
+    let mut sum = 0.0;
+    for k in 1..=n {
+        sum += 1.0 / (k as f64).powf(exponent);
+        cdf.push(sum);
+    }
+    for c in cdf.iter_mut() {
+        *c /= sum;
+    }
+    Distribution::Zipf(cdf)
+}
+
+impl Distribution {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        match self {
+            Distribution::Constant(v) => *v,
+            Distribution::Uniform(low, high) => {
+                if low < high {
+                    rng.gen_range(*low, *high)
+                } else {
+                    *low
+                }
+            }
+            Distribution::Exponential(mean) => Exp::new(1.0 / *mean).sample(rng),
+            Distribution::Normal(mean, sd) => Normal::new(*mean, *sd).sample(rng),
+            Distribution::LogNormal(mean, sd) => LogNormal::new(*mean, *sd).sample(rng),
+            Distribution::Pareto(scale, shape) => Pareto::new(*scale, *shape).sample(rng),
+            Distribution::Empirical(values) => {
+                if values.len() == 1 {
+                    return values[0];
+                }
+                let pos = rng.gen::<f64>() * (values.len() - 1) as f64;
+                let idx = pos as usize;
+                let frac = pos - idx as f64;
+                values[idx] + frac * (values[idx + 1] - values[idx])
+            }
+            Distribution::Zipf(cdf) => {
+                let u = rng.gen::<f64>();
+                let idx = match cdf.binary_search_by(|c| c.partial_cmp(&u).unwrap()) {
+                    Ok(idx) => idx + 1,
+                    Err(idx) => idx,
+                };
+                (idx.min(cdf.len() - 1) + 1) as f64
+            }
+        }
+    }
+
+    //samples a point in time / duration. Negative samples are cut off at 0
+    pub fn sample_time<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        let v = self.sample(rng);
+        if v <= 0.0 {
+            0
+        } else {
+            v.round() as u64
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        match self {
+            Distribution::Constant(v) => *v,
+            Distribution::Uniform(low, high) => (low + high) / 2.0,
+            Distribution::Exponential(mean) => *mean,
+            Distribution::Normal(mean, _) => *mean,
+            Distribution::LogNormal(mean, sd) => (mean + sd * sd / 2.0).exp(),
+            Distribution::Pareto(scale, shape) => {
+                if *shape <= 1.0 {
+                    std::f64::INFINITY
+                } else {
+                    shape * scale / (shape - 1.0)
+                }
+            }
+            Distribution::Empirical(values) => values.iter().sum::<f64>() / values.len() as f64,
+            Distribution::Zipf(cdf) => {
+                let mut last = 0.0;
+                let mut mean = 0.0;
+                for (idx, c) in cdf.iter().enumerate() {
+                    mean += (idx + 1) as f64 * (c - last);
+                    last = *c;
+                }
+                mean
+            }
+        }
+    }
+}
+
+//Err unless p is in [0, 1], NaN included
+pub fn check_probability(what: &str, p: f64) -> Result<(), Box<dyn std::error::Error>> {
+    if !(0.0..=1.0).contains(&p) {
+        return Err(format!("{} has to be in [0, 1], was: {}", what, p).into());
+    }
+    Ok(())
+}
+
+type ValueParser = fn(&str) -> Result<f64, Box<dyn std::error::Error>>;
+
+//"inf" and "NaN" are no parameter of any distribution
+fn parse_number(s: &str) -> Result<f64, Box<dyn std::error::Error>> {
+    match s.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(format!("Not a valid number: \"{}\"", s.trim()).into()),
+    }
+}
+
+fn parse_args(
+    name: &str,
+    args: &str,
+    count: usize,
+    parse_value: ValueParser,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let values = args
+        .split(',')
+        .map(|a| parse_value(a))
+        .collect::<Result<Vec<f64>, _>>()?;
+    if values.len() != count {
+        return Err(format!("{} takes {} arguments, got {}", name, count, values.len()).into());
+    }
+    Ok(values)
+}
+
+//Parses distributions from configuration strings, eg "exponential(10ms)", "uniform(1ms, 5ms)" or
+//"zipf(1000, 0.8)". Arguments may have a time unit (ns, us, ms, s, min, h) and are converted to
+//nanoseconds, plain numbers are taken as they are. A single value is a constant.
+//Parameters the distribution is not defined for (eg a negative standard deviation) are rejected.
+impl std::str::FromStr for Distribution {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_distribution(s, clock::parse_duration)
+    }
+}
+
+//like parsing a Distribution but the arguments are plain numbers without time units, eg for sizes
+pub fn parse_unitless(s: &str) -> Result<Distribution, Box<dyn std::error::Error>> {
+    parse_distribution(s, parse_number)
+}
+
+fn parse_distribution(
+    s: &str,
+    parse_value: ValueParser,
+) -> Result<Distribution, Box<dyn std::error::Error>> {
+    let s = s.trim();
+    let (name, args) = match (s.find('('), s.ends_with(')')) {
+        (Some(open), true) => (s[..open].trim(), &s[open + 1..s.len() - 1]),
+        (None, false) => return Ok(Distribution::Constant(parse_value(s)?)),
+        _ => return Err(format!("Not a valid distribution: \"{}\"", s).into()),
+    };
+
+    let d = match name {
+        "constant" => Distribution::Constant(parse_args(name, args, 1, parse_value)?[0]),
+        "uniform" => {
+            let a = parse_args(name, args, 2, parse_value)?;
+            if a[0] > a[1] {
+                return Err(format!("uniform needs low <= high: \"{}\"", s).into());
+            }
+            Distribution::Uniform(a[0], a[1])
+        }
+        "exponential" => {
+            let mean = parse_args(name, args, 1, parse_value)?[0];
+            if mean.is_nan() || mean <= 0.0 {
+                return Err(format!("exponential needs a positive mean: \"{}\"", s).into());
+            }
+            Distribution::Exponential(mean)
+        }
+        "normal" | "lognormal" => {
+            let a = parse_args(name, args, 2, parse_value)?;
+            if a[1].is_nan() || a[1] < 0.0 {
+                return Err(format!("{} needs a standard deviation >= 0: \"{}\"", name, s).into());
+            }
+            if name == "normal" {
+                Distribution::Normal(a[0], a[1])
+            } else {
+                Distribution::LogNormal(a[0], a[1])
+            }
+        }
+        "pareto" => {
+            let a = parse_args(name, args, 2, parse_value)?;
+            if a.iter().any(|x| x.is_nan() || *x <= 0.0) {
+                return Err(format!("pareto needs a positive scale and shape: \"{}\"", s).into());
+            }
+            Distribution::Pareto(a[0], a[1])
+        }
+        "zipf" => {
+            let a = parse_args(name, args, 2, parse_value)?;
+            if a[0] < 1.0 || a[0] > MAX_ZIPF_RANKS as f64 || a[0].fract() != 0.0 {
+                return Err(format!(
+                    "zipf needs a whole number of ranks between 1 and {}: \"{}\"",
+                    MAX_ZIPF_RANKS, s
+                )
+                .into());
+            }
+            new_zipf(a[0] as u64, a[1])
+        }
+        "empirical" => {
+            let count = args.split(',').count();
+            new_empirical(parse_args(name, args, count, parse_value)?)
+        }
+        other => return Err(format!("Unknown distribution \"{}\"", other).into()),
+    };
+    Ok(d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prng::XorShiftRng;
+    use rand::SeedableRng;
+
+    fn sample_mean(d: &Distribution, n: usize) -> f64 {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        (0..n).map(|_| d.sample(&mut rng)).sum::<f64>() / n as f64
+    }
+
+    #[test]
+    fn parse_distributions() {
+        match "10ms".parse::<Distribution>().unwrap() {
+            Distribution::Constant(v) => assert_eq!(v, 10e6),
+            d => panic!("parsed {:?}", d),
+        }
+        match "uniform(1ms, 5ms)".parse::<Distribution>().unwrap() {
+            Distribution::Uniform(low, high) => assert_eq!((low, high), (1e6, 5e6)),
+            d => panic!("parsed {:?}", d),
+        }
+        match " normal(-2, 0.5) ".parse::<Distribution>().unwrap() {
+            Distribution::Normal(mean, sd) => assert_eq!((mean, sd), (-2.0, 0.5)),
+            d => panic!("parsed {:?}", d),
+        }
+        match "empirical(3, 1, 2)".parse::<Distribution>().unwrap() {
+            Distribution::Empirical(values) => assert_eq!(values, vec![1.0, 2.0, 3.0]),
+            d => panic!("parsed {:?}", d),
+        }
+        match "zipf(4, 1)".parse::<Distribution>().unwrap() {
+            Distribution::Zipf(cdf) => assert_eq!(cdf.len(), 4),
+            d => panic!("parsed {:?}", d),
+        }
+    }
+
+    #[test]
+    fn parse_unitless_takes_plain_numbers() {
+        match parse_unitless("uniform(64, 1500)").unwrap() {
+            Distribution::Uniform(low, high) => assert_eq!((low, high), (64.0, 1500.0)),
+            d => panic!("parsed {:?}", d),
+        }
+        assert!(parse_unitless("1500").is_ok());
+        assert!(parse_unitless("10ms").is_err());
+        assert!(parse_unitless("exponential(1s)").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_invalid_parameters() {
+        for s in &[
+            "uniform(5, 1)",
+            "exponential(0)",
+            "exponential(-1ms)",
+            "normal(0, -1)",
+            "lognormal(0, -1)",
+            "pareto(0, 1)",
+            "pareto(1, 0)",
+            "pareto(1, -2)",
+            "zipf(0, 1)",
+            "zipf(2.5, 1)",
+            "zipf(100000000, 1)",
+            "zipf(5, NaN)",
+            "exponential(NaN)",
+            "uniform(1, NaN)",
+            "normal(0, inf)",
+            "constant(inf)",
+            "normal(1)",
+            "gamma(1, 2)",
+            "exponential(1",
+        ] {
+            assert!(s.parse::<Distribution>().is_err(), "{} was accepted", s);
+        }
+        for s in &[
+            "NaN",
+            "exponential(NaN)",
+            "pareto(NaN, 1)",
+            "normal(0, -inf)",
+        ] {
+            assert!(parse_unitless(s).is_err(), "{} was accepted", s);
+        }
+    }
+
+    #[test]
+    fn sample_means() {
+        let n = 100000;
+        let close = |d: Distribution, expected: f64| {
+            let mean = sample_mean(&d, n);
+            assert!(
+                (mean - expected).abs() < 0.02 * expected.abs().max(1.0),
+                "{:?}: {} != {}",
+                d,
+                mean,
+                expected
+            );
+            assert!((d.mean() - expected).abs() < 1e-9);
+        };
+        close(Distribution::Constant(3.0), 3.0);
+        close(Distribution::Uniform(2.0, 4.0), 3.0);
+        close(Distribution::Exponential(10.0), 10.0);
+        close(Distribution::Normal(5.0, 2.0), 5.0);
+        close(Distribution::Pareto(1.0, 3.0), 1.5);
+        close(new_empirical(vec![0.0, 10.0]), 5.0);
+    }
+
+    #[test]
+    fn zipf_rank_frequencies() {
+        //rank k has probability (1/k) / (1 + 1/2 + 1/3)
+        let d = new_zipf(3, 1.0);
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut counts = [0u64; 3];
+        let n = 110000;
+        for _ in 0..n {
+            let rank = d.sample(&mut rng);
+            assert!(rank >= 1.0 && rank <= 3.0 && rank.fract() == 0.0);
+            counts[rank as usize - 1] += 1;
+        }
+        for (count, expected) in counts.iter().zip(&[60000.0, 30000.0, 20000.0]) {
+            assert!((*count as f64 - expected).abs() < 1000.0, "{:?}", counts);
+        }
+        assert!((d.mean() - 18.0 / 11.0).abs() < 1e-9);
+
+        let single = new_zipf(1, 0.8);
+        assert_eq!(single.sample(&mut rng), 1.0);
+    }
+
+    #[test]
+    fn sample_time_cuts_off_negative_values() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        assert_eq!(Distribution::Constant(-5.0).sample_time(&mut rng), 0);
+        assert_eq!(Distribution::Constant(2.6).sample_time(&mut rng), 3);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod distribution;
+pub mod streams;
+pub mod variates;
@@ -0,0 +1,138 @@
+use crate::core::id_mngmnt::id_types::{ConnectionId, ModuleId};
+
+use rand::prng::XorShiftRng;
+use rand::SeedableRng;
+
+use std::collections::BTreeMap;
+
+//Independent random number streams. Stream 0 is seeded with the global seed, every other stream
+//with the global seed mixed with its index, so adding a stream (or a module that draws from its own
+//stream) does not change the numbers any other stream produces.
+//Modules and connections that are not mapped to a stream draw from their own one, derived from
+//their id, so they don't change each others numbers either.
+pub struct RngStreams {
+    seed: [u8; 16],
+    streams: BTreeMap<usize, XorShiftRng>,
+    names: Vec<String>,
+
+    module_map: BTreeMap<ModuleId, usize>,
+    connection_map: BTreeMap<ConnectionId, usize>,
+}
+
+//the streams of unmapped modules and connections start here, far above the named streams
+pub const ID_STREAMS: usize = 1 << 31;
+
+pub fn new_rng_streams(seed: [u8; 16]) -> RngStreams {
+    RngStreams {
+        seed: seed,
+        streams: BTreeMap::new(),
+        names: vec!["default".to_owned()],
+
+        module_map: BTreeMap::new(),
+        connection_map: BTreeMap::new(),
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn stream_seed(seed: [u8; 16], index: usize) -> [u8; 16] {
+    if index == 0 {
+        return seed;
+    }
+
+    let mut mixed = seed;
+    let mut state = index as u64;
+    for chunk in mixed.chunks_mut(8) {
+        let bytes = splitmix64(&mut state).to_le_bytes();
+        for (b, m) in chunk.iter_mut().zip(bytes.iter()) {
+            *b ^= m;
+        }
+    }
+    mixed
+}
+
+impl RngStreams {
+    //the stream with the given index, streams that were not used yet are created on the fly
+    pub fn get(&mut self, index: usize) -> &mut XorShiftRng {
+        let seed = self.seed;
+        self.streams
+            .entry(index)
+            .or_insert_with(|| XorShiftRng::from_seed(stream_seed(seed, index)))
+    }
+
+    //index of the stream with this name. Unknown names get the next free index
+    pub fn named(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|n| n == name) {
+            Some(idx) => idx,
+            None => {
+                self.names.push(name.to_owned());
+                self.names.len() - 1
+            }
+        }
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index).map(|n| n.as_str())
+    }
+
+    pub fn map_module(&mut self, module: ModuleId, stream: usize) {
+        self.module_map.insert(module, stream);
+    }
+
+    pub fn map_connection(&mut self, conn: ConnectionId, stream: usize) {
+        self.connection_map.insert(conn, stream);
+    }
+
+    //module and connection ids come from the same counter, so their own streams don't overlap
+    pub fn module_stream(&self, module: ModuleId) -> usize {
+        match self.module_map.get(&module) {
+            Some(stream) => *stream,
+            None => ID_STREAMS + module.raw() as usize,
+        }
+    }
+
+    pub fn connection_stream(&self, conn: ConnectionId) -> usize {
+        match self.connection_map.get(&conn) {
+            Some(stream) => *stream,
+            None => ID_STREAMS + conn.raw() as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn unmapped_ids_get_their_own_stream() {
+        let mut rngs = new_rng_streams([3; 16]);
+        let a = rngs.module_stream(ModuleId(1));
+        let b = rngs.module_stream(ModuleId(2));
+        let c = rngs.connection_stream(ConnectionId(3));
+        assert!(a != b && b != c && a != c);
+        assert!(a != 0 && a != rngs.named("default"));
+
+        let first: u64 = rngs.get(b).gen();
+        let mut other = new_rng_streams([3; 16]);
+        //drawing from another stream first does not change the numbers of this one
+        let _: u64 = other.get(a).gen();
+        assert_eq!(other.get(b).gen::<u64>(), first);
+    }
+
+    #[test]
+    fn mapped_ids_share_the_stream() {
+        let mut rngs = new_rng_streams([3; 16]);
+        let shared = rngs.named("shared");
+        rngs.map_module(ModuleId(1), shared);
+        rngs.map_connection(ConnectionId(2), shared);
+        assert_eq!(rngs.module_stream(ModuleId(1)), shared);
+        assert_eq!(rngs.connection_stream(ConnectionId(2)), shared);
+    }
+}
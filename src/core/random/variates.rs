@@ -0,0 +1,133 @@
+use crate::core::random::distribution::{new_zipf, Distribution};
+
+use rand::prng::XorShiftRng;
+use rand::Rng;
+
+//Common random variates on the stream of the module that is currently handled, see
+//EventHandleContext::random. Times are in nanoseconds like everything else.
+pub struct Random<'a> {
+    pub rng: &'a mut XorShiftRng,
+}
+
+impl<'a> Random<'a> {
+    pub fn sample(&mut self, dist: &Distribution) -> f64 {
+        dist.sample(self.rng)
+    }
+
+    //samples a duration, negative values are cut off at 0
+    pub fn sample_time(&mut self, dist: &Distribution) -> u64 {
+        dist.sample_time(self.rng)
+    }
+
+    //in [0, 1)
+    pub fn uniform01(&mut self) -> f64 {
+        self.rng.gen::<f64>()
+    }
+
+    //in [low, high)
+    pub fn uniform(&mut self, low: f64, high: f64) -> f64 {
+        self.sample(&Distribution::Uniform(low, high))
+    }
+
+    //in [low, high)
+    pub fn uniform_int(&mut self, low: u64, high: u64) -> u64 {
+        self.rng.gen_range(low, high)
+    }
+
+    pub fn bernoulli(&mut self, p: f64) -> bool {
+        self.rng.gen::<f64>() < p
+    }
+
+    pub fn exponential(&mut self, mean: f64) -> f64 {
+        self.sample(&Distribution::Exponential(mean))
+    }
+
+    pub fn normal(&mut self, mean: f64, sd: f64) -> f64 {
+        self.sample(&Distribution::Normal(mean, sd))
+    }
+
+    pub fn lognormal(&mut self, mu: f64, sigma: f64) -> f64 {
+        self.sample(&Distribution::LogNormal(mu, sigma))
+    }
+
+    pub fn pareto(&mut self, scale: f64, shape: f64) -> f64 {
+        self.sample(&Distribution::Pareto(scale, shape))
+    }
+
+    //rank in 1..=n. Builds the cdf on every call, keep a Distribution around for repeated draws
+    pub fn zipf(&mut self, n: u64, exponent: f64) -> u64 {
+        self.sample(&new_zipf(n, exponent)) as u64
+    }
+
+    //an index into weights, drawn with probability proportional to its weight. Err if a weight is
+    //negative or not finite or if they are all 0
+    pub fn weighted_index(&mut self, weights: &[f64]) -> Result<usize, Box<dyn std::error::Error>> {
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(format!("Weights have to be finite and >= 0: {:?}", weights).into());
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err(format!("At least one weight has to be > 0: {:?}", weights).into());
+        }
+        let mut u = self.rng.gen::<f64>() * total;
+        for (idx, w) in weights.iter().enumerate() {
+            if u < *w {
+                return Ok(idx);
+            }
+            u -= w;
+        }
+        //rounding can leave u at total, the last index with a weight is the one that was drawn
+        Ok(weights.iter().rposition(|w| *w > 0.0).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn weighted_index_follows_the_weights() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut random = Random { rng: &mut rng };
+        let mut counts = [0u64; 3];
+        for _ in 0..60000 {
+            counts[random.weighted_index(&[1.0, 0.0, 2.0]).unwrap()] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        assert!(counts[0] > 19000 && counts[0] < 21000, "{:?}", counts);
+    }
+
+    #[test]
+    fn weighted_index_rejects_weights_it_can_not_draw_from() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut random = Random { rng: &mut rng };
+        assert!(random.weighted_index(&[]).is_err());
+        assert!(random.weighted_index(&[0.0, 0.0]).is_err());
+        assert!(random.weighted_index(&[1.0, -1.0]).is_err());
+        assert!(random.weighted_index(&[1.0, std::f64::NAN]).is_err());
+        assert!(random.weighted_index(&[1.0, std::f64::INFINITY]).is_err());
+        assert_eq!(random.weighted_index(&[0.0, 3.0, 0.0]).unwrap(), 1);
+    }
+
+    #[test]
+    fn variates_stay_in_range() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut random = Random { rng: &mut rng };
+        let mut hits = 0;
+        for _ in 0..10000 {
+            let u = random.uniform01();
+            assert!(u >= 0.0 && u < 1.0);
+            let i = random.uniform_int(3, 6);
+            assert!(i >= 3 && i < 6);
+            let z = random.zipf(5, 1.2);
+            assert!(z >= 1 && z <= 5);
+            assert!(random.exponential(2.0) >= 0.0);
+            assert!(random.pareto(1.5, 2.0) >= 1.5);
+            if random.bernoulli(0.25) {
+                hits += 1;
+            }
+        }
+        assert!(hits > 2300 && hits < 2700, "{}", hits);
+    }
+}
@@ -1,17 +1,24 @@
 use crate::core::clock;
+use crate::core::commands::{Command, LinkStateChange};
 use crate::core::connection::connection::Connection;
 use crate::core::connection::connection::PortKind;
 use crate::core::connection::mesh;
 use crate::core::connection::mesh::ConnectionMesh;
 use crate::core::contexts::{EventHandleContext, SimulationContext};
+use crate::core::events::ev;
 use crate::core::events::event::TimerEvent;
+use crate::core::factory::module_factory;
+use crate::core::factory::module_factory::ModuleFactory;
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
-use crate::core::id_mngmnt::id_types::{GateId, ModuleId, PortId};
-use crate::core::messages::message::Message;
+use crate::core::id_mngmnt::id_types::{ConnectionId, GateId, ModuleId, PortId};
+use crate::core::messages::message::{Message, TimedMessage};
+use crate::core::messages::text_message;
 use crate::core::modules::module::{FinalizeResult, Module};
-
-use rand::prng::XorShiftRng;
-use rand::SeedableRng;
+use crate::core::random::streams::{new_rng_streams, RngStreams};
+use crate::core::scenario::{Action, ModuleRef, PortRef, Scenario};
+use crate::core::trace;
+use crate::core::trace::TraceEntry;
+use crate::core::validation::{validate_topology, Severity, TopologyProblem};
 
 use std::cell::RefCell;
 use std::io::Write;
@@ -25,11 +32,15 @@ pub enum Tree<T> {
 }
 
 struct ModuleMngr {
-    modules: std::collections::HashMap<ModuleId, Rc<RefCell<Box<Module>>>>,
+    modules: std::collections::BTreeMap<ModuleId, Rc<RefCell<Box<Module>>>>,
 }
 
 impl ModuleMngr {
-    fn finalize_modules(&mut self, tree: &Tree<(String, ModuleId)>, ctx: &mut EventHandleContext) {
+    fn finalize_modules(
+        &mut self,
+        tree: &Tree<(String, ModuleId)>,
+        ctx: &mut EventHandleContext,
+    ) -> Vec<(String, String, String)> {
         let mut global_results = Vec::new();
 
         match tree {
@@ -49,6 +60,7 @@ impl ModuleMngr {
         //for (mname, fname, val) in global_results {
         //    println!("{} {} {}", mname, fname, val);
         //}
+        global_results
     }
 
     fn finalize_modules_rec(
@@ -65,12 +77,17 @@ impl ModuleMngr {
                 //descend then finalize
                 for c in children {
                     match self.finalize_modules_rec(c, ctx) {
+                        //results of children are reported under "Parent.Child", the same path
+                        //scenarios use to refer to them. Up to the sink statistics the names were
+                        //concatenated without the dot, scripts matching result names of nested
+                        //modules need the dotted path now
                         Some(res) => {
                             let mut renamed = res
                                 .results
                                 .iter()
                                 .map(|(mname, fname, val)| {
                                     let mut new_name = name.clone();
+                                    new_name.push('.');
                                     new_name.push_str(mname);
                                     (new_name, fname.clone(), val.clone())
                                 })
@@ -81,6 +98,7 @@ impl ModuleMngr {
                     }
                 }
 
+                ctx.mctx.stream = ctx.mctx.rngs.module_stream(*id);
                 match self
                     .modules
                     .get_mut(&id)
@@ -94,18 +112,21 @@ impl ModuleMngr {
                     None => {}
                 }
             }
-            Tree::Leaf((_, id)) => match self
-                .modules
-                .get_mut(&id)
-                .unwrap()
-                .borrow_mut()
-                .finalize(ctx)
-            {
-                Some(mut r) => {
-                    local_results.results.append(&mut r.results);
+            Tree::Leaf((_, id)) => {
+                ctx.mctx.stream = ctx.mctx.rngs.module_stream(*id);
+                match self
+                    .modules
+                    .get_mut(&id)
+                    .unwrap()
+                    .borrow_mut()
+                    .finalize(ctx)
+                {
+                    Some(mut r) => {
+                        local_results.results.append(&mut r.results);
+                    }
+                    None => {}
                 }
-                None => {}
-            },
+            }
         }
 
         if local_results.results.len() > 0 {
@@ -121,12 +142,23 @@ pub struct Runner {
 
     timer_queue: std::collections::BinaryHeap<TimerEvent>,
     msg_buffer: std::collections::VecDeque<(Box<Message>, GateId, PortId)>,
+    commands: std::collections::VecDeque<Command>,
 
     pub connections: ConnectionMesh,
-    prng: rand::prng::XorShiftRng,
+    pub rngs: RngStreams,
 
     pub module_forest: Vec<Tree<(String, ModuleId)>>,
     modules: ModuleMngr,
+
+    //used by modules to create other modules while the simulation runs
+    pub factory: ModuleFactory,
+
+    scenario: Option<Scenario>,
+
+    //only recorded if enabled
+    trace: Option<Vec<TraceEntry>>,
+
+    results: Vec<(String, String, String)>,
 }
 
 pub fn new_runner(seed: [u8; 16]) -> Runner {
@@ -134,77 +166,650 @@ pub fn new_runner(seed: [u8; 16]) -> Runner {
         clock: clock::new(),
 
         modules: ModuleMngr {
-            modules: std::collections::HashMap::new(),
+            modules: std::collections::BTreeMap::new(),
         },
         timer_queue: std::collections::BinaryHeap::new(),
         msg_buffer: std::collections::VecDeque::new(),
+        commands: std::collections::VecDeque::new(),
 
         connections: ConnectionMesh {
-            connections: std::collections::HashMap::new(),
-            gates: std::collections::HashMap::new(),
+            connections: std::collections::BTreeMap::new(),
+            gates: std::collections::BTreeMap::new(),
+            paths: std::collections::BTreeMap::new(),
+            links_down: std::collections::BTreeSet::new(),
+            dropped_link_down: std::collections::BTreeMap::new(),
+            disconnected_ports: std::collections::BTreeSet::new(),
+            dropped_disconnected: 0,
+            declared_gates: std::collections::BTreeMap::new(),
 
             messages: std::collections::BinaryHeap::new(),
             messages_now: std::collections::VecDeque::new(),
         },
 
-        prng: XorShiftRng::from_seed(seed),
+        rngs: new_rng_streams(seed),
 
         module_forest: Vec::new(),
+        factory: module_factory::new(),
+        scenario: None,
+        trace: None,
+        results: Vec::new(),
     }
 }
 
 impl Runner {
+    //let the connection mesh route messages through containers directly
+    pub fn resolve_paths(&mut self) {
+        let mut passthroughs = std::collections::BTreeMap::new();
+        for (id, module) in &self.modules.modules {
+            let pairs = module.borrow().passthrough_gates();
+            if pairs.is_empty() {
+                continue;
+            }
+
+            let mut mapping = std::collections::BTreeMap::new();
+            for (outer, inner) in pairs {
+                mapping.insert(outer, inner);
+                mapping.insert(inner, outer);
+            }
+            passthroughs.insert(*id, mapping);
+        }
+
+        self.connections.resolve_paths(&passthroughs);
+    }
+
     pub fn init_modules(&mut self, id_reg: &mut IdRegistrar) {
+        self.resolve_paths();
+
+        let ids: Vec<ModuleId> = self.modules.modules.keys().map(|id| *id).collect();
+        self.init_in_stages(ids, id_reg);
+    }
+
+    //every module finishes stage N before any module starts stage N+1. Modules created during
+    //initialization run through all their stages when they are added
+    fn init_in_stages(&mut self, mut ids: Vec<ModuleId>, id_reg: &mut IdRegistrar) {
+        ids.retain(|id| self.modules.modules.contains_key(id));
+        ids.sort();
+        let stages: Vec<u32> = ids
+            .iter()
+            .map(|id| self.modules.modules[id].borrow().num_init_stages())
+            .collect();
+        let max_stages = stages.iter().cloned().max().unwrap_or(0);
+
+        for stage in 0..max_stages {
+            for (id, num_stages) in ids.iter().zip(&stages) {
+                //modules may have been deleted by an earlier one
+                if stage >= *num_stages || !self.modules.modules.contains_key(id) {
+                    continue;
+                }
+                self.init_module(*id, stage, id_reg);
+                self.apply_commands(id_reg);
+            }
+        }
+    }
+
+    fn init_module(&mut self, mod_id: ModuleId, stage: u32, id_reg: &mut IdRegistrar) {
+        let mut gate_map = std::collections::BTreeMap::new();
+        for ((m, g, p), port) in &self.connections.gates {
+            if *m == mod_id {
+                if gate_map.get(g).is_none() {
+                    gate_map.insert(*g, std::collections::BTreeMap::new());
+                }
+                gate_map.get_mut(g).unwrap().insert(*p, *port);
+            }
+        }
+
         let mut ctx = EventHandleContext {
             msgs_to_send: &mut self.msg_buffer,
             timer_queue: &mut self.timer_queue,
+            commands: &mut self.commands,
+            mesh: &self.connections,
+            factory: &self.factory,
 
             mctx: SimulationContext {
-                prng: &mut self.prng,
+                stream: self.rngs.module_stream(mod_id),
+                rngs: &mut self.rngs,
                 id_reg: id_reg,
                 time: &self.clock,
             },
         };
 
-        for (_, module) in &mut self.modules.modules {
-            let mut module = module.borrow_mut();
-            let mod_id = (*module).module_id();
+        self.modules
+            .modules
+            .get(&mod_id)
+            .unwrap()
+            .borrow_mut()
+            .initialize(stage, &gate_map, &mut ctx);
+        self.send_buffered(mod_id, id_reg);
+    }
+
+    //hand everything a module wanted to send to the connection mesh
+    fn send_buffered(&mut self, sender: ModuleId, id_reg: &mut IdRegistrar) {
+        let mut mctx = SimulationContext {
+            stream: self.rngs.module_stream(sender),
+            rngs: &mut self.rngs,
+            id_reg: id_reg,
+            time: &self.clock,
+        };
+
+        while let Some((msg, gate, port)) = self.msg_buffer.pop_front() {
+            self.connections
+                .send_message(msg, sender, gate, port, &mut mctx);
+        }
+    }
 
-            let mut gate_map = std::collections::HashMap::new();
-            for ((m, g, p), port) in &self.connections.gates {
-                if *m == mod_id {
-                    if gate_map.get(g).is_none() {
-                        gate_map.insert(*g, std::collections::HashMap::new());
+    //apply what modules requested while handling their last message or event. Modules that were
+    //added get initialized once all commands of the batch are applied, so they can be connected
+    //by the same handler that created them
+    fn apply_commands(&mut self, id_reg: &mut IdRegistrar) {
+        let mut added = Vec::new();
+        let mut topology_changed = false;
+
+        while let Some(cmd) = self.commands.pop_front() {
+            match cmd {
+                Command::SetLinkState { conn, up, notify } => {
+                    if let Err(e) = self.set_link_state(id_reg, conn, up, notify) {
+                        println!("Error: Could not change link state at runtime: {}", e);
+                    }
+                }
+                Command::AddModule { module, parent } => {
+                    let id = module.module_id();
+                    let name = module.name();
+                    if let Some(p) = parent {
+                        if !self.modules.modules.contains_key(&p) {
+                            println!(
+                                "Error: Could not add module {} at runtime, its parent {} does not exist",
+                                id.raw(),
+                                p.raw()
+                            );
+                            continue;
+                        }
+                    }
+                    match self.add_module(module) {
+                        Ok(()) => {
+                            self.insert_into_tree(parent, name, id);
+                            added.push(id);
+                            topology_changed = true;
+                        }
+                        Err(e) => println!("Error: Could not add module at runtime: {}", e),
                     }
-                    gate_map.get_mut(g).unwrap().insert(*p, *port);
                 }
+                Command::Connect {
+                    conn,
+                    kind,
+                    mod_out,
+                    gate_out,
+                    out_port,
+                    mod_in,
+                    gate_in,
+                    in_port,
+                } => {
+                    //a module asked for a connection that can not be made. The simulation keeps
+                    //running without it, like with a scenario action that fails
+                    match self.connect_modules(
+                        conn, kind, mod_out, gate_out, out_port, mod_in, gate_in, in_port,
+                    ) {
+                        Ok(()) => topology_changed = true,
+                        Err(e) => println!(
+                            "Error: Could not connect modules at runtime, the connection is dropped: {}",
+                            e
+                        ),
+                    }
+                }
+                Command::Disconnect { conn } => match self.connections.disconnect(conn) {
+                    Ok(()) => topology_changed = true,
+                    Err(e) => println!("Error: Could not disconnect at runtime: {}", e),
+                },
+                Command::DeleteModule { module } => match self.delete_module(module, id_reg) {
+                    Ok(()) => topology_changed = true,
+                    Err(e) => println!("Error: Could not delete module at runtime: {}", e),
+                },
+            }
+        }
+
+        if topology_changed {
+            self.resolve_paths();
+        }
+        self.init_in_stages(added, id_reg);
+    }
+
+    fn insert_into_tree(&mut self, parent: Option<ModuleId>, name: String, id: ModuleId) {
+        let parent = match parent {
+            Some(p) => p,
+            None => {
+                self.module_forest.push(Tree::Leaf((name, id)));
+                return;
+            }
+        };
+
+        fn insert(
+            tree: &mut Tree<(String, ModuleId)>,
+            parent: ModuleId,
+            new: &mut Option<Tree<(String, ModuleId)>>,
+        ) {
+            let is_parent = match tree {
+                Tree::Node((_, id), _) | Tree::Leaf((_, id)) => *id == parent,
+            };
+            if is_parent {
+                if let Tree::Leaf(data) = tree {
+                    *tree = Tree::Node(data.clone(), Vec::new());
+                }
+                if let Tree::Node(_, children) = tree {
+                    children.push(new.take().unwrap());
+                }
+                return;
+            }
+            if let Tree::Node(_, children) = tree {
+                for c in children {
+                    if new.is_none() {
+                        return;
+                    }
+                    insert(c, parent, new);
+                }
+            }
+        }
+
+        let mut new = Some(Tree::Leaf((name, id)));
+        for tree in &mut self.module_forest {
+            if new.is_none() {
+                break;
+            }
+            insert(tree, parent, &mut new);
+        }
+        if new.is_some() {
+            panic!(
+                "Tried to add module {} below module {} which is not in the module forest",
+                id.raw(),
+                parent.raw()
+            );
+        }
+    }
+
+    //take the subtree of a module out of the module forest
+    fn remove_from_tree(&mut self, module: ModuleId) -> Option<Tree<(String, ModuleId)>> {
+        fn id_of(tree: &Tree<(String, ModuleId)>) -> ModuleId {
+            match tree {
+                Tree::Node((_, id), _) | Tree::Leaf((_, id)) => *id,
+            }
+        }
+        fn remove(
+            trees: &mut Vec<Tree<(String, ModuleId)>>,
+            module: ModuleId,
+        ) -> Option<Tree<(String, ModuleId)>> {
+            if let Some(idx) = trees.iter().position(|t| id_of(t) == module) {
+                return Some(trees.remove(idx));
+            }
+            for tree in trees.iter_mut() {
+                if let Tree::Node(_, children) = tree {
+                    if let Some(found) = remove(children, module) {
+                        return Some(found);
+                    }
+                }
+            }
+            None
+        }
+
+        remove(&mut self.module_forest, module)
+    }
+
+    //the path ("Parent.Child") of the module a module is below of in the module forest
+    fn parent_path(&self, module: ModuleId) -> Option<String> {
+        fn find(
+            trees: &[Tree<(String, ModuleId)>],
+            module: ModuleId,
+            path: &mut Vec<String>,
+        ) -> bool {
+            for tree in trees {
+                match tree {
+                    Tree::Leaf((_, id)) if *id == module => return true,
+                    Tree::Node((_, id), _) if *id == module => return true,
+                    Tree::Node((name, _), children) => {
+                        path.push(name.clone());
+                        if find(children, module, path) {
+                            return true;
+                        }
+                        path.pop();
+                    }
+                    Tree::Leaf(_) => {}
+                }
+            }
+            false
+        }
+
+        let mut path = Vec::new();
+        if find(&self.module_forest, module, &mut path) && !path.is_empty() {
+            Some(path.join("."))
+        } else {
+            None
+        }
+    }
+
+    //finalize and remove a module and all modules below it in the module forest
+    pub fn delete_module(
+        &mut self,
+        module: ModuleId,
+        id_reg: &mut IdRegistrar,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.modules.modules.contains_key(&module) {
+            return Err(format!(
+                "Tried to delete module that does not exist: {}",
+                module.raw()
+            )
+            .into());
+        }
+        let prefix = self.parent_path(module);
+        let subtree = match self.remove_from_tree(module) {
+            Some(tree) => tree,
+            None => Tree::Leaf((String::new(), module)),
+        };
+
+        let mut ctx = EventHandleContext {
+            msgs_to_send: &mut self.msg_buffer,
+            timer_queue: &mut self.timer_queue,
+            commands: &mut self.commands,
+            mesh: &self.connections,
+            factory: &self.factory,
+
+            mctx: SimulationContext {
+                stream: self.rngs.module_stream(module),
+                rngs: &mut self.rngs,
+                id_reg: id_reg,
+                time: &self.clock,
+            },
+        };
+        if let Some(r) = self.modules.finalize_modules_rec(&subtree, &mut ctx) {
+            //named by their path like the results of the modules still there at the end
+            for (mname, fname, val) in r.results {
+                let mname = match &prefix {
+                    Some(p) => format!("{}.{}", p, mname),
+                    None => mname,
+                };
+                self.results.push((mname, fname, val));
+            }
+        }
+        //whatever was sent in finalize is dropped with the module
+        self.msg_buffer.clear();
+
+        let mut ids = Vec::new();
+        fn collect(tree: &Tree<(String, ModuleId)>, ids: &mut Vec<ModuleId>) {
+            match tree {
+                Tree::Node((_, id), children) => {
+                    ids.push(*id);
+                    for c in children {
+                        collect(c, ids);
+                    }
+                }
+                Tree::Leaf((_, id)) => ids.push(*id),
+            }
+        }
+        collect(&subtree, &mut ids);
+
+        for id in ids {
+            self.connections.remove_module(id);
+            self.timer_queue.retain(|ev| ev.mod_id != id);
+            self.modules.modules.remove(&id);
+        }
+        Ok(())
+    }
+
+    //take a connection down or bring it back up. Messages sent over a connection that is down are
+    //dropped and counted. If notify is set the modules at both ends get an Ev<LinkStateChange>, if
+    //they asked for it with Module::wants_link_state
+    pub fn set_link_state(
+        &mut self,
+        id_reg: &mut IdRegistrar,
+        conn: ConnectionId,
+        up: bool,
+        notify: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.connections.connections.contains_key(&conn) {
+            return Err(format!("No connection with id {}", conn.raw()).into());
+        }
+        if self.connections.is_link_up(conn) == up {
+            return Ok(());
+        }
+        self.connections.set_link_state(conn, up)?;
+
+        if !notify {
+            return Ok(());
+        }
+        //containers only pass messages through and don't handle events. If the connection ends at
+        //one, the modules behind it are found through the resolved paths
+        let is_container = |module: &ModuleId| match self.modules.modules.get(module) {
+            Some(m) => !m.borrow().passthrough_gates().is_empty(),
+            None => true,
+        };
+        let mut endpoints = self.connections.endpoints(conn);
+        if endpoints.iter().any(|(module, _, _)| is_container(module)) {
+            endpoints.extend(self.connections.path_endpoints(conn));
+        }
+        let wants_link_state = |module: &ModuleId| match self.modules.modules.get(module) {
+            Some(m) => m.borrow().wants_link_state(),
+            None => false,
+        };
+        let endpoints: std::collections::BTreeSet<(ModuleId, GateId, PortId)> = endpoints
+            .into_iter()
+            .filter(|(module, _, _)| !is_container(module) && wants_link_state(module))
+            .collect();
+
+        for (module, gate, port) in endpoints {
+            self.timer_queue.push(TimerEvent {
+                time: self.clock.now(),
+                mod_id: module,
+                event: Box::new(ev::new_ev(
+                    id_reg,
+                    LinkStateChange {
+                        conn: conn,
+                        gate: gate,
+                        port: port,
+                        up: up,
+                    },
+                )),
+            });
+        }
+        Ok(())
+    }
+
+    //actions of the scenario are applied at their time, before the events and messages of that time
+    pub fn set_scenario(&mut self, scenario: Scenario) {
+        self.scenario = Some(scenario);
+    }
+
+    //find a module by its path in the module forest ("Parent.Child") or its id
+    pub fn find_module(&self, module: &ModuleRef) -> Option<ModuleId> {
+        let path = match module {
+            ModuleRef::Id(id) => {
+                return match self.modules.modules.get(&ModuleId(*id)) {
+                    Some(_) => Some(ModuleId(*id)),
+                    None => None,
+                };
+            }
+            ModuleRef::Path(path) => path,
+        };
+
+        let mut level: &[Tree<(String, ModuleId)>] = &self.module_forest;
+        let mut found = None;
+        for name in path.split('.') {
+            let next = level.iter().find(|tree| match tree {
+                Tree::Node((n, _), _) | Tree::Leaf((n, _)) => n == name,
+            });
+            match next {
+                Some(Tree::Node((_, id), children)) => {
+                    found = Some(*id);
+                    level = children;
+                }
+                Some(Tree::Leaf((_, id))) => {
+                    found = Some(*id);
+                    level = &[];
+                }
+                None => return None,
+            }
+        }
+        found
+    }
+
+    fn find_port(
+        &self,
+        port: &PortRef,
+    ) -> Result<(ModuleId, GateId, PortId), Box<dyn std::error::Error>> {
+        let module = match self.find_module(&port.module) {
+            Some(m) => m,
+            None => return Err(format!("no module {}", port.module).into()),
+        };
+        match self.gate(module, &port.gate, port.port) {
+            Some((gate, p)) => Ok((module, gate, p)),
+            None => Err(format!(
+                "module {} has no port {} on gate {}",
+                port.module, port.port, port.gate
+            )
+            .into()),
+        }
+    }
+
+    fn apply_scenario(
+        &mut self,
+        id_reg: &mut IdRegistrar,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let due = match &mut self.scenario {
+            Some(scenario) => scenario.take_due(self.clock.now()),
+            None => return Ok(()),
+        };
+
+        for ev in due {
+            match self.apply_action(ev.action, id_reg) {
+                Ok(()) => {}
+                Err(e) => return Err(format!("Scenario line {}: {}", ev.line, e).into()),
             }
+        }
+        Ok(())
+    }
 
-            (*module).initialize(&gate_map, &mut ctx);
-            while ctx.msgs_to_send.len() > 0 {
-                let (msg, gate, port) = ctx.msgs_to_send.pop_front().unwrap();
-                self.connections
-                    .send_message(msg, module.module_id(), gate, port, &mut ctx.mctx);
+    fn apply_action(
+        &mut self,
+        action: Action,
+        id_reg: &mut IdRegistrar,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match action {
+            Action::SetParameter { module, key, value } => {
+                let id = match self.find_module(&module) {
+                    Some(id) => id,
+                    None => return Err(format!("no module {}", module).into()),
+                };
+                self.modules.modules[&id]
+                    .borrow_mut()
+                    .set_parameter(&key, &value)?;
             }
+            Action::SetLinkState { port, up } => {
+                let triple = self.find_port(&port)?;
+                let conn = match self.connections.gates.get(&triple) {
+                    Some(p) => p.conn_id,
+                    None => return Err(format!("{} is not connected", port).into()),
+                };
+                self.set_link_state(id_reg, conn, up, true)?;
+                self.apply_commands(id_reg);
+            }
+            Action::CreateModule {
+                type_str,
+                parent,
+                parameters,
+            } => {
+                let type_id = match self.factory.type_id(id_reg, &type_str) {
+                    Some(id) => id,
+                    None => return Err(format!("unknown module type {}", type_str).into()),
+                };
+                let parent = match parent {
+                    Some(p) => match self.find_module(&p) {
+                        Some(id) => Some(id),
+                        None => return Err(format!("no module {}", p).into()),
+                    },
+                    None => None,
+                };
+                let module = self.factory.generate(id_reg, type_id, &parameters)?;
+                self.commands.push_back(Command::AddModule {
+                    module: module,
+                    parent: parent,
+                });
+                self.apply_commands(id_reg);
+            }
+            Action::DeleteModule { module } => {
+                let id = match self.find_module(&module) {
+                    Some(id) => id,
+                    None => return Err(format!("no module {}", module).into()),
+                };
+                self.commands
+                    .push_back(Command::DeleteModule { module: id });
+                self.apply_commands(id_reg);
+            }
+            Action::Inject { port, text } => {
+                let (module, gate, p) = self.find_port(&port)?;
+                let mut msg = text_message::new_text_msg(id_reg, text);
+                msg.meta.creation_time = Some(self.clock.now());
+                self.connections.messages_now.push_back(TimedMessage {
+                    time: self.clock.now(),
+                    msg: Box::new(msg),
+                    recipient: module,
+                    recp_gate: gate,
+                    recp_port: p,
+                    transit: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    //record every message and event that gets handled, see trace()
+    pub fn enable_trace(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(Vec::new());
+        }
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        match &self.trace {
+            Some(t) => t,
+            None => &[],
         }
     }
 
+    //check the topology for problems that would otherwise only show up while running
+    pub fn validate(&self) -> Vec<TopologyProblem> {
+        let passthroughs = self
+            .modules
+            .modules
+            .iter()
+            .map(|(id, m)| (*id, m.borrow().passthrough_gates()))
+            .filter(|(_, pairs)| !pairs.is_empty())
+            .collect();
+
+        validate_topology(&self.connections, &passthroughs, &self.module_forest)
+    }
+
     pub fn finalize_modules(&mut self, id_reg: &mut IdRegistrar) {
         let mut ctx = EventHandleContext {
             msgs_to_send: &mut self.msg_buffer,
             timer_queue: &mut self.timer_queue,
+            commands: &mut self.commands,
+            mesh: &self.connections,
+            factory: &self.factory,
 
             mctx: SimulationContext {
-                prng: &mut self.prng,
+                stream: 0,
+                rngs: &mut self.rngs,
                 id_reg: id_reg,
                 time: &self.clock,
             },
         };
 
-        self.modules.finalize_modules(
+        let mut results = self.modules.finalize_modules(
             &Tree::Node(("Top".to_owned(), ModuleId(0)), self.module_forest.clone()),
             &mut ctx,
         );
+        self.results.append(&mut results);
+    }
+
+    //(module, field, value) that modules reported when they were finalized, including modules
+    //that were deleted while the simulation ran
+    pub fn results(&self) -> &[(String, String, String)] {
+        &self.results
     }
 
     pub fn add_to_tree(&mut self, tree: Tree<(String, ModuleId)>) {
@@ -226,26 +831,25 @@ impl Runner {
         in_port: PortId,
     ) -> Result<(), Box<std::error::Error>> {
         //check validity of modules
-        match self.modules.modules.get(&mod_in) {
-            None => panic!(
-                "Tried to connect module that does not exist: {}",
-                mod_in.raw()
-            ),
-            Some(_) => {}
-        }
-        match self.modules.modules.get(&mod_out) {
-            None => panic!(
-                "Tried to connect module that does not exist: {}",
-                mod_out.raw(),
-            ),
-            Some(_) => {}
-        }
-        match self.connections.connections.get(&conn.connection_id()) {
-            Some(_) => panic!(
+        for module in &[mod_out, mod_in] {
+            if !self.modules.modules.contains_key(module) {
+                return Err(format!(
+                    "Tried to connect module that does not exist: {}",
+                    module.raw()
+                )
+                .into());
+            }
+        }
+        if self
+            .connections
+            .connections
+            .contains_key(&conn.connection_id())
+        {
+            return Err(format!(
                 "Tried to insert connection: {} that already exists",
                 conn.connection_id().raw()
-            ),
-            None => {}
+            )
+            .into());
         }
 
         //handoff to connection mesh
@@ -255,16 +859,16 @@ impl Runner {
     }
 
     pub fn add_module(&mut self, module: Box<Module>) -> Result<(), Box<std::error::Error>> {
-        match self.modules.modules.get(&module.module_id()) {
-            Some(_) => {
-                panic!(
-                    "Tried to add module with already existing module_id: {}",
-                    module.module_id().raw()
-                );
-            }
-            None => {}
+        if self.modules.modules.contains_key(&module.module_id()) {
+            return Err(format!(
+                "Tried to add module with already existing module_id: {}",
+                module.module_id().raw()
+            )
+            .into());
         }
 
+        self.connections
+            .declare_gates(module.module_id(), module.gates().to_vec());
         self.modules
             .modules
             .insert(module.module_id(), Rc::new(RefCell::new(module)));
@@ -272,6 +876,14 @@ impl Runner {
         Ok(())
     }
 
+    //look up port idx of the gate with the given name on a module, eg: r.gate(router_id, "out", 3)
+    pub fn gate(&self, module: ModuleId, name: &str, idx: u64) -> Option<(GateId, PortId)> {
+        match self.modules.modules.get(&module) {
+            Some(m) => m.borrow().gate(name, idx),
+            None => None,
+        }
+    }
+
     //returns how many messages were found
     fn process_messages(&mut self, id_reg: &mut IdRegistrar) -> u64 {
         let mut msg_counter = 0;
@@ -288,12 +900,37 @@ impl Runner {
             }
 
             let tmsg = self.connections.messages.pop().unwrap();
+            //a message that reaches the next connection of a resolved path, it only shows up
+            //at a module if the path changed since it was sent
+            let mut tmsg = match tmsg.transit {
+                Some(_) => {
+                    let mut mctx = SimulationContext {
+                        stream: 0,
+                        rngs: &mut self.rngs,
+                        id_reg: id_reg,
+                        time: &self.clock,
+                    };
+                    match self.connections.continue_path(tmsg, &mut mctx) {
+                        Some(tmsg) => tmsg,
+                        None => continue,
+                    }
+                }
+                None => tmsg,
+            };
+            tmsg.msg.meta_mut().arrival_time = self.clock.now();
+            if let Some(t) = &mut self.trace {
+                t.push(trace::message_entry(&tmsg, self.clock.now()));
+            }
             let mut ctx = EventHandleContext {
                 msgs_to_send: &mut self.msg_buffer,
                 timer_queue: &mut self.timer_queue,
+                commands: &mut self.commands,
+                mesh: &self.connections,
+                factory: &self.factory,
 
                 mctx: SimulationContext {
-                    prng: &mut self.prng,
+                    stream: self.rngs.module_stream(tmsg.recipient),
+                    rngs: &mut self.rngs,
                     id_reg: id_reg,
                     time: &self.clock,
                 },
@@ -306,12 +943,8 @@ impl Runner {
                 .borrow_mut()
                 .handle_message(tmsg.msg, tmsg.recp_gate, tmsg.recp_port, &mut ctx)
                 .unwrap();
-
-            while ctx.msgs_to_send.len() > 0 {
-                let (msg, gate, port) = ctx.msgs_to_send.pop_front().unwrap();
-                self.connections
-                    .send_message(msg, tmsg.recipient, gate, port, &mut ctx.mctx);
-            }
+            self.send_buffered(tmsg.recipient, id_reg);
+            self.apply_commands(id_reg);
 
             msg_counter += 1;
         }
@@ -321,13 +954,21 @@ impl Runner {
                 break;
             }
 
-            let tmsg = self.connections.messages_now.pop_front().unwrap();
+            let mut tmsg = self.connections.messages_now.pop_front().unwrap();
+            tmsg.msg.meta_mut().arrival_time = self.clock.now();
+            if let Some(t) = &mut self.trace {
+                t.push(trace::message_entry(&tmsg, self.clock.now()));
+            }
             let mut ctx = EventHandleContext {
                 msgs_to_send: &mut self.msg_buffer,
                 timer_queue: &mut self.timer_queue,
+                commands: &mut self.commands,
+                mesh: &self.connections,
+                factory: &self.factory,
 
                 mctx: SimulationContext {
-                    prng: &mut self.prng,
+                    stream: self.rngs.module_stream(tmsg.recipient),
+                    rngs: &mut self.rngs,
                     id_reg: id_reg,
                     time: &self.clock,
                 },
@@ -339,12 +980,8 @@ impl Runner {
                 .borrow_mut()
                 .handle_message(tmsg.msg, tmsg.recp_gate, tmsg.recp_port, &mut ctx)
                 .unwrap();
-
-            while ctx.msgs_to_send.len() > 0 {
-                let (msg, gate, port) = ctx.msgs_to_send.pop_front().unwrap();
-                self.connections
-                    .send_message(msg, tmsg.recipient, gate, port, &mut ctx.mctx);
-            }
+            self.send_buffered(tmsg.recipient, id_reg);
+            self.apply_commands(id_reg);
             msg_counter += 1;
         }
 
@@ -366,40 +1003,39 @@ impl Runner {
             }
 
             let ev = self.timer_queue.pop().unwrap();
+            if let Some(t) = &mut self.trace {
+                t.push(trace::event_entry(&ev));
+            }
 
-            let mut module = match self.modules.modules.get_mut(&ev.mod_id) {
-                Some(m) => m.borrow_mut(),
-                None => panic!(
-                    "Non existent module-ID found in a timer-event: {}",
-                    ev.mod_id.raw(),
-                ),
+            //the module was deleted after the event was scheduled, eg by another module in the same
+            //handler that created the event
+            let module = match self.modules.modules.get(&ev.mod_id) {
+                Some(m) => m,
+                None => continue,
             };
             let mut ctx = EventHandleContext {
                 msgs_to_send: &mut self.msg_buffer,
                 timer_queue: &mut self.timer_queue,
+                commands: &mut self.commands,
+                mesh: &self.connections,
+                factory: &self.factory,
 
                 mctx: SimulationContext {
-                    prng: &mut self.prng,
+                    stream: self.rngs.module_stream(ev.mod_id),
+                    rngs: &mut self.rngs,
                     id_reg: id_reg,
                     time: &self.clock,
                 },
             };
 
-            let result = module.handle_timer_event(ev.event.as_ref(), &mut ctx);
+            let result = module
+                .borrow_mut()
+                .handle_timer_event(ev.event.as_ref(), &mut ctx);
             match result {
                 Err(e) => return Err(e),
                 Ok(_) => {
-                    while ctx.msgs_to_send.len() > 0 {
-                        let (msg, gate, port) = ctx.msgs_to_send.pop_front().unwrap();
-
-                        self.connections.send_message(
-                            msg,
-                            module.module_id(),
-                            gate,
-                            port,
-                            &mut ctx.mctx,
-                        );
-                    }
+                    self.send_buffered(ev.mod_id, id_reg);
+                    self.apply_commands(id_reg);
                 }
             }
 
@@ -430,6 +1066,19 @@ impl Runner {
             }
             None => {}
         }
+
+        match &self.scenario {
+            Some(scenario) => match scenario.next_time() {
+                Some(time) => {
+                    if time <= min {
+                        min = time;
+                        at_least_one = true;
+                    }
+                }
+                None => {}
+            },
+            None => {}
+        }
         if at_least_one {
             Some(min)
         } else {
@@ -492,6 +1141,8 @@ impl Runner {
                 println!("     ");
             }
 
+            self.apply_scenario(id_reg)?;
+
             //process events and messages until no more messages are there and no more events registered for this clock time
 
             loop {
@@ -517,6 +1168,19 @@ impl Runner {
         id_reg: &mut IdRegistrar,
         endtime: u64,
     ) -> Result<(), Box<std::error::Error>> {
+        println!("Validating topology");
+        let problems = self.validate();
+        let mut errors = 0;
+        for p in &problems {
+            if p.severity == Severity::Error {
+                errors += 1;
+            }
+            println!("{:?}: {}", p.severity, p.description);
+        }
+        if errors > 0 {
+            return Err(format!("Topology has {} errors, not running", errors).into());
+        }
+
         println!("Initializing Modules");
         self.init_modules(id_reg);
 
@@ -539,6 +1203,17 @@ impl Runner {
         println!("No more messages nor events available. This simulation is over.");
         println!("Running took: {:?}", duration);
 
+        let dropped: u64 = self.connections.dropped_link_down.values().sum();
+        if dropped > 0 {
+            println!("Messages dropped on down links: {}", dropped);
+        }
+        if self.connections.dropped_disconnected > 0 {
+            println!(
+                "Messages dropped on removed connections: {}",
+                self.connections.dropped_disconnected
+            );
+        }
+
         println!("Finalizing Modules");
         self.finalize_modules(id_reg);
 
@@ -702,3 +1377,522 @@ pub fn print_parent_as_dot(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::connection::Port;
+    use crate::core::connection::simple_connection;
+    use crate::core::events::event::Event;
+    use crate::core::id_mngmnt::id_types::ModuleTypeId;
+    use crate::core::modules::module::{sim_module, HandleResult};
+    use crate::core::scenario::parse_scenario;
+    use crate::core::testing::{new_test_env, TestEnv};
+
+    const IN: GateId = GateId(0);
+    const OUT: GateId = GateId(1);
+
+    type Log = Rc<RefCell<Vec<String>>>;
+    type OnTick = Box<dyn FnMut(ModuleId, &mut EventHandleContext) -> String>;
+
+    struct Tick {}
+
+    //writes everything that happens to it into a log shared with the test. At every time in ticks
+    //it calls on_tick, which sends a message on out unless the test sets something else
+    struct Probe {
+        type_id: ModuleTypeId,
+        id: ModuleId,
+        name: String,
+        log: Log,
+        stages: u32,
+        ticks: Vec<u64>,
+        on_tick: OnTick,
+        ticked: u64,
+    }
+
+    impl Probe {
+        fn log(&self, entry: String) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", self.name, entry));
+        }
+    }
+
+    fn send(_id: ModuleId, ctx: &mut EventHandleContext) -> String {
+        let msg = text_message::new_text_msg(ctx.mctx.id_reg, "tick".to_owned());
+        ctx.msgs_to_send.push_back((Box::new(msg), OUT, PortId(0)));
+        "sent".to_owned()
+    }
+
+    #[sim_module(
+        type_str = "ProbeModule",
+        gate(id = IN, name = "in", dir = input),
+        gate(id = OUT, name = "out", dir = output),
+    )]
+    impl Module for Probe {
+        fn handle_message(
+            &mut self,
+            _msg: Box<dyn Message>,
+            _gate: GateId,
+            _port: PortId,
+            ctx: &mut EventHandleContext,
+        ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+            self.log(format!("msg {}", ctx.mctx.time.now()));
+            Ok(HandleResult {})
+        }
+
+        fn handle_timer_event(
+            &mut self,
+            ev: &dyn Event,
+            ctx: &mut EventHandleContext,
+        ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+            let now = ctx.mctx.time.now();
+            match ev.downcast_ref::<LinkStateChange>() {
+                Some(change) if change.payload.up => self.log(format!("link up {}", now)),
+                Some(_) => self.log(format!("link down {}", now)),
+                None => {
+                    self.ticked += 1;
+                    let done = (self.on_tick)(self.id, ctx);
+                    self.log(format!("tick {} {}", now, done));
+                }
+            }
+            Ok(HandleResult {})
+        }
+
+        fn wants_link_state(&self) -> bool {
+            true
+        }
+
+        fn num_init_stages(&self) -> u32 {
+            self.stages
+        }
+
+        fn initialize(
+            &mut self,
+            stage: u32,
+            _gates: &std::collections::BTreeMap<GateId, std::collections::BTreeMap<PortId, Port>>,
+            ctx: &mut EventHandleContext,
+        ) {
+            self.log(format!("init {}", stage));
+            if stage == 0 {
+                for time in &self.ticks {
+                    ctx.timer_queue.push(TimerEvent {
+                        time: *time,
+                        mod_id: self.id,
+                        event: Box::new(ev::new_ev(ctx.mctx.id_reg, Tick {})),
+                    });
+                }
+            }
+        }
+
+        fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+            self.log("finalize".to_owned());
+            Some(FinalizeResult {
+                results: vec![(
+                    self.name.clone(),
+                    "ticks".to_owned(),
+                    self.ticked.to_string(),
+                )],
+            })
+        }
+    }
+
+    fn setup(env: &mut TestEnv) -> (Runner, Log) {
+        Probe::register(&mut env.id_reg);
+        simple_connection::register(&mut env.id_reg);
+        text_message::register(&mut env.id_reg);
+        (new_runner([7; 16]), Rc::new(RefCell::new(Vec::new())))
+    }
+
+    fn probe(
+        r: &mut Runner,
+        env: &mut TestEnv,
+        log: &Log,
+        name: &str,
+        ticks: Vec<u64>,
+        on_tick: OnTick,
+    ) -> ModuleId {
+        staged_probe(r, env, log, name, 1, ticks, on_tick)
+    }
+
+    fn staged_probe(
+        r: &mut Runner,
+        env: &mut TestEnv,
+        log: &Log,
+        name: &str,
+        stages: u32,
+        ticks: Vec<u64>,
+        on_tick: OnTick,
+    ) -> ModuleId {
+        let id = env.id_reg.new_module_id();
+        let p = Probe {
+            id: id,
+            type_id: env
+                .id_reg
+                .lookup_module_id(Probe::TYPE_STR.to_owned())
+                .unwrap(),
+            name: name.to_owned(),
+            log: log.clone(),
+            stages: stages,
+            ticks: ticks,
+            on_tick: on_tick,
+            ticked: 0,
+        };
+        r.add_module(Box::new(p)).unwrap();
+        r.add_to_tree(Tree::Leaf((name.to_owned(), id)));
+        id
+    }
+
+    //out of from to in of to, with a delay of 1
+    fn link(r: &mut Runner, env: &mut TestEnv, from: ModuleId, to: ModuleId) -> ConnectionId {
+        let conn = simple_connection::new_simple_connection(&mut env.id_reg, 1, 0, 0);
+        let id = conn.connection_id();
+        r.connect_modules(
+            Box::new(conn),
+            mesh::ConnectionKind::Onedirectional,
+            from,
+            OUT,
+            PortId(0),
+            to,
+            IN,
+            PortId(0),
+        )
+        .unwrap();
+        id
+    }
+
+    fn entries(log: &Log, name: &str) -> Vec<String> {
+        log.borrow()
+            .iter()
+            .filter(|e| e.starts_with(&format!("{} ", name)))
+            .map(|e| e[name.len() + 1..].to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn links_drop_messages_while_down_and_notify_both_ends() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let a = probe(
+            &mut r,
+            &mut env,
+            &log,
+            "A",
+            vec![10, 20, 30, 40],
+            Box::new(send),
+        );
+        let b = probe(&mut r, &mut env, &log, "B", vec![], Box::new(send));
+        let conn = link(&mut r, &mut env, a, b);
+        r.set_scenario(parse_scenario("15 link-down A:out:0\n35 link-up A:out:0").unwrap());
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        assert_eq!(
+            entries(&log, "B"),
+            vec![
+                "init 0",
+                "msg 11",
+                "link down 15",
+                "link up 35",
+                "msg 41",
+                "finalize"
+            ]
+        );
+        assert!(entries(&log, "A").contains(&"link down 15".to_owned()));
+        assert!(entries(&log, "A").contains(&"link up 35".to_owned()));
+        assert_eq!(r.connections.dropped_link_down[&conn], 2);
+    }
+
+    #[test]
+    fn modules_take_their_own_links_down() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let down: OnTick =
+            Box::new(
+                |id, ctx| match ctx.set_link_state(id, OUT, PortId(0), false, true) {
+                    Ok(()) => "down".to_owned(),
+                    Err(e) => e.to_string(),
+                },
+            );
+        let a = probe(&mut r, &mut env, &log, "A", vec![10], down);
+        let b = probe(&mut r, &mut env, &log, "B", vec![], Box::new(send));
+        let conn = link(&mut r, &mut env, a, b);
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        assert_eq!(
+            entries(&log, "A"),
+            vec!["init 0", "tick 10 down", "link down 10", "finalize"]
+        );
+        assert_eq!(
+            entries(&log, "B"),
+            vec!["init 0", "link down 10", "finalize"]
+        );
+        assert!(!r.connections.is_link_up(conn));
+    }
+
+    #[test]
+    fn unconnected_ports_are_an_error_not_a_panic() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let unconnected: OnTick = Box::new(|id, ctx| {
+            let down = ctx.set_link_state(id, OUT, PortId(0), false, true);
+            let gone = ctx.disconnect(id, IN, PortId(0));
+            format!("{} {}", down.is_err(), gone.is_err())
+        });
+        probe(&mut r, &mut env, &log, "A", vec![10], unconnected);
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        assert_eq!(
+            entries(&log, "A"),
+            vec!["init 0", "tick 10 true true", "finalize"]
+        );
+        assert!(r
+            .set_link_state(&mut env.id_reg, ConnectionId(999), false, true)
+            .is_err());
+        assert!(r.connections.disconnect(ConnectionId(999)).is_err());
+    }
+
+    #[test]
+    fn deleted_modules_can_not_be_connected_or_deleted_again() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let b = probe(&mut r, &mut env, &log, "B", vec![], Box::new(send));
+        let delete_and_connect: OnTick = Box::new(move |id, ctx| {
+            ctx.delete_module(b);
+            ctx.delete_module(b);
+            let conn = simple_connection::new_simple_connection(ctx.mctx.id_reg, 1, 0, 0);
+            ctx.connect_modules(
+                Box::new(conn),
+                mesh::ConnectionKind::Onedirectional,
+                id,
+                OUT,
+                PortId(0),
+                b,
+                IN,
+                PortId(0),
+            );
+            "deleted".to_owned()
+        });
+        let a = probe(
+            &mut r,
+            &mut env,
+            &log,
+            "A",
+            vec![10, 20],
+            delete_and_connect,
+        );
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        //the second delete and the connect fail, the simulation goes on without them
+        assert_eq!(entries(&log, "B"), vec!["init 0", "finalize"]);
+        assert_eq!(
+            entries(&log, "A"),
+            vec!["init 0", "tick 10 deleted", "tick 20 deleted", "finalize"]
+        );
+        assert!(r.connections.gates.is_empty());
+        assert!(r.modules.modules.contains_key(&a));
+        assert!(r.delete_module(b, &mut env.id_reg).is_err());
+    }
+
+    #[test]
+    fn timers_of_deleted_modules_do_not_fire() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let b = probe(&mut r, &mut env, &log, "B", vec![20, 30], Box::new(send));
+        let delete: OnTick = Box::new(move |_, ctx| {
+            ctx.delete_module(b);
+            //scheduled for a module that is gone when the event is due
+            ctx.timer_queue.push(TimerEvent {
+                time: 40,
+                mod_id: b,
+                event: Box::new(ev::new_ev(ctx.mctx.id_reg, Tick {})),
+            });
+            "deleted".to_owned()
+        });
+        probe(&mut r, &mut env, &log, "A", vec![10], delete);
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        assert_eq!(entries(&log, "B"), vec!["init 0", "finalize"]);
+        assert_eq!(
+            entries(&log, "A"),
+            vec!["init 0", "tick 10 deleted", "finalize"]
+        );
+    }
+
+    //results of modules below others are reported under their path in the module forest, like
+    //scenarios refer to them
+    #[test]
+    fn results_are_named_by_their_path() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let top = probe(&mut r, &mut env, &log, "Top", vec![], Box::new(send));
+        let mid = probe(&mut r, &mut env, &log, "Mid", vec![], Box::new(send));
+        let low = probe(&mut r, &mut env, &log, "Low", vec![], Box::new(send));
+        r.module_forest = vec![Tree::Node(
+            ("Top".to_owned(), top),
+            vec![Tree::Node(
+                ("Mid".to_owned(), mid),
+                vec![Tree::Leaf(("Low".to_owned(), low))],
+            )],
+        )];
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        let names: Vec<&str> = r.results().iter().map(|(m, _, _)| m.as_ref()).collect();
+        assert_eq!(names, vec!["Top.Mid.Low", "Top.Mid", "Top"]);
+    }
+
+    #[test]
+    fn deleted_modules_report_results_by_their_path() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let top = probe(&mut r, &mut env, &log, "Top", vec![], Box::new(send));
+        let low = probe(&mut r, &mut env, &log, "Low", vec![], Box::new(send));
+        r.module_forest = vec![Tree::Node(
+            ("Top".to_owned(), top),
+            vec![Tree::Leaf(("Low".to_owned(), low))],
+        )];
+        let delete: OnTick = Box::new(move |_, ctx| {
+            ctx.delete_module(low);
+            String::new()
+        });
+        probe(&mut r, &mut env, &log, "A", vec![10], delete);
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        let names: Vec<&str> = r.results().iter().map(|(m, _, _)| m.as_ref()).collect();
+        assert_eq!(names, vec!["Top.Low", "Top", "A"]);
+    }
+
+    #[test]
+    fn scenario_actions_are_applied_at_their_time() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let a = probe(
+            &mut r,
+            &mut env,
+            &log,
+            "A",
+            vec![10, 20, 40],
+            Box::new(send),
+        );
+        let b = probe(&mut r, &mut env, &log, "B", vec![], Box::new(send));
+        let conn = link(&mut r, &mut env, a, b);
+        r.set_scenario(
+            parse_scenario(
+                "15 link-down A:out:0\n\
+                 25 create SinkModule B name=Extra throughput_bin=1us\n\
+                 30 inject B:in:0 hello\n\
+                 35 delete B.Extra\n",
+            )
+            .unwrap(),
+        );
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        assert_eq!(
+            entries(&log, "B"),
+            vec!["init 0", "msg 11", "link down 15", "msg 30", "finalize"]
+        );
+        assert_eq!(r.connections.dropped_link_down[&conn], 2);
+        //the created sink was finalized when it was deleted, below the module it was created in
+        assert!(r
+            .results()
+            .iter()
+            .any(|(m, f, v)| m == "B.Extra" && f == "sunk_msgs" && v == "0"));
+        assert!(r
+            .find_module(&ModuleRef::Path("B.Extra".to_owned()))
+            .is_none());
+    }
+
+    #[test]
+    fn failing_scenario_actions_stop_the_run() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        probe(
+            &mut r,
+            &mut env,
+            &log,
+            "A",
+            vec![10, 30],
+            Box::new(|_, _| String::new()),
+        );
+        r.set_scenario(parse_scenario("# header\n20 delete Nobody").unwrap());
+
+        let e = r.run(&mut env.id_reg, 100).unwrap_err().to_string();
+
+        assert!(e.starts_with("Scenario line 2:"), "{}", e);
+        assert_eq!(entries(&log, "A"), vec!["init 0", "tick 10 ", "finalize"]);
+    }
+
+    #[test]
+    fn every_stage_is_finished_before_the_next_one() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        let nothing = || -> OnTick { Box::new(|_, _| String::new()) };
+        staged_probe(&mut r, &mut env, &log, "A", 1, vec![], nothing());
+        staged_probe(&mut r, &mut env, &log, "B", 3, vec![], nothing());
+        staged_probe(&mut r, &mut env, &log, "C", 2, vec![], nothing());
+        staged_probe(&mut r, &mut env, &log, "D", 0, vec![], nothing());
+
+        r.init_modules(&mut env.id_reg);
+
+        assert_eq!(
+            *log.borrow(),
+            vec!["A init 0", "B init 0", "C init 0", "B init 1", "C init 1", "B init 2"]
+        );
+    }
+
+    #[test]
+    fn added_modules_run_through_their_stages_once() {
+        let mut env = new_test_env();
+        let (mut r, log) = setup(&mut env);
+        staged_probe(&mut r, &mut env, &log, "B", 2, vec![], Box::new(send));
+        let type_id = env
+            .id_reg
+            .lookup_module_id(Probe::TYPE_STR.to_owned())
+            .unwrap();
+        let new_log = log.clone();
+        let create: OnTick = Box::new(move |_, ctx| {
+            let p = Probe {
+                id: ctx.mctx.id_reg.new_module_id(),
+                type_id: type_id,
+                name: "N".to_owned(),
+                log: new_log.clone(),
+                stages: 3,
+                ticks: vec![],
+                on_tick: Box::new(send),
+                ticked: 0,
+            };
+            ctx.commands.push_back(Command::AddModule {
+                module: Box::new(p),
+                parent: None,
+            });
+            "created".to_owned()
+        });
+        staged_probe(&mut r, &mut env, &log, "A", 1, vec![10], create);
+
+        r.run(&mut env.id_reg, 100).unwrap();
+
+        assert_eq!(
+            log.borrow()[..7].to_vec(),
+            vec![
+                "B init 0",
+                "A init 0",
+                "B init 1",
+                "A tick 10 created",
+                "N init 0",
+                "N init 1",
+                "N init 2"
+            ]
+        );
+        assert_eq!(
+            log.borrow().iter().filter(|e| e.contains("init")).count(),
+            6
+        );
+    }
+}
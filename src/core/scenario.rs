@@ -0,0 +1,377 @@
+use crate::core::clock;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+
+//A script of actions that get applied at given simulation times. One action per line:
+//
+//# time   action      arguments
+//10ms     set-param   Router.Rate rate 500
+//1s       link-down   Router.Queue:out:0
+//2s       link-up     Router.Queue:out:0
+//3s       create      SinkModule Router name=Sink2
+//4s       delete      Router.Sink2
+//5s       inject      Src:in:0 hello there
+//
+//Modules are referenced by their path in the module forest ("Parent.Child") or by their id ("#12").
+//If siblings share a name the path refers to the first of them.
+//Ports are referenced as module:gate:port with the gate name the module declares. Created modules
+//are added below the given parent ("-" for the top of the forest), the remaining key=value pairs
+//are handed to the generator of the module type. Injected messages are TextMsgs delivered to the
+//given port as if they arrived there.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModuleRef {
+    Path(String),
+    Id(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortRef {
+    pub module: ModuleRef,
+    pub gate: String,
+    pub port: u64,
+}
+
+impl std::fmt::Display for ModuleRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ModuleRef::Path(path) => write!(f, "{}", path),
+            ModuleRef::Id(id) => write!(f, "#{}", id),
+        }
+    }
+}
+
+impl std::fmt::Display for PortRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.module, self.gate, self.port)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    SetParameter {
+        module: ModuleRef,
+        key: String,
+        value: String,
+    },
+    SetLinkState {
+        port: PortRef,
+        up: bool,
+    },
+    CreateModule {
+        type_str: String,
+        parent: Option<ModuleRef>,
+        parameters: HashMap<String, String>,
+    },
+    DeleteModule {
+        module: ModuleRef,
+    },
+    Inject {
+        port: PortRef,
+        text: String,
+    },
+}
+
+pub struct ScenarioEvent {
+    pub time: u64,
+    pub line: usize,
+    pub action: Action,
+}
+
+pub struct Scenario {
+    //sorted by time, actions at the same time stay in script order
+    pub events: VecDeque<ScenarioEvent>,
+}
+
+impl Scenario {
+    pub fn next_time(&self) -> Option<u64> {
+        self.events.front().map(|ev| ev.time)
+    }
+
+    //removes and returns all actions that are due at time now
+    pub fn take_due(&mut self, now: u64) -> Vec<ScenarioEvent> {
+        let mut due = Vec::new();
+        while let Some(ev) = self.events.front() {
+            if ev.time > now {
+                break;
+            }
+            due.push(self.events.pop_front().unwrap());
+        }
+        due
+    }
+}
+
+pub fn load_scenario(path: &str) -> Result<Scenario, Box<dyn std::error::Error>> {
+    let mut text = String::new();
+    std::fs::File::open(path)?.read_to_string(&mut text)?;
+    parse_scenario(&text)
+}
+
+pub fn parse_scenario(text: &str) -> Result<Scenario, Box<dyn std::error::Error>> {
+    let mut events = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() || words[0].starts_with('#') {
+            continue;
+        }
+
+        match parse_line(&words) {
+            Ok((time, action)) => events.push(ScenarioEvent {
+                time: time,
+                line: idx + 1,
+                action: action,
+            }),
+            Err(e) => return Err(format!("Scenario line {}: {}", idx + 1, e).into()),
+        }
+    }
+
+    //stable, so actions at the same time keep their order
+    events.sort_by_key(|ev| ev.time);
+    Ok(Scenario {
+        events: events.into_iter().collect(),
+    })
+}
+
+fn parse_line(words: &[&str]) -> Result<(u64, Action), Box<dyn std::error::Error>> {
+    if words.len() < 2 {
+        return Err("expected: time action arguments".into());
+    }
+    let time = clock::parse_time(words[0])?;
+    let args = &words[2..];
+
+    let action = match words[1] {
+        "set-param" => {
+            if args.len() < 3 {
+                return Err("expected: set-param module key value".into());
+            }
+            Action::SetParameter {
+                module: parse_module_ref(args[0])?,
+                key: args[1].to_owned(),
+                value: args[2..].join(" "),
+            }
+        }
+        "link-down" | "link-up" => {
+            if args.len() != 1 {
+                return Err(format!("expected: {} module:gate:port", words[1]).into());
+            }
+            Action::SetLinkState {
+                port: parse_port_ref(args[0])?,
+                up: words[1] == "link-up",
+            }
+        }
+        "create" => {
+            if args.len() < 2 {
+                return Err("expected: create type parent [key=value...]".into());
+            }
+            let parent = match args[1] {
+                "-" => None,
+                p => Some(parse_module_ref(p)?),
+            };
+            let mut parameters = HashMap::new();
+            for kv in &args[2..] {
+                match kv.find('=') {
+                    Some(pos) => {
+                        parameters.insert(kv[..pos].to_owned(), kv[pos + 1..].to_owned());
+                    }
+                    None => return Err(format!("expected key=value, got \"{}\"", kv).into()),
+                }
+            }
+            Action::CreateModule {
+                type_str: args[0].to_owned(),
+                parent: parent,
+                parameters: parameters,
+            }
+        }
+        "delete" => {
+            if args.len() != 1 {
+                return Err("expected: delete module".into());
+            }
+            Action::DeleteModule {
+                module: parse_module_ref(args[0])?,
+            }
+        }
+        "inject" => {
+            if args.is_empty() {
+                return Err("expected: inject module:gate:port [text]".into());
+            }
+            Action::Inject {
+                port: parse_port_ref(args[0])?,
+                text: args[1..].join(" "),
+            }
+        }
+        other => return Err(format!("unknown action \"{}\"", other).into()),
+    };
+
+    Ok((time, action))
+}
+
+fn parse_module_ref(s: &str) -> Result<ModuleRef, Box<dyn std::error::Error>> {
+    if s.starts_with('#') {
+        match s[1..].parse() {
+            Ok(id) => Ok(ModuleRef::Id(id)),
+            Err(_) => Err(format!("invalid module id \"{}\"", s).into()),
+        }
+    } else if s.is_empty() {
+        Err("empty module reference".into())
+    } else {
+        Ok(ModuleRef::Path(s.to_owned()))
+    }
+}
+
+fn parse_port_ref(s: &str) -> Result<PortRef, Box<dyn std::error::Error>> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() != 3 {
+        return Err(format!("expected module:gate:port, got \"{}\"", s).into());
+    }
+    let port = match parts[2].parse() {
+        Ok(p) => p,
+        Err(_) => return Err(format!("invalid port index \"{}\"", parts[2]).into()),
+    };
+
+    Ok(PortRef {
+        module: parse_module_ref(parts[0])?,
+        gate: parts[1].to_owned(),
+        port: port,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Action {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        parse_line(&words).unwrap().1
+    }
+
+    fn port(module: ModuleRef, gate: &str, port: u64) -> PortRef {
+        PortRef {
+            module: module,
+            gate: gate.to_owned(),
+            port: port,
+        }
+    }
+
+    #[test]
+    fn every_action_is_parsed() {
+        let words: Vec<&str> = "10ms set-param Router.Rate rate 500 kbit"
+            .split_whitespace()
+            .collect();
+        assert_eq!(
+            parse_line(&words).unwrap(),
+            (
+                10 * clock::MILLI_SECONDS,
+                Action::SetParameter {
+                    module: ModuleRef::Path("Router.Rate".to_owned()),
+                    key: "rate".to_owned(),
+                    value: "500 kbit".to_owned(),
+                }
+            )
+        );
+        assert_eq!(
+            parse("1s link-down Router.Queue:out:2"),
+            Action::SetLinkState {
+                port: port(ModuleRef::Path("Router.Queue".to_owned()), "out", 2),
+                up: false,
+            }
+        );
+        assert_eq!(
+            parse("2s link-up #12:out:0"),
+            Action::SetLinkState {
+                port: port(ModuleRef::Id(12), "out", 0),
+                up: true,
+            }
+        );
+
+        let mut parameters = HashMap::new();
+        parameters.insert("name".to_owned(), "Sink2".to_owned());
+        parameters.insert("rate".to_owned(), "a=b".to_owned());
+        assert_eq!(
+            parse("3s create SinkModule Router name=Sink2 rate=a=b"),
+            Action::CreateModule {
+                type_str: "SinkModule".to_owned(),
+                parent: Some(ModuleRef::Path("Router".to_owned())),
+                parameters: parameters,
+            }
+        );
+        assert_eq!(
+            parse("3s create SinkModule -"),
+            Action::CreateModule {
+                type_str: "SinkModule".to_owned(),
+                parent: None,
+                parameters: HashMap::new(),
+            }
+        );
+        assert_eq!(
+            parse("4s delete #3"),
+            Action::DeleteModule {
+                module: ModuleRef::Id(3),
+            }
+        );
+        assert_eq!(
+            parse("5s inject Src:in:0 hello there"),
+            Action::Inject {
+                port: port(ModuleRef::Path("Src".to_owned()), "in", 0),
+                text: "hello there".to_owned(),
+            }
+        );
+        assert_eq!(
+            parse("5s inject Src:in:0"),
+            Action::Inject {
+                port: port(ModuleRef::Path("Src".to_owned()), "in", 0),
+                text: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        for (text, line) in &[
+            ("1s delete A\n\n# comment\n2s explode A", 4),
+            ("1s", 1),
+            ("1s delete A\nsoon delete A", 2),
+            ("infs delete A", 1),
+            ("1s set-param A key", 1),
+            ("1s link-down A:out", 1),
+            ("1s link-down A:out:x", 1),
+            ("1s link-up A:out:0 B:in:0", 1),
+            ("1s create SinkModule - name", 1),
+            ("1s create SinkModule", 1),
+            ("1s delete #x", 1),
+            ("1s delete", 1),
+            ("1s inject", 1),
+        ] {
+            let e = match parse_scenario(text) {
+                Ok(_) => panic!("{:?} was accepted", text),
+                Err(e) => e.to_string(),
+            };
+            assert!(
+                e.starts_with(&format!("Scenario line {}:", line)),
+                "{:?}: {}",
+                text,
+                e
+            );
+        }
+    }
+
+    #[test]
+    fn actions_are_sorted_by_time_and_keep_their_order() {
+        let mut scenario = parse_scenario(
+            "# time action\n\
+             2s delete C\n\
+             1s delete A\n\
+             \n\
+             2s delete D\n\
+             1s delete B\n",
+        )
+        .unwrap();
+        assert_eq!(scenario.next_time(), Some(clock::SECONDS));
+
+        let lines = |due: Vec<ScenarioEvent>| due.iter().map(|ev| ev.line).collect::<Vec<_>>();
+        assert!(scenario.take_due(clock::SECONDS - 1).is_empty());
+        assert_eq!(lines(scenario.take_due(clock::SECONDS)), vec![3, 6]);
+        assert_eq!(lines(scenario.take_due(3 * clock::SECONDS)), vec![2, 5]);
+        assert_eq!(scenario.next_time(), None);
+    }
+}
@@ -0,0 +1,384 @@
+//Helpers for collecting statistics in modules. Results are reported from Module::finalize.
+
+//count, mean, variance (Welford), min and max of a series of values
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub count: u64,
+    pub min: f64,
+    pub max: f64,
+    mean: f64,
+    m2: f64,
+}
+
+pub fn new_summary() -> Summary {
+    Summary {
+        count: 0,
+        min: std::f64::INFINITY,
+        max: std::f64::NEG_INFINITY,
+        mean: 0.0,
+        m2: 0.0,
+    }
+}
+
+impl Summary {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+//Histogram over non-negative integers with buckets that grow exponentially: every power of two is
+//split into SUB_BUCKETS linear buckets, so the relative error of a bucket is below 1/SUB_BUCKETS
+//regardless of the magnitude. Needs constant memory, so it can take every delay of a long run.
+#[derive(Clone, Debug)]
+pub struct LogHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+}
+
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+pub fn new_log_histogram() -> LogHistogram {
+    LogHistogram {
+        buckets: Vec::new(),
+        count: 0,
+    }
+}
+
+fn bucket_of(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let magnitude = 63 - value.leading_zeros() - SUB_BUCKET_BITS;
+    let sub = (value >> magnitude) - SUB_BUCKETS;
+    ((magnitude as u64 + 1) * SUB_BUCKETS + sub) as usize
+}
+
+//smallest value that falls into the bucket
+fn bucket_lower(bucket: usize) -> u64 {
+    let bucket = bucket as u64;
+    if bucket < SUB_BUCKETS {
+        return bucket;
+    }
+    let magnitude = bucket / SUB_BUCKETS - 1;
+    let sub = bucket % SUB_BUCKETS;
+    (SUB_BUCKETS + sub) << magnitude
+}
+
+impl LogHistogram {
+    pub fn add(&mut self, value: u64) {
+        let bucket = bucket_of(value);
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    //lower bound of the bucket the p-quantile (0..=1) falls into
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((p * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return bucket_lower(bucket);
+            }
+        }
+        bucket_lower(self.buckets.len() - 1)
+    }
+
+    //(lower bound, count) of every non-empty bucket
+    pub fn bins(&self) -> Vec<(u64, u64)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| **n > 0)
+            .map(|(bucket, n)| (bucket_lower(bucket), *n))
+            .collect()
+    }
+}
+
+//Sums up values in consecutive bins of simulation time, eg bits received per millisecond
+#[derive(Clone, Debug)]
+pub struct TimeBins {
+    pub width: u64,
+    //bin index -> (number of values, sum of values)
+    bins: std::collections::BTreeMap<u64, (u64, u64)>,
+}
+
+pub fn new_time_bins(width: u64) -> TimeBins {
+    if width == 0 {
+        panic!("Time bins need a width > 0");
+    }
+    TimeBins {
+        width: width,
+        bins: std::collections::BTreeMap::new(),
+    }
+}
+
+impl TimeBins {
+    pub fn add(&mut self, time: u64, value: u64) {
+        let bin = self.bins.entry(time / self.width).or_insert((0, 0));
+        bin.0 += 1;
+        bin.1 += value;
+    }
+
+    //(start time, number of values, sum of values) of every non-empty bin
+    pub fn bins(&self) -> Vec<(u64, u64, u64)> {
+        self.bins
+            .iter()
+            .map(|(idx, (n, sum))| (idx * self.width, *n, *sum))
+            .collect()
+    }
+
+    //like bins, but with the empty bins between the first and the last non-empty one as well
+    pub fn contiguous_bins(&self) -> Vec<(u64, u64, u64)> {
+        let (first, last) = match (self.bins.keys().next(), self.bins.keys().next_back()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Vec::new(),
+        };
+        (first..=last)
+            .map(|idx| {
+                let (n, sum) = self.bins.get(&idx).cloned().unwrap_or((0, 0));
+                (idx * self.width, n, sum)
+            })
+            .collect()
+    }
+}
+
+//Average of a value over simulation time, eg the length of a queue. Every value counts for as
+//long as it was set
+#[derive(Clone, Debug)]
+pub struct TimeWeighted {
+    pub max: f64,
+    start: u64,
+    last_time: u64,
+    last_value: f64,
+    area: f64,
+}
+
+pub fn new_time_weighted(start: u64, value: f64) -> TimeWeighted {
+    TimeWeighted {
+        max: value,
+        start: start,
+        last_time: start,
+        last_value: value,
+        area: 0.0,
+    }
+}
+
+impl TimeWeighted {
+    pub fn set(&mut self, now: u64, value: f64) {
+        self.area += self.last_value * (now - self.last_time) as f64;
+        self.last_time = now;
+        self.last_value = value;
+        self.max = self.max.max(value);
+    }
+
+    pub fn mean(&self, now: u64) -> f64 {
+        let area = self.area + self.last_value * (now - self.last_time) as f64;
+        if now > self.start {
+            area / (now - self.start) as f64
+        } else {
+            self.last_value
+        }
+    }
+}
+
+//Time weighted average of a value per bin of simulation time, eg the length of a queue per
+//millisecond. Like TimeWeighted, but the average is kept for every bin on its own
+#[derive(Clone, Debug)]
+pub struct TimeWeightedBins {
+    pub width: u64,
+    start: u64,
+    last_time: u64,
+    last_value: f64,
+    //bin index -> integral of the value over the part of the bin up to last_time
+    areas: std::collections::BTreeMap<u64, f64>,
+}
+
+pub fn new_time_weighted_bins(width: u64, start: u64, value: f64) -> TimeWeightedBins {
+    if width == 0 {
+        panic!("Time bins need a width > 0");
+    }
+    TimeWeightedBins {
+        width: width,
+        start: start,
+        last_time: start,
+        last_value: value,
+        areas: std::collections::BTreeMap::new(),
+    }
+}
+
+impl TimeWeightedBins {
+    pub fn set(&mut self, now: u64, value: f64) {
+        if self.last_value != 0.0 {
+            let mut t = self.last_time;
+            while t < now {
+                let bin = t / self.width;
+                let end = std::cmp::min((bin + 1) * self.width, now);
+                *self.areas.entry(bin).or_insert(0.0) += self.last_value * (end - t) as f64;
+                t = end;
+            }
+        }
+        self.last_time = now;
+        self.last_value = value;
+    }
+
+    //time of the last change of the value
+    pub fn last_time(&self) -> u64 {
+        self.last_time
+    }
+
+    //(start time, average) of every bin up to now. The first and the last bin are averaged over
+    //the part of them that was observed
+    pub fn bins(&self, now: u64) -> Vec<(u64, f64)> {
+        if now <= self.start {
+            return Vec::new();
+        }
+        (self.start / self.width..=(now - 1) / self.width)
+            .map(|bin| {
+                let from = std::cmp::max(bin * self.width, self.start);
+                let to = std::cmp::min((bin + 1) * self.width, now);
+                let mut area = self.areas.get(&bin).cloned().unwrap_or(0.0);
+                //the current value has not been added to the areas yet
+                let pending_from = std::cmp::max(from, self.last_time);
+                if pending_from < to {
+                    area += self.last_value * (to - pending_from) as f64;
+                }
+                (bin * self.width, area / (to - from) as f64)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_mean_and_variance() {
+        let mut s = new_summary();
+        assert_eq!(s.variance(), 0.0);
+        for v in &[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            s.add(*v);
+        }
+        assert_eq!(s.count, 8);
+        assert_eq!((s.min, s.max), (2.0, 9.0));
+        assert!((s.mean() - 5.0).abs() < 1e-12);
+        assert!((s.variance() - 32.0 / 7.0).abs() < 1e-12);
+
+        let mut single = new_summary();
+        single.add(3.0);
+        assert_eq!((single.mean(), single.stddev()), (3.0, 0.0));
+    }
+
+    #[test]
+    fn buckets_cover_every_value() {
+        let mut values: Vec<u64> = (0..5000).collect();
+        values.extend((5..62).map(|shift| (1u64 << shift) + 12345));
+        let mut last_bucket = 0;
+        for v in values {
+            let bucket = bucket_of(v);
+            assert!(bucket >= last_bucket);
+            last_bucket = bucket;
+
+            let lower = bucket_lower(bucket);
+            assert!(lower <= v && v < bucket_lower(bucket + 1), "{}", v);
+            assert!((v - lower) as f64 <= v as f64 / SUB_BUCKETS as f64);
+        }
+        assert_eq!(bucket_of(SUB_BUCKETS - 1), SUB_BUCKETS as usize - 1);
+        assert_eq!(bucket_lower(bucket_of(SUB_BUCKETS)), SUB_BUCKETS);
+    }
+
+    #[test]
+    fn histogram_percentiles() {
+        let mut h = new_log_histogram();
+        assert_eq!(h.percentile(0.5), 0);
+        for v in 1..=100 {
+            h.add(v);
+        }
+        assert_eq!(h.count(), 100);
+        assert_eq!(h.percentile(0.0), 1);
+        assert_eq!(h.percentile(0.5), 50);
+        assert_eq!(h.percentile(0.99), 96);
+        assert_eq!(h.percentile(1.0), 100);
+        assert_eq!(h.bins().iter().map(|(_, n)| n).sum::<u64>(), 100);
+    }
+
+    #[test]
+    fn time_bins_fill_empty_bins() {
+        let mut bins = new_time_bins(10);
+        assert!(bins.contiguous_bins().is_empty());
+        bins.add(5, 1);
+        bins.add(7, 3);
+        bins.add(35, 2);
+        assert_eq!(bins.bins(), vec![(0, 2, 4), (30, 1, 2)]);
+        assert_eq!(
+            bins.contiguous_bins(),
+            vec![(0, 2, 4), (10, 0, 0), (20, 0, 0), (30, 1, 2)]
+        );
+    }
+
+    #[test]
+    fn time_weighted_mean() {
+        let mut tw = new_time_weighted(10, 2.0);
+        assert_eq!(tw.mean(10), 2.0);
+        tw.set(20, 4.0);
+        tw.set(30, 0.0);
+        //2 for 10ns, 4 for 10ns and 0 for 20ns
+        assert!((tw.mean(50) - 60.0 / 40.0).abs() < 1e-12);
+        assert_eq!(tw.max, 4.0);
+    }
+
+    #[test]
+    fn time_weighted_bins_average_per_bin() {
+        let mut tw = new_time_weighted_bins(10, 5, 0.0);
+        tw.set(8, 2.0);
+        tw.set(25, 1.0);
+        tw.set(42, 0.0);
+        assert_eq!(
+            tw.bins(45),
+            vec![
+                //0 from 5 to 8 and 2 from 8 to 10
+                (0, 4.0 / 5.0),
+                (10, 2.0),
+                //2 from 20 to 25 and 1 from 25 to 30
+                (20, 1.5),
+                (30, 1.0),
+                //only observed up to 45
+                (40, 2.0 / 5.0),
+            ]
+        );
+        assert!(new_time_weighted_bins(10, 5, 1.0).bins(5).is_empty());
+    }
+}
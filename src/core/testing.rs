@@ -0,0 +1,87 @@
+use crate::core::clock;
+use crate::core::clock::Clock;
+use crate::core::commands::Command;
+use crate::core::contexts::{EventHandleContext, SimulationContext};
+use crate::core::events::event::TimerEvent;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::messages::msg;
+use crate::core::random::streams::{new_rng_streams, RngStreams};
+use crate::core::runner::{new_runner, Runner};
+
+use std::collections::{BinaryHeap, VecDeque};
+
+//Owns everything a SimulationContext or EventHandleContext borrows, so unit tests can drive
+//connections and models and call the handlers of modules directly
+pub struct TestEnv {
+    pub clock: Clock,
+    pub id_reg: IdRegistrar,
+    pub rngs: RngStreams,
+
+    //what modules handed to the EventHandleContext of with_ctx
+    pub sent: VecDeque<(Box<dyn Message>, GateId, PortId)>,
+    pub timers: BinaryHeap<TimerEvent>,
+    pub commands: VecDeque<Command>,
+    //for the connection mesh and the module factory only
+    runner: Runner,
+}
+
+pub fn new_test_env() -> TestEnv {
+    TestEnv {
+        clock: clock::new(),
+        id_reg: IdRegistrar {
+            last_id: 0,
+            last_type_id: 0,
+            type_ids: std::collections::HashMap::new(),
+            type_ids_reverse: std::collections::HashMap::new(),
+            rust_type_ids: std::collections::HashMap::new(),
+        },
+        rngs: new_rng_streams([7; 16]),
+
+        sent: VecDeque::new(),
+        timers: BinaryHeap::new(),
+        commands: VecDeque::new(),
+        runner: new_runner([7; 16]),
+    }
+}
+
+impl TestEnv {
+    pub fn ctx(&mut self) -> SimulationContext<'_> {
+        SimulationContext {
+            time: &self.clock,
+            id_reg: &mut self.id_reg,
+            rngs: &mut self.rngs,
+            stream: 0,
+        }
+    }
+
+    //eg: env.with_ctx(|ctx| queue.handle_message(msg, IN_GATE, PortId(0), ctx))
+    pub fn with_ctx<R>(&mut self, f: impl FnOnce(&mut EventHandleContext) -> R) -> R {
+        let mut ctx = EventHandleContext {
+            mctx: SimulationContext {
+                time: &self.clock,
+                id_reg: &mut self.id_reg,
+                rngs: &mut self.rngs,
+                stream: 0,
+            },
+            timer_queue: &mut self.timers,
+            msgs_to_send: &mut self.sent,
+            commands: &mut self.commands,
+            mesh: &self.runner.connections,
+            factory: &self.runner.factory,
+        };
+        f(&mut ctx)
+    }
+
+    pub fn set_time(&mut self, time: u64) {
+        self.clock.set(time).unwrap();
+    }
+
+    //a message without payload of the given size
+    pub fn msg(&mut self, bit_length: u64) -> Box<dyn Message> {
+        let mut m = msg::new_msg(&mut self.id_reg, ());
+        m.meta.bit_length = bit_length;
+        Box::new(m)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::core::events::event::TimerEvent;
+use crate::core::messages::message::TimedMessage;
+
+//a record of every message and event the runner hands to a module, in the order it does so.
+//Two runs with the same seed and topology must produce the same trace
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEntry {
+    Message {
+        time: u64,
+        module: u64,
+        gate: u64,
+        port: u64,
+        msg_type: u64,
+        msg_id: u64,
+    },
+    Event {
+        time: u64,
+        module: u64,
+        event_type: u64,
+        event_id: u64,
+    },
+}
+
+pub fn message_entry(tmsg: &TimedMessage, now: u64) -> TraceEntry {
+    TraceEntry::Message {
+        time: now,
+        module: tmsg.recipient.raw(),
+        gate: tmsg.recp_gate.0,
+        port: tmsg.recp_port.0,
+        msg_type: tmsg.msg.msg_type_id().0,
+        msg_id: tmsg.msg.msg_id().0,
+    }
+}
+
+pub fn event_entry(ev: &TimerEvent) -> TraceEntry {
+    TraceEntry::Event {
+        time: ev.time,
+        module: ev.mod_id.raw(),
+        event_type: ev.event.event_type_id().0,
+        event_id: ev.event.event_id().raw(),
+    }
+}
+
+impl std::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TraceEntry::Message {
+                time,
+                module,
+                gate,
+                port,
+                msg_type,
+                msg_id,
+            } => write!(
+                f,
+                "{} msg M{} G{} P{} type {} id {}",
+                time, module, gate, port, msg_type, msg_id
+            ),
+            TraceEntry::Event {
+                time,
+                module,
+                event_type,
+                event_id,
+            } => write!(
+                f,
+                "{} ev M{} type {} id {}",
+                time, module, event_type, event_id
+            ),
+        }
+    }
+}
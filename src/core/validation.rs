@@ -0,0 +1,438 @@
+use crate::core::connection::connection::PortKind;
+use crate::core::connection::mesh::ConnectionMesh;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId};
+use crate::core::runner::Tree;
+
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Severity {
+    //the simulation can run but probably not as intended
+    Warning,
+    //the simulation would panic or lose messages
+    Error,
+}
+
+pub struct TopologyProblem {
+    pub severity: Severity,
+    pub description: String,
+}
+
+fn problem(severity: Severity, description: String) -> TopologyProblem {
+    TopologyProblem {
+        severity: severity,
+        description: description,
+    }
+}
+
+//collects "Parent.Child" style names for every module in the forest and reports modules
+//that appear more than once
+fn collect_forest(
+    prefix: &str,
+    tree: &Tree<(String, ModuleId)>,
+    names: &mut HashMap<ModuleId, String>,
+    problems: &mut Vec<TopologyProblem>,
+) {
+    let (name, id, children) = match tree {
+        Tree::Node((name, id), children) => (name, id, Some(children)),
+        Tree::Leaf((name, id)) => (name, id, None),
+    };
+
+    let path = if prefix.is_empty() {
+        name.clone()
+    } else {
+        format!("{}.{}", prefix, name)
+    };
+
+    if names.contains_key(id) {
+        problems.push(problem(
+            Severity::Error,
+            format!(
+                "Module {} ({}) appears more than once in the module forest",
+                path,
+                id.raw()
+            ),
+        ));
+    }
+    names.insert(*id, path.clone());
+
+    if let Some(children) = children {
+        for c in children {
+            collect_forest(&path, c, names, problems);
+        }
+    }
+}
+
+//Checks a topology before the simulation starts. Reports:
+// * modules that were added but are not part of the module forest (they would never be finalized)
+// * ids in the module forest that were never added as modules
+// * declared gates that have no connections (or sized gates with unconnected ports)
+// * connections on gates a module does not declare
+// * container gate mappings that lead to a port without a connection
+pub fn validate_topology(
+    mesh: &ConnectionMesh,
+    passthroughs: &BTreeMap<ModuleId, Vec<(GateId, GateId)>>,
+    forest: &[Tree<(String, ModuleId)>],
+) -> Vec<TopologyProblem> {
+    let mut problems = Vec::new();
+
+    let mut names = HashMap::new();
+    for tree in forest {
+        collect_forest("", tree, &mut names, &mut problems);
+    }
+    let name_of = |id: &ModuleId| match names.get(id) {
+        Some(name) => format!("{} ({})", name, id.raw()),
+        None => format!("({})", id.raw()),
+    };
+
+    let declared = &mesh.declared_gates;
+
+    for id in declared.keys() {
+        if !names.contains_key(id) {
+            problems.push(problem(
+                Severity::Error,
+                format!(
+                    "Module {} was added but is missing from the module forest",
+                    name_of(id)
+                ),
+            ));
+        }
+    }
+    let mut forest_ids: Vec<_> = names.keys().collect();
+    forest_ids.sort();
+    for id in forest_ids {
+        if !declared.contains_key(id) {
+            problems.push(problem(
+                Severity::Error,
+                format!(
+                    "Module {} is in the module forest but was never added",
+                    name_of(id)
+                ),
+            ));
+        }
+    }
+
+    //gate -> connected ports, per module
+    let mut connected: BTreeMap<ModuleId, BTreeMap<GateId, Vec<u64>>> = BTreeMap::new();
+    for ((m, g, p), port) in &mesh.gates {
+        connected
+            .entry(*m)
+            .or_insert_with(BTreeMap::new)
+            .entry(*g)
+            .or_insert_with(Vec::new)
+            .push(p.0);
+
+        let declares_gate = match mesh.declared_gates.get(m) {
+            Some(gates) => gates.iter().any(|d| d.id == *g),
+            None => false,
+        };
+        if !declares_gate {
+            problems.push(problem(
+                Severity::Error,
+                format!(
+                    "Connection on port {} of gate {} of module {} but the module does not declare that gate",
+                    p.0,
+                    g.0,
+                    name_of(m)
+                ),
+            ));
+        }
+        if !mesh.declared_gates.contains_key(&port.rcv_mod) {
+            problems.push(problem(
+                Severity::Error,
+                format!(
+                    "Connection from module {} leads to module {} which does not exist",
+                    name_of(m),
+                    port.rcv_mod.raw()
+                ),
+            ));
+        }
+    }
+
+    for (id, gates) in declared {
+        for gate in gates.iter() {
+            let ports = connected.get(id).and_then(|gs| gs.get(&gate.id));
+            match (ports, gate.size) {
+                (None, _) => problems.push(problem(
+                    Severity::Warning,
+                    format!(
+                        "Gate {} ({}) of module {} is not connected",
+                        gate.name,
+                        gate.id.0,
+                        name_of(id)
+                    ),
+                )),
+                (Some(ports), Some(size)) => {
+                    let missing: Vec<String> = (0..size)
+                        .filter(|idx| !ports.contains(idx))
+                        .map(|idx| idx.to_string())
+                        .collect();
+                    if !missing.is_empty() {
+                        problems.push(problem(
+                            Severity::Warning,
+                            format!(
+                                "Ports {} of gate {} ({}) of module {} are not connected",
+                                missing.join(","),
+                                gate.name,
+                                gate.id.0,
+                                name_of(id)
+                            ),
+                        ));
+                    }
+                }
+                (Some(_), None) => {}
+            }
+        }
+    }
+
+    for (id, pairs) in passthroughs {
+        for (outer, inner) in pairs {
+            for (from, to) in &[(*outer, *inner), (*inner, *outer)] {
+                let from_ports = mesh
+                    .gates
+                    .iter()
+                    .filter(|((m, g, _), _)| m == id && g == from);
+
+                for ((_, _, p), port) in from_ports {
+                    let receives = match port.kind {
+                        PortKind::In | PortKind::InOut => true,
+                        PortKind::Out => false,
+                    };
+                    if !receives {
+                        continue;
+                    }
+                    match mesh.gates.get(&(*id, *to, *p)) {
+                        Some(next) => match next.kind {
+                            PortKind::Out | PortKind::InOut => {}
+                            //only a problem if something is actually sent that way
+                            PortKind::In => problems.push(problem(
+                                Severity::Warning,
+                                format!(
+                                    "Messages arriving at gate {} port {} of container {} are mapped to gate {} port {} which only receives",
+                                    from.0,
+                                    p.0,
+                                    name_of(id),
+                                    to.0,
+                                    p.0
+                                ),
+                            )),
+                        },
+                        None => problems.push(problem(
+                            Severity::Error,
+                            format!(
+                                "Messages arriving at gate {} port {} of container {} are mapped to gate {} port {} which is not connected",
+                                from.0,
+                                p.0,
+                                name_of(id),
+                                to.0,
+                                p.0
+                            ),
+                        )),
+                    }
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::connection::mesh::ConnectionKind;
+    use crate::core::connection::simple_connection;
+    use crate::core::id_mngmnt::id_types::PortId;
+    use crate::core::modules::container::{self, ModuleContainer};
+    use crate::core::modules::simple_module::{self, SimpleModule};
+    use crate::core::modules::sink::{self, Sink};
+    use crate::core::runner::{self, Runner};
+    use crate::core::testing::{new_test_env, TestEnv};
+
+    fn setup(env: &mut TestEnv) -> Runner {
+        SimpleModule::register(&mut env.id_reg);
+        Sink::register(&mut env.id_reg);
+        ModuleContainer::register(&mut env.id_reg);
+        simple_connection::register(&mut env.id_reg);
+        runner::new_runner([7; 16])
+    }
+
+    fn add_simple(r: &mut Runner, env: &mut TestEnv, name: &str, in_forest: bool) -> ModuleId {
+        let m = simple_module::new_simple_module(&mut env.id_reg, name.to_owned());
+        let id = m.id;
+        r.add_module(Box::new(m)).unwrap();
+        if in_forest {
+            r.add_to_tree(Tree::Leaf((name.to_owned(), id)));
+        }
+        id
+    }
+
+    fn add_sink(r: &mut Runner, env: &mut TestEnv, name: &str) -> ModuleId {
+        let m = sink::new_sink(&mut env.id_reg, name.to_owned());
+        let id = m.id;
+        r.add_module(Box::new(m)).unwrap();
+        r.add_to_tree(Tree::Leaf((name.to_owned(), id)));
+        id
+    }
+
+    fn connect(
+        r: &mut Runner,
+        env: &mut TestEnv,
+        from: (ModuleId, GateId),
+        to: (ModuleId, GateId),
+    ) {
+        let conn = simple_connection::new_simple_connection(&mut env.id_reg, 1, 0, 0);
+        r.connect_modules(
+            Box::new(conn),
+            ConnectionKind::Onedirectional,
+            from.0,
+            from.1,
+            PortId(0),
+            to.0,
+            to.1,
+            PortId(0),
+        )
+        .unwrap();
+    }
+
+    fn has(problems: &[TopologyProblem], severity: Severity, text: &str) -> bool {
+        problems
+            .iter()
+            .any(|p| p.severity == severity && p.description.contains(text))
+    }
+
+    fn errors(problems: &[TopologyProblem]) -> usize {
+        problems
+            .iter()
+            .filter(|p| p.severity == Severity::Error)
+            .count()
+    }
+
+    //a simple module sending to a sink, with the in gate of the simple module left open
+    fn pair(env: &mut TestEnv) -> (Runner, ModuleId, ModuleId) {
+        let mut r = setup(env);
+        let a = add_simple(&mut r, env, "A", true);
+        let b = add_sink(&mut r, env, "B");
+        connect(
+            &mut r,
+            env,
+            (a, simple_module::OUT_GATE),
+            (b, sink::IN_GATE),
+        );
+        (r, a, b)
+    }
+
+    #[test]
+    fn the_forest_has_to_match_the_added_modules() {
+        let mut env = new_test_env();
+        let (mut r, a, _) = pair(&mut env);
+        assert_eq!(errors(&r.validate()), 0);
+
+        add_simple(&mut r, &mut env, "Lost", false);
+        r.add_to_tree(Tree::Leaf(("Ghost".to_owned(), ModuleId(999))));
+        r.add_to_tree(Tree::Leaf(("A".to_owned(), a)));
+        let problems = r.validate();
+        assert!(has(
+            &problems,
+            Severity::Error,
+            "missing from the module forest"
+        ));
+        assert!(has(
+            &problems,
+            Severity::Error,
+            "Ghost (999) is in the module forest but was never added"
+        ));
+        assert!(has(&problems, Severity::Error, "appears more than once"));
+        assert_eq!(errors(&problems), 3);
+    }
+
+    #[test]
+    fn unconnected_gates_and_ports_are_warnings() {
+        let mut env = new_test_env();
+        let (mut r, _, b) = pair(&mut env);
+        let problems = r.validate();
+        assert!(has(&problems, Severity::Warning, "Gate in (1) of module A"));
+        assert_eq!(problems.len(), 1);
+
+        r.connections.declared_gates.get_mut(&b).unwrap()[0].size = Some(3);
+        assert!(has(
+            &r.validate(),
+            Severity::Warning,
+            "Ports 1,2 of gate in (0) of module B"
+        ));
+    }
+
+    #[test]
+    fn connections_need_declared_gates_and_existing_modules() {
+        let mut env = new_test_env();
+        let (mut r, a, b) = pair(&mut env);
+        r.connections
+            .declared_gates
+            .get_mut(&a)
+            .unwrap()
+            .retain(|g| g.id != simple_module::OUT_GATE);
+        r.connections.declared_gates.remove(&b);
+        let problems = r.validate();
+        assert!(has(
+            &problems,
+            Severity::Error,
+            "does not declare that gate"
+        ));
+        assert!(has(&problems, Severity::Error, "which does not exist"));
+    }
+
+    #[test]
+    fn container_mappings_need_a_sending_port() {
+        let mut env = new_test_env();
+        let mut r = setup(&mut env);
+        let outer = GateId(0);
+        let inner = GateId(1);
+        let c =
+            container::new_module_container(&mut env.id_reg, "C".to_owned(), vec![(outer, inner)]);
+        let c_id = c.id;
+        r.add_module(Box::new(c)).unwrap();
+        r.add_to_tree(Tree::Leaf(("C".to_owned(), c_id)));
+        let a = add_simple(&mut r, &mut env, "A", true);
+        connect(
+            &mut r,
+            &mut env,
+            (a, simple_module::OUT_GATE),
+            (c_id, outer),
+        );
+        assert!(has(
+            &r.validate(),
+            Severity::Error,
+            "mapped to gate 1 port 0 which is not connected"
+        ));
+
+        //now the inner port exists but only receives as well
+        let b = add_simple(&mut r, &mut env, "B", true);
+        connect(
+            &mut r,
+            &mut env,
+            (b, simple_module::OUT_GATE),
+            (c_id, inner),
+        );
+        let problems = r.validate();
+        assert_eq!(errors(&problems), 0);
+        assert!(has(
+            &problems,
+            Severity::Warning,
+            "mapped to gate 1 port 0 which only receives"
+        ));
+        assert!(has(
+            &problems,
+            Severity::Warning,
+            "mapped to gate 0 port 0 which only receives"
+        ));
+    }
+
+    #[test]
+    fn run_refuses_a_topology_with_errors() {
+        let mut env = new_test_env();
+        let (mut r, _, _) = pair(&mut env);
+        add_simple(&mut r, &mut env, "Lost", false);
+        let err = r.run(&mut env.id_reg, 100).err().unwrap();
+        assert!(err.to_string().contains("1 errors"), "{}", err);
+    }
+}
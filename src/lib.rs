@@ -1,2 +1,6 @@
+//lets the code generated by sim_macros refer to ::sim from inside this crate too
+extern crate self as sim;
+
 pub mod core;
 pub mod net;
+pub mod queueing;
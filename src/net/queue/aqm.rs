@@ -0,0 +1,131 @@
+use crate::core::clock;
+use crate::core::random::variates::Random;
+use crate::net::queue::codel::new_codel;
+use crate::net::queue::red::new_red;
+
+//What an active queue management algorithm decides for a message
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    Accept,
+    Drop,
+    //signal congestion: the queue sets the ECN congestion experienced flag if the message is
+    //ECN capable and drops it otherwise
+    Mark,
+}
+
+//The state of the queue an Aqm is asked about
+pub struct QueueState {
+    pub now: u64,
+    pub msgs: u64,
+    pub bytes: u64,
+}
+
+//Consulted by the Queue for every message. Capacity limits and the drop policy of the queue are
+//applied after on_enqueue accepted (or marked) a message.
+pub trait Aqm {
+    //a message arrives, state does not include it yet
+    fn on_enqueue(&mut self, state: &QueueState, rng: &mut Random) -> Verdict;
+
+    //the head message leaves after waiting sojourn ns, state does not include it anymore.
+    //If the message is dropped the queue asks again for the next one
+    fn on_dequeue(&mut self, sojourn: u64, state: &QueueState) -> Verdict;
+
+    fn name(&self) -> String;
+}
+
+fn parse_args(args: &str) -> Vec<&str> {
+    args.split(',').map(|a| a.trim()).collect()
+}
+
+//Parses "red(min_th, max_th, max_p)", "red(min_th, max_th, max_p, weight)",
+//"codel(target, interval)" or "codel" with the defaults of 5ms and 100ms. Thresholds are in messages
+pub fn parse_aqm(s: &str) -> Result<Box<dyn Aqm>, Box<dyn std::error::Error>> {
+    let s = s.trim();
+    let (name, args) = match (s.find('('), s.ends_with(')')) {
+        (Some(open), true) => (s[..open].trim(), parse_args(&s[open + 1..s.len() - 1])),
+        (None, false) => (s, Vec::new()),
+        _ => return Err(format!("Not a valid aqm: \"{}\"", s).into()),
+    };
+
+    //the constructors panic on these, so they are checked here first
+    match (name, args.len()) {
+        ("red", 3) | ("red", 4) => {
+            let min_th: f64 = args[0].parse()?;
+            let max_th: f64 = args[1].parse()?;
+            let max_p: f64 = args[2].parse()?;
+            if min_th.is_nan() || max_th.is_nan() || min_th >= max_th {
+                return Err(format!("red needs min_th < max_th: \"{}\"", s).into());
+            }
+            if max_p.is_nan() || max_p <= 0.0 || max_p > 1.0 {
+                return Err(format!("red needs a max_p in (0, 1]: \"{}\"", s).into());
+            }
+            let mut red = new_red(min_th, max_th, max_p);
+            if args.len() == 4 {
+                red.weight = args[3].parse()?;
+                if red.weight.is_nan() || red.weight <= 0.0 || red.weight > 1.0 {
+                    return Err(format!("red needs a weight in (0, 1]: \"{}\"", s).into());
+                }
+            }
+            Ok(Box::new(red))
+        }
+        ("codel", 0) => Ok(Box::new(new_codel(
+            5 * clock::MILLI_SECONDS,
+            100 * clock::MILLI_SECONDS,
+        ))),
+        ("codel", 2) => {
+            let target = clock::parse_time(args[0])?;
+            let interval = clock::parse_time(args[1])?;
+            if interval == 0 {
+                return Err(format!("codel needs an interval > 0: \"{}\"", s).into());
+            }
+            Ok(Box::new(new_codel(target, interval)))
+        }
+        ("red", _) => Err("red takes min_th, max_th, max_p and optionally weight".into()),
+        ("codel", _) => Err("codel takes target and interval or no arguments".into()),
+        (other, _) => Err(format!("Unknown aqm \"{}\"", other).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_valid_aqms() {
+        assert_eq!(
+            parse_aqm("red(5, 15, 0.1)").unwrap().name(),
+            "red(5, 15, 0.1)"
+        );
+        assert!(parse_aqm("red(5, 15, 1, 0.5)").is_ok());
+        assert_eq!(
+            parse_aqm("codel").unwrap().name(),
+            "codel(5000000, 100000000)"
+        );
+        assert_eq!(
+            parse_aqm("codel(1ms, 10ms)").unwrap().name(),
+            "codel(1000000, 10000000)"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_configurations() {
+        for s in &[
+            "red(15, 5, 0.1)",
+            "red(5, 5, 0.1)",
+            "red(5, 15, 0)",
+            "red(5, 15, 1.5)",
+            "red(5, 15, 0.1, 0)",
+            "red(5, 15, 0.1, 2)",
+            "red(NaN, 15, 0.1)",
+            "red(5, NaN, 0.1)",
+            "red(5, 15, NaN)",
+            "red(5, 15, 0.1, NaN)",
+            "red(5, 15)",
+            "codel(5ms, 0)",
+            "codel(5ms)",
+            "pie",
+        ] {
+            assert!(parse_aqm(s).is_err(), "{} was accepted", s);
+        }
+    }
+}
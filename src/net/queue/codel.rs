@@ -0,0 +1,167 @@
+use crate::core::random::variates::Random;
+use crate::net::queue::aqm::{Aqm, QueueState, Verdict};
+
+//Controlled Delay (RFC 8289). Looks at how long messages waited when they leave. Once the waiting
+//time stayed above target for a whole interval it starts dropping (or marking) at the head, with
+//the time between drops shrinking by the square root of the number of drops, until the waiting time
+//falls below target again.
+pub struct CoDel {
+    pub target: u64,
+    pub interval: u64,
+    //no drops while at most this many bytes are queued, one maximum sized message
+    pub mtu_bytes: u64,
+
+    //when the waiting time has been above target for an interval, 0 while it is below
+    first_above_time: u64,
+    drop_next: u64,
+    count: u64,
+    last_count: u64,
+    dropping: bool,
+}
+
+pub fn new_codel(target: u64, interval: u64) -> CoDel {
+    if interval == 0 {
+        panic!("CoDel needs an interval > 0");
+    }
+    CoDel {
+        target: target,
+        interval: interval,
+        mtu_bytes: 1500,
+
+        first_above_time: 0,
+        drop_next: 0,
+        count: 0,
+        last_count: 0,
+        dropping: false,
+    }
+}
+
+impl CoDel {
+    fn control_law(&self, t: u64) -> u64 {
+        t + (self.interval as f64 / (self.count as f64).sqrt()) as u64
+    }
+
+    fn ok_to_drop(&mut self, sojourn: u64, state: &QueueState) -> bool {
+        if sojourn < self.target || state.bytes <= self.mtu_bytes {
+            self.first_above_time = 0;
+            return false;
+        }
+        if self.first_above_time == 0 {
+            self.first_above_time = state.now + self.interval;
+            return false;
+        }
+        state.now >= self.first_above_time
+    }
+}
+
+impl Aqm for CoDel {
+    fn on_enqueue(&mut self, _state: &QueueState, _rng: &mut Random) -> Verdict {
+        Verdict::Accept
+    }
+
+    fn on_dequeue(&mut self, sojourn: u64, state: &QueueState) -> Verdict {
+        let ok_to_drop = self.ok_to_drop(sojourn, state);
+
+        if self.dropping {
+            if !ok_to_drop {
+                self.dropping = false;
+                return Verdict::Accept;
+            }
+            if state.now >= self.drop_next {
+                self.count += 1;
+                self.drop_next = self.control_law(self.drop_next);
+                return Verdict::Mark;
+            }
+            return Verdict::Accept;
+        }
+
+        if ok_to_drop {
+            self.dropping = true;
+            //start close to the drop rate of the last dropping state if that was not long ago
+            let delta = self.count.saturating_sub(self.last_count);
+            self.count =
+                if delta > 1 && state.now.saturating_sub(self.drop_next) < 16 * self.interval {
+                    delta
+                } else {
+                    1
+                };
+            self.last_count = self.count;
+            self.drop_next = self.control_law(state.now);
+            return Verdict::Mark;
+        }
+        Verdict::Accept
+    }
+
+    fn name(&self) -> String {
+        format!("codel({}, {})", self.target, self.interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: u64 = 100;
+
+    fn dequeue(codel: &mut CoDel, now: u64, sojourn: u64) -> Verdict {
+        let state = QueueState {
+            now: now,
+            msgs: 10,
+            bytes: 10 * 1500,
+        };
+        codel.on_dequeue(sojourn, &state)
+    }
+
+    #[test]
+    fn control_law_shrinks_with_the_square_root_of_count() {
+        let mut codel = new_codel(5, INTERVAL);
+        codel.count = 1;
+        assert_eq!(codel.control_law(1000), 1100);
+        codel.count = 4;
+        assert_eq!(codel.control_law(1000), 1050);
+        codel.count = 16;
+        assert_eq!(codel.control_law(1000), 1025);
+    }
+
+    #[test]
+    fn drops_after_an_interval_above_target() {
+        let mut codel = new_codel(5, INTERVAL);
+
+        //above target, but not for a whole interval yet
+        assert_eq!(dequeue(&mut codel, 1000, 10), Verdict::Accept);
+        assert_eq!(dequeue(&mut codel, 1099, 10), Verdict::Accept);
+
+        //first drop, the next one is an interval later
+        assert_eq!(dequeue(&mut codel, 1100, 10), Verdict::Mark);
+        assert_eq!(codel.drop_next, 1200);
+        assert_eq!(dequeue(&mut codel, 1150, 10), Verdict::Accept);
+
+        //then interval / sqrt(count) apart
+        assert_eq!(dequeue(&mut codel, 1200, 10), Verdict::Mark);
+        assert_eq!(codel.drop_next, 1200 + 70);
+        assert_eq!(dequeue(&mut codel, 1270, 10), Verdict::Mark);
+        assert_eq!(codel.drop_next, 1270 + 57);
+
+        //below target the dropping state ends
+        assert_eq!(dequeue(&mut codel, 1300, 1), Verdict::Accept);
+        assert!(!codel.dropping);
+        assert_eq!(dequeue(&mut codel, 1400, 1), Verdict::Accept);
+    }
+
+    #[test]
+    fn no_drops_with_a_small_backlog() {
+        let mut codel = new_codel(5, INTERVAL);
+        let state = QueueState {
+            now: 0,
+            msgs: 1,
+            bytes: 1500,
+        };
+        for t in 0..10 {
+            let state = QueueState {
+                now: t * INTERVAL,
+                ..state
+            };
+            assert_eq!(codel.on_dequeue(1000, &state), Verdict::Accept);
+        }
+    }
+}
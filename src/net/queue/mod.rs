@@ -1 +1,5 @@
+pub mod aqm;
+pub mod codel;
 pub mod queue;
+pub mod red;
+pub mod scheduler;
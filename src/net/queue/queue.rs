@@ -1,18 +1,54 @@
-use crate::core::connection::connection::Port;
+use crate::core::clock;
 use crate::core::contexts::EventHandleContext;
 use crate::core::events::event::Event;
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::core::statistics::{
+    new_summary, new_time_weighted, new_time_weighted_bins, Summary, TimeWeighted, TimeWeightedBins,
+};
+use crate::net::queue::aqm::{parse_aqm, Aqm, QueueState, Verdict};
+
+use std::collections::VecDeque;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DropPolicy {
+    //drop the message that arrives at a full queue
+    DropTail,
+    //drop the oldest messages until the new one fits
+    DropHead,
+    //drop randomly chosen messages (the new one included) until the new one fits
+    RandomDrop,
+}
 
 pub struct Queue {
     type_id: ModuleTypeId,
     id: ModuleId,
     name: String,
 
-    msgs: Vec<Box<Message>>,
-    receive_ready: Vec<PortId>,
+    //None means unbounded
+    pub capacity_msgs: Option<u64>,
+    pub capacity_bytes: Option<u64>,
+    pub drop_policy: DropPolicy,
+    //active queue management, consulted before the capacity limits
+    pub aqm: Option<Box<dyn Aqm>>,
+
+    //messages with the time they were queued
+    msgs: VecDeque<(Box<dyn Message>, u64)>,
+    bytes_queued: u64,
+    receive_ready: VecDeque<PortId>,
+
+    length: TimeWeighted,
+    //average length per bin of simulation time, see set_parameter length_bin
+    length_bins: TimeWeightedBins,
+    waiting_time: Summary,
+    msgs_enqueued: u64,
+    msgs_dropped: u64,
+    bytes_dropped: u64,
+    //included in msgs_dropped
+    aqm_dropped: u64,
+    msgs_marked: u64,
 }
 
 //messages get sent out here, on the port where the trigger came
@@ -25,57 +61,228 @@ pub const IN_GATE: GateId = GateId(1);
 //and sent on the port on OUT_GATE
 pub const TRIGG_GATE: GateId = GateId(2);
 
-pub static TYPE_STR: &str = "QueueModule";
-
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
-}
-
 pub fn new(id_reg: &mut IdRegistrar, name: String) -> Queue {
     Queue {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
+        type_id: id_reg.lookup_module_id(Queue::TYPE_STR.to_owned()).unwrap(),
         name: name,
 
-        msgs: Vec::new(),
-        receive_ready: Vec::new(),
+        capacity_msgs: None,
+        capacity_bytes: None,
+        drop_policy: DropPolicy::DropTail,
+        aqm: None,
+
+        msgs: VecDeque::new(),
+        bytes_queued: 0,
+        receive_ready: VecDeque::new(),
+
+        length: new_time_weighted(0, 0.0),
+        length_bins: new_time_weighted_bins(100 * clock::MILLI_SECONDS, 0, 0.0),
+        waiting_time: new_summary(),
+        msgs_enqueued: 0,
+        msgs_dropped: 0,
+        bytes_dropped: 0,
+        aqm_dropped: 0,
+        msgs_marked: 0,
     }
 }
 
-impl Module for Queue {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        vec![OUT_GATE, IN_GATE, TRIGG_GATE]
+//a queue with active queue management, see aqm::parse_aqm for the configuration strings
+pub fn new_with_aqm(id_reg: &mut IdRegistrar, name: String, aqm: Box<dyn Aqm>) -> Queue {
+    let mut queue = new(id_reg, name);
+    queue.aqm = Some(aqm);
+    queue
+}
+
+fn bytes_of(msg: &dyn Message) -> u64 {
+    msg.meta().byte_length()
+}
+
+impl Queue {
+    fn fits(&self, msgs: u64, bytes: u64) -> bool {
+        let msgs_ok = match self.capacity_msgs {
+            Some(cap) => msgs <= cap,
+            None => true,
+        };
+        let bytes_ok = match self.capacity_bytes {
+            Some(cap) => bytes <= cap,
+            None => true,
+        };
+        msgs_ok && bytes_ok
+    }
+
+    fn drop_msg(&mut self, msg: Box<dyn Message>) {
+        self.msgs_dropped += 1;
+        self.bytes_dropped += bytes_of(msg.as_ref());
     }
 
+    fn set_length(&mut self, now: u64) {
+        self.length.set(now, self.msgs.len() as f64);
+        self.length_bins.set(now, self.msgs.len() as f64);
+    }
+
+    fn state(&self, now: u64) -> QueueState {
+        QueueState {
+            now: now,
+            msgs: self.msgs.len() as u64,
+            bytes: self.bytes_queued,
+        }
+    }
+
+    //carries out the verdict of the aqm, returns the message if it may pass
+    fn apply_verdict(
+        &mut self,
+        verdict: Verdict,
+        mut msg: Box<dyn Message>,
+    ) -> Option<Box<dyn Message>> {
+        match verdict {
+            Verdict::Accept => Some(msg),
+            Verdict::Mark if msg.meta().ecn_capable => {
+                msg.meta_mut().ecn_ce = true;
+                self.msgs_marked += 1;
+                Some(msg)
+            }
+            Verdict::Mark | Verdict::Drop => {
+                self.aqm_dropped += 1;
+                self.drop_msg(msg);
+                None
+            }
+        }
+    }
+
+    //asks the aqm about an arriving message
+    fn admit(
+        &mut self,
+        msg: Box<dyn Message>,
+        ctx: &mut EventHandleContext,
+    ) -> Option<Box<dyn Message>> {
+        let state = self.state(ctx.mctx.time.now());
+        let verdict = match self.aqm.as_mut() {
+            Some(aqm) => aqm.on_enqueue(&state, &mut ctx.random()),
+            None => Verdict::Accept,
+        };
+        self.apply_verdict(verdict, msg)
+    }
+
+    //asks the aqm about a message that is about to leave
+    fn release(
+        &mut self,
+        msg: Box<dyn Message>,
+        sojourn: u64,
+        now: u64,
+    ) -> Option<Box<dyn Message>> {
+        let state = self.state(now);
+        let verdict = match self.aqm.as_mut() {
+            Some(aqm) => aqm.on_dequeue(sojourn, &state),
+            None => Verdict::Accept,
+        };
+        self.apply_verdict(verdict, msg)
+    }
+
+    //the next message that gets through the aqm, if any is left
+    fn dequeue(&mut self, now: u64) -> Option<Box<dyn Message>> {
+        while !self.msgs.is_empty() {
+            let (msg, queued_at) = self.remove_at(0);
+            self.set_length(now);
+            if let Some(msg) = self.release(msg, now - queued_at, now) {
+                self.waiting_time.add((now - queued_at) as f64);
+                return Some(msg);
+            }
+        }
+        None
+    }
+
+    fn remove_at(&mut self, idx: usize) -> (Box<dyn Message>, u64) {
+        let (msg, time) = self.msgs.remove(idx).unwrap();
+        self.bytes_queued -= bytes_of(msg.as_ref());
+        (msg, time)
+    }
+
+    //makes room for msg according to the drop policy and queues it, unless it is dropped itself
+    fn enqueue(&mut self, msg: Box<dyn Message>, ctx: &mut EventHandleContext) {
+        let bytes = bytes_of(msg.as_ref());
+        let now = ctx.mctx.time.now();
+
+        //a message that is larger than the whole queue never fits
+        if !self.fits(1, bytes) {
+            self.drop_msg(msg);
+            return;
+        }
+
+        while !self.fits(self.msgs.len() as u64 + 1, self.bytes_queued + bytes) {
+            match self.drop_policy {
+                DropPolicy::DropTail => {
+                    self.drop_msg(msg);
+                    return;
+                }
+                DropPolicy::DropHead => {
+                    let (old, _) = self.remove_at(0);
+                    self.drop_msg(old);
+                }
+                DropPolicy::RandomDrop => {
+                    let victim = ctx.random().uniform_int(0, self.msgs.len() as u64 + 1) as usize;
+                    if victim == self.msgs.len() {
+                        self.drop_msg(msg);
+                        self.set_length(now);
+                        return;
+                    }
+                    let (old, _) = self.remove_at(victim);
+                    self.drop_msg(old);
+                }
+            }
+        }
+
+        self.bytes_queued += bytes;
+        self.msgs.push_back((msg, now));
+        self.msgs_enqueued += 1;
+        self.set_length(now);
+    }
+}
+
+#[sim_module(
+    type_str = "QueueModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+    gate(id = TRIGG_GATE, name = "trigger", dir = input),
+)]
+impl Module for Queue {
     fn handle_message(
         &mut self,
-        msg: Box<Message>,
+        msg: Box<dyn Message>,
         gate: GateId,
         port: PortId,
         ctx: &mut EventHandleContext,
-    ) -> Result<HandleResult, Box<std::error::Error>> {
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
         match gate {
             IN_GATE => {
                 //if some port signaled readyness push to this port instead of queuing
                 //else put into queue
-                if !self.receive_ready.is_empty() {
-                    let bufferd_port = self.receive_ready.remove(0);
-                    ctx.msgs_to_send.push_back((msg, OUT_GATE, bufferd_port));
-                } else {
-                    self.msgs.push(msg);
+                let msg = match self.admit(msg, ctx) {
+                    Some(msg) => msg,
+                    None => return Ok(HandleResult {}),
+                };
+                match self.receive_ready.pop_front() {
+                    Some(bufferd_port) => {
+                        self.msgs_enqueued += 1;
+                        let now = ctx.mctx.time.now();
+                        match self.release(msg, 0, now) {
+                            Some(msg) => {
+                                self.waiting_time.add(0.0);
+                                ctx.msgs_to_send.push_back((msg, OUT_GATE, bufferd_port));
+                            }
+                            None => self.receive_ready.push_front(bufferd_port),
+                        }
+                    }
+                    None => self.enqueue(msg, ctx),
                 }
             }
 
             //if triggered send message from queue to the port on OUT_GATE
             //else remember readiness in receive_ready
-            TRIGG_GATE => {
-                if !self.msgs.is_empty() {
-                    let bufferd_msg = self.msgs.remove(0);
-                    ctx.msgs_to_send.push_back((bufferd_msg, OUT_GATE, port));
-                } else {
-                    self.receive_ready.push(port);
-                }
-            }
+            TRIGG_GATE => match self.dequeue(ctx.mctx.time.now()) {
+                Some(bufferd_msg) => ctx.msgs_to_send.push_back((bufferd_msg, OUT_GATE, port)),
+                None => self.receive_ready.push_back(port),
+            },
             OUT_GATE => panic!("Should not receive messages on OUT_GATE"),
             _ => panic!("Should not receive messages on other gates"),
         }
@@ -85,33 +292,293 @@ impl Module for Queue {
 
     fn handle_timer_event(
         &mut self,
-        _ev: &Event,
+        _ev: &dyn Event,
         _ctx: &mut EventHandleContext,
-    ) -> Result<HandleResult, Box<std::error::Error>> {
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
         panic!("Should never receive timer events")
     }
 
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
+    //capacity_msgs and capacity_bytes take a number or "none", drop_policy one of drop-tail,
+    //drop-head or random-drop. Shrinking the capacity does not drop messages already queued.
+    //aqm takes "none" or an aqm like "red(5, 15, 0.1)" or "codel(5ms, 100ms)", see aqm::parse_aqm.
+    //length_bin is the width of the bins the average length is reported for, eg "10ms", 100ms by
+    //default. Changing it starts the series over
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let capacity = |value: &str| -> Result<Option<u64>, Box<dyn std::error::Error>> {
+            match value {
+                "none" => Ok(None),
+                v => Ok(Some(v.parse()?)),
+            }
+        };
+        match key {
+            "capacity_msgs" => self.capacity_msgs = capacity(value)?,
+            "capacity_bytes" => self.capacity_bytes = capacity(value)?,
+            "drop_policy" => {
+                self.drop_policy = match value {
+                    "drop-tail" => DropPolicy::DropTail,
+                    "drop-head" => DropPolicy::DropHead,
+                    "random-drop" => DropPolicy::RandomDrop,
+                    _ => return Err(format!("Unknown drop policy {}", value).into()),
+                }
+            }
+            "aqm" => {
+                self.aqm = match value {
+                    "none" => None,
+                    v => Some(parse_aqm(v)?),
+                }
+            }
+            "length_bin" => {
+                let width = clock::parse_time(value)?;
+                if width == 0 {
+                    return Err("length_bin must be greater than 0".into());
+                }
+                let now = self.length_bins.last_time();
+                self.length_bins = new_time_weighted_bins(width, now, self.msgs.len() as f64);
+            }
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let now = ctx.mctx.time.now();
+        let name = self.name();
+        let mut results = vec![
+            (
+                name.clone(),
+                "enqueued_msgs".to_owned(),
+                self.msgs_enqueued.to_string(),
+            ),
+            (
+                name.clone(),
+                "dropped_msgs".to_owned(),
+                self.msgs_dropped.to_string(),
+            ),
+            (
+                name.clone(),
+                "dropped_bytes".to_owned(),
+                self.bytes_dropped.to_string(),
+            ),
+            (
+                name.clone(),
+                "mean_length".to_owned(),
+                self.length.mean(now).to_string(),
+            ),
+            (
+                name.clone(),
+                "max_length".to_owned(),
+                self.length.max.to_string(),
+            ),
+            (
+                name.clone(),
+                "left_in_queue".to_owned(),
+                self.msgs.len().to_string(),
+            ),
+        ];
+
+        if let Some(aqm) = &self.aqm {
+            results.push((name.clone(), "aqm".to_owned(), aqm.name()));
+            results.push((
+                name.clone(),
+                "aqm_dropped_msgs".to_owned(),
+                self.aqm_dropped.to_string(),
+            ));
+            results.push((
+                name.clone(),
+                "marked_msgs".to_owned(),
+                self.msgs_marked.to_string(),
+            ));
+        }
+
+        //bins the queue was empty for all the time are left out
+        for (start, length) in self.length_bins.bins(now) {
+            if length == 0.0 {
+                continue;
+            }
+            results.push((
+                name.clone(),
+                format!("length_{}", start),
+                length.to_string(),
+            ));
+        }
+
+        if self.waiting_time.count > 0 {
+            results.push((
+                name.clone(),
+                "mean_waiting_time".to_owned(),
+                self.waiting_time.mean().round().to_string(),
+            ));
+            results.push((
+                name.clone(),
+                "max_waiting_time".to_owned(),
+                self.waiting_time.max.to_string(),
+            ));
+        }
+
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::{new_test_env, TestEnv};
+
+    fn queue(env: &mut TestEnv, params: &[(&str, &str)]) -> Queue {
+        Queue::register(&mut env.id_reg);
+        let mut q = new(&mut env.id_reg, "Queue".to_owned());
+        for (key, value) in params {
+            q.set_parameter(key, value).unwrap();
+        }
+        q
     }
 
-    fn module_id(&self) -> ModuleId {
-        self.id
+    fn push(env: &mut TestEnv, q: &mut Queue, bytes: u64) {
+        let msg = env.msg(bytes * 8);
+        env.with_ctx(|ctx| q.handle_message(msg, IN_GATE, PortId(0), ctx))
+            .unwrap();
     }
 
-    fn name(&self) -> String {
-        self.name.clone()
+    //triggers the queue on port, returns the size of the message it sent out if there was one
+    fn pull(env: &mut TestEnv, q: &mut Queue, port: u64) -> Option<u64> {
+        let trigger = env.msg(0);
+        env.with_ctx(|ctx| q.handle_message(trigger, TRIGG_GATE, PortId(port), ctx))
+            .unwrap();
+        env.sent.pop_front().map(|(msg, gate, p)| {
+            assert!(gate == OUT_GATE && p == PortId(port));
+            bytes_of(msg.as_ref())
+        })
     }
 
-    fn initialize(
-        &mut self,
-        _gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
-        _ctx: &mut EventHandleContext,
-    ) {
+    fn drain(env: &mut TestEnv, q: &mut Queue) -> Vec<u64> {
+        let mut sizes = Vec::new();
+        while let Some(bytes) = pull(env, q, 0) {
+            sizes.push(bytes);
+        }
+        //the last pull found the queue empty
+        q.receive_ready.clear();
+        sizes
     }
 
-    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
-        //println!("Finalize Queue: {}", &self.name);
-        None
+    #[test]
+    fn drop_tail_drops_arrivals() {
+        let mut env = new_test_env();
+        let mut q = queue(&mut env, &[("capacity_msgs", "2")]);
+        for bytes in &[10, 20, 30] {
+            push(&mut env, &mut q, *bytes);
+        }
+        assert_eq!((q.msgs_dropped, q.bytes_dropped), (1, 30));
+        assert_eq!(drain(&mut env, &mut q), vec![10, 20]);
+    }
+
+    #[test]
+    fn drop_head_drops_the_oldest() {
+        let mut env = new_test_env();
+        let mut q = queue(
+            &mut env,
+            &[("capacity_msgs", "2"), ("drop_policy", "drop-head")],
+        );
+        for bytes in &[10, 20, 30] {
+            push(&mut env, &mut q, *bytes);
+        }
+        assert_eq!((q.msgs_dropped, q.bytes_dropped), (1, 10));
+        assert_eq!(drain(&mut env, &mut q), vec![20, 30]);
+    }
+
+    #[test]
+    fn random_drop_picks_any_message() {
+        let mut env = new_test_env();
+        let mut q = queue(
+            &mut env,
+            &[("capacity_msgs", "10"), ("drop_policy", "random-drop")],
+        );
+        for bytes in 1..=1000 {
+            push(&mut env, &mut q, bytes);
+        }
+        assert_eq!(q.msgs_dropped, 990);
+        let kept = drain(&mut env, &mut q);
+        assert_eq!(kept.len(), 10);
+        //neither drop-tail nor drop-head, and still in order
+        assert!(kept != (1..=10).collect::<Vec<u64>>(), "{:?}", kept);
+        assert!(kept != (991..=1000).collect::<Vec<u64>>(), "{:?}", kept);
+        assert!(kept.windows(2).all(|w| w[0] < w[1]), "{:?}", kept);
+    }
+
+    #[test]
+    fn byte_capacity_counts_bytes_not_messages() {
+        let mut env = new_test_env();
+        let mut q = queue(
+            &mut env,
+            &[("capacity_bytes", "100"), ("drop_policy", "drop-head")],
+        );
+        push(&mut env, &mut q, 60);
+        push(&mut env, &mut q, 40);
+        assert_eq!(q.msgs_dropped, 0);
+        //larger than the whole queue, nothing is dropped to make room for it
+        push(&mut env, &mut q, 101);
+        assert_eq!((q.msgs_dropped, q.bytes_queued), (1, 100));
+        push(&mut env, &mut q, 50);
+        assert_eq!(q.bytes_dropped, 101 + 60);
+        assert_eq!(drain(&mut env, &mut q), vec![40, 50]);
+
+        //a started byte takes a whole one
+        let mut q = queue(&mut env, &[("capacity_bytes", "2")]);
+        let msg = env.msg(17);
+        env.with_ctx(|ctx| q.handle_message(msg, IN_GATE, PortId(0), ctx))
+            .unwrap();
+        assert_eq!(q.msgs_dropped, 1);
+    }
+
+    #[test]
+    fn pulls_on_an_empty_queue_are_served_first() {
+        let mut env = new_test_env();
+        let mut q = queue(&mut env, &[]);
+        assert_eq!(pull(&mut env, &mut q, 3), None);
+        assert_eq!(pull(&mut env, &mut q, 1), None);
+
+        //sent on right away, in the order the pulls came in
+        env.set_time(5);
+        push(&mut env, &mut q, 10);
+        push(&mut env, &mut q, 20);
+        let sent: Vec<(u64, u64)> = env
+            .sent
+            .drain(..)
+            .map(|(msg, _, port)| (bytes_of(msg.as_ref()), port.0))
+            .collect();
+        assert_eq!(sent, vec![(10, 3), (20, 1)]);
+        assert_eq!(q.msgs.len(), 0);
+        assert_eq!(q.msgs_enqueued, 2);
+        assert_eq!(q.waiting_time.max, 0.0);
+
+        push(&mut env, &mut q, 30);
+        assert!(env.sent.is_empty());
+        env.set_time(15);
+        assert_eq!(pull(&mut env, &mut q, 0), Some(30));
+        assert_eq!(q.waiting_time.max, 10.0);
+    }
+
+    #[test]
+    fn length_is_reported_for_bins_with_messages() {
+        let mut env = new_test_env();
+        let mut q = queue(&mut env, &[]);
+        env.set_time(150 * clock::MILLI_SECONDS);
+        push(&mut env, &mut q, 10);
+        env.set_time(250 * clock::MILLI_SECONDS);
+        drain(&mut env, &mut q);
+        env.set_time(clock::SECONDS);
+
+        let results = env.with_ctx(|ctx| q.finalize(ctx)).unwrap().results;
+        let bins: Vec<(String, String)> = results
+            .into_iter()
+            .filter(|(_, f, _)| f.starts_with("length_"))
+            .map(|(_, f, v)| (f, v))
+            .collect();
+        assert_eq!(
+            bins,
+            vec![
+                ("length_100000000".to_owned(), "0.5".to_owned()),
+                ("length_200000000".to_owned(), "0.5".to_owned()),
+            ]
+        );
     }
 }
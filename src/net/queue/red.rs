@@ -0,0 +1,129 @@
+use crate::core::random::variates::Random;
+use crate::net::queue::aqm::{Aqm, QueueState, Verdict};
+
+//Random Early Detection (Floyd, Jacobson 1993). Keeps an exponentially weighted average of the
+//queue length (in messages) at arrivals. Below min_th everything is accepted, above max_th
+//everything is dropped, in between messages are marked with a probability that rises linearly up
+//to max_p and grows with the number of messages accepted since the last mark.
+//The average is not decayed while the queue is idle.
+pub struct Red {
+    pub min_th: f64,
+    pub max_th: f64,
+    pub max_p: f64,
+    pub weight: f64,
+
+    avg: f64,
+    //messages since the last mark, -1 if the average was below min_th
+    count: i64,
+}
+
+pub fn new_red(min_th: f64, max_th: f64, max_p: f64) -> Red {
+    if min_th.is_nan() || max_th.is_nan() || min_th >= max_th {
+        panic!("RED needs min_th < max_th");
+    }
+    if max_p.is_nan() || max_p <= 0.0 || max_p > 1.0 {
+        panic!("RED needs a max_p in (0, 1]");
+    }
+    Red {
+        min_th: min_th,
+        max_th: max_th,
+        max_p: max_p,
+        weight: 0.002,
+
+        avg: 0.0,
+        count: -1,
+    }
+}
+
+impl Red {
+    pub fn average(&self) -> f64 {
+        self.avg
+    }
+}
+
+impl Aqm for Red {
+    fn on_enqueue(&mut self, state: &QueueState, rng: &mut Random) -> Verdict {
+        self.avg = (1.0 - self.weight) * self.avg + self.weight * state.msgs as f64;
+
+        if self.avg < self.min_th {
+            self.count = -1;
+            return Verdict::Accept;
+        }
+        if self.avg >= self.max_th {
+            self.count = 0;
+            return Verdict::Drop;
+        }
+
+        self.count += 1;
+        let p_b = self.max_p * (self.avg - self.min_th) / (self.max_th - self.min_th);
+        let denom = 1.0 - self.count as f64 * p_b;
+        let p_a = if denom <= 0.0 { 1.0 } else { p_b / denom };
+        if rng.bernoulli(p_a) {
+            self.count = 0;
+            Verdict::Mark
+        } else {
+            Verdict::Accept
+        }
+    }
+
+    fn on_dequeue(&mut self, _sojourn: u64, _state: &QueueState) -> Verdict {
+        Verdict::Accept
+    }
+
+    fn name(&self) -> String {
+        format!("red({}, {}, {})", self.min_th, self.max_th, self.max_p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prng::XorShiftRng;
+    use rand::SeedableRng;
+
+    fn state(msgs: u64) -> QueueState {
+        QueueState {
+            now: 0,
+            msgs: msgs,
+            bytes: msgs * 1000,
+        }
+    }
+
+    #[test]
+    fn thresholds() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut random = Random { rng: &mut rng };
+        let mut red = new_red(5.0, 15.0, 0.1);
+        red.weight = 1.0;
+        for _ in 0..100 {
+            assert_eq!(red.on_enqueue(&state(4), &mut random), Verdict::Accept);
+        }
+        assert_eq!(red.average(), 4.0);
+        for _ in 0..100 {
+            assert_eq!(red.on_enqueue(&state(15), &mut random), Verdict::Drop);
+        }
+    }
+
+    #[test]
+    fn marks_between_the_thresholds() {
+        let mut rng = XorShiftRng::from_seed([7; 16]);
+        let mut random = Random { rng: &mut rng };
+        let mut red = new_red(5.0, 15.0, 0.1);
+        red.weight = 1.0;
+
+        //p_b is 0.05 halfway between the thresholds. Counting the accepted messages spreads the
+        //marks out evenly: a mark comes 1 to 1/p_b - 1 = 19 messages after the last one, 10 on average
+        let n = 21000;
+        let mut marks = 0;
+        let mut since_mark = 0;
+        for _ in 0..n {
+            since_mark += 1;
+            if red.on_enqueue(&state(10), &mut random) == Verdict::Mark {
+                assert!(since_mark <= 19, "{} messages without a mark", since_mark);
+                since_mark = 0;
+                marks += 1;
+            }
+        }
+        assert!(marks > 2000 && marks < 2200, "{} marks", marks);
+    }
+}
@@ -0,0 +1,529 @@
+use crate::core::contexts::EventHandleContext;
+use crate::core::events::event::Event;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::messages::packet::Packet;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::core::statistics::{new_summary, Summary};
+
+use std::collections::{BTreeMap, VecDeque};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Discipline {
+    //always serve the lowest non empty class
+    StrictPriority,
+    //serve up to weight messages of each class per round
+    WeightedRoundRobin,
+    //serve up to weight * quantum bytes of each class per round, unused credit carries over
+    //while the class stays backlogged
+    DeficitRoundRobin,
+    //serve the message with the smallest finish tag, where a message of class i finishes
+    //size / weight_i after the later of the previous message of its class and the message
+    //currently in service (self-clocked fair queueing)
+    WeightedFairQueueing,
+}
+
+//What a message is classified by
+pub enum Classifier {
+    //the flow of a Msg<Packet>. Other messages go to the last class
+    Flow,
+    //the module that sent the message first. Messages without a source go to the last class
+    Source,
+    //returns the class directly, values past the last class go to the last class
+    Function(Box<dyn Fn(&dyn Message) -> usize>),
+}
+
+impl Classifier {
+    //the class of msg out of 0..classes. class_map maps the value looked at (flow or source id)
+    //to a class, values that are not in there go to class value % classes
+    pub fn classify(
+        &self,
+        msg: &(dyn Message + 'static),
+        class_map: &BTreeMap<u64, usize>,
+        classes: usize,
+    ) -> usize {
+        let last = classes - 1;
+        let value = match self {
+            Classifier::Flow => msg.downcast_ref::<Packet>().map(|p| p.payload.flow),
+            Classifier::Source => msg.meta().source.map(|s| s.raw()),
+            Classifier::Function(f) => return f(msg).min(last),
+        };
+        match value {
+            Some(v) => match class_map.get(&v) {
+                Some(class) => (*class).min(last),
+                None => (v % classes as u64) as usize,
+            },
+            None => last,
+        }
+    }
+}
+
+//"flow" or "source", functions can only be set in code
+pub fn parse_classifier(value: &str) -> Result<Classifier, Box<dyn std::error::Error>> {
+    match value {
+        "flow" => Ok(Classifier::Flow),
+        "source" => Ok(Classifier::Source),
+        _ => Err(format!("Unknown classifier {}", value).into()),
+    }
+}
+
+//parses a list of value:class pairs like "7:0,8:0,9:1"
+pub fn parse_class_map(value: &str) -> Result<BTreeMap<u64, usize>, Box<dyn std::error::Error>> {
+    let mut map = BTreeMap::new();
+    for pair in value.split(',') {
+        let parts: Vec<&str> = pair.split(':').collect();
+        if parts.len() != 2 {
+            return Err(format!("expected value:class, got \"{}\"", pair).into());
+        }
+        map.insert(parts[0].trim().parse()?, parts[1].trim().parse()?);
+    }
+    Ok(map)
+}
+
+struct Entry {
+    msg: Box<dyn Message>,
+    queued_at: u64,
+    bytes: u64,
+    //finish tag for weighted fair queueing
+    tag: f64,
+}
+
+struct SubQueue {
+    msgs: VecDeque<Entry>,
+    weight: u64,
+
+    //deficit round robin credit in bytes
+    deficit: u64,
+    //finish tag of the last message of this class, for weighted fair queueing
+    last_tag: f64,
+
+    enqueued: u64,
+    dropped: u64,
+    sent: u64,
+    sent_bytes: u64,
+    waiting_time: Summary,
+}
+
+fn new_sub_queue(weight: u64) -> SubQueue {
+    SubQueue {
+        msgs: VecDeque::new(),
+        weight: weight,
+
+        deficit: 0,
+        last_tag: 0.0,
+
+        enqueued: 0,
+        dropped: 0,
+        sent: 0,
+        sent_bytes: 0,
+        waiting_time: new_summary(),
+    }
+}
+
+//Classifies incoming messages into sub-queues (classes 0..n) and serves them according to the
+//discipline. Speaks the same protocol on the same gates as Queue, so it can be used everywhere a
+//Queue is, eg as the buffers of make_router_with_buffers.
+pub struct Scheduler {
+    type_id: ModuleTypeId,
+    id: ModuleId,
+    name: String,
+
+    pub discipline: Discipline,
+    pub classifier: Classifier,
+    //see Classifier::classify
+    pub class_map: BTreeMap<u64, usize>,
+    //per class, None means unbounded. Messages arriving at a full class are dropped
+    pub capacity_msgs: Option<u64>,
+    //bytes a class may send per round and unit of weight with deficit round robin. Only set
+    //through set_parameter, which keeps it above 0
+    quantum: u64,
+
+    classes: Vec<SubQueue>,
+    receive_ready: VecDeque<PortId>,
+
+    //round robin position, and whether the current class already got its credit this round
+    current: usize,
+    served_in_round: u64,
+    visited: bool,
+    //finish tag of the message last sent, for weighted fair queueing
+    virtual_time: f64,
+}
+
+//messages get sent out here, on the port where the trigger came
+pub const OUT_GATE: GateId = GateId(0);
+
+//messages come in here (0..n) ports
+pub const IN_GATE: GateId = GateId(1);
+
+//if a message is received here the next message is scheduled and sent on the port on OUT_GATE
+pub const TRIGG_GATE: GateId = GateId(2);
+
+//one class per weight. Strict priority ignores the weights, class 0 has the highest priority
+pub fn new_scheduler(
+    id_reg: &mut IdRegistrar,
+    name: String,
+    discipline: Discipline,
+    weights: Vec<u64>,
+) -> Scheduler {
+    if weights.is_empty() {
+        panic!("A scheduler needs at least one class");
+    }
+    if weights.contains(&0) {
+        panic!("Scheduler weights must be greater than 0");
+    }
+    Scheduler {
+        id: id_reg.new_module_id(),
+        type_id: id_reg
+            .lookup_module_id(Scheduler::TYPE_STR.to_owned())
+            .unwrap(),
+        name: name,
+
+        discipline: discipline,
+        classifier: Classifier::Flow,
+        class_map: BTreeMap::new(),
+        capacity_msgs: None,
+        quantum: 1500,
+
+        classes: weights.into_iter().map(new_sub_queue).collect(),
+        receive_ready: VecDeque::new(),
+
+        current: 0,
+        served_in_round: 0,
+        visited: false,
+        virtual_time: 0.0,
+    }
+}
+
+fn bytes_of(msg: &dyn Message) -> u64 {
+    msg.meta().byte_length()
+}
+
+fn parse_weights(value: &str) -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+    let weights = value
+        .split(',')
+        .map(|w| w.trim().parse())
+        .collect::<Result<Vec<u64>, _>>()?;
+    if weights.contains(&0) {
+        return Err("Scheduler weights must be greater than 0".into());
+    }
+    Ok(weights)
+}
+
+impl Scheduler {
+    fn enqueue(&mut self, msg: Box<dyn Message>, now: u64) {
+        let class = self
+            .classifier
+            .classify(msg.as_ref(), &self.class_map, self.classes.len());
+        let bytes = bytes_of(msg.as_ref());
+        let virtual_time = self.virtual_time;
+        let capacity = self.capacity_msgs;
+        let q = &mut self.classes[class];
+
+        if let Some(cap) = capacity {
+            if q.msgs.len() as u64 >= cap {
+                q.dropped += 1;
+                return;
+            }
+        }
+
+        let tag = q.last_tag.max(virtual_time) + bytes as f64 / q.weight as f64;
+        q.last_tag = tag;
+        q.enqueued += 1;
+        q.msgs.push_back(Entry {
+            msg: msg,
+            queued_at: now,
+            bytes: bytes,
+            tag: tag,
+        });
+    }
+
+    //the class to serve next, None if all are empty
+    fn pick_class(&mut self) -> Option<usize> {
+        if self.classes.iter().all(|q| q.msgs.is_empty()) {
+            return None;
+        }
+
+        match self.discipline {
+            Discipline::StrictPriority => self.classes.iter().position(|q| !q.msgs.is_empty()),
+            Discipline::WeightedRoundRobin => loop {
+                let q = &self.classes[self.current];
+                if !q.msgs.is_empty() && self.served_in_round < q.weight {
+                    self.served_in_round += 1;
+                    return Some(self.current);
+                }
+                self.current = (self.current + 1) % self.classes.len();
+                self.served_in_round = 0;
+            },
+            Discipline::DeficitRoundRobin => loop {
+                //without credit no class could ever send and this would loop forever
+                debug_assert!(
+                    self.quantum > 0,
+                    "Scheduler {} has a quantum of 0",
+                    self.name
+                );
+                let quantum = self.quantum;
+                let q = &mut self.classes[self.current];
+                if q.msgs.is_empty() {
+                    q.deficit = 0;
+                } else {
+                    if !self.visited {
+                        q.deficit += q.weight * quantum;
+                        self.visited = true;
+                    }
+                    let head = q.msgs.front().unwrap().bytes;
+                    if head <= q.deficit {
+                        q.deficit -= head;
+                        return Some(self.current);
+                    }
+                }
+                self.current = (self.current + 1) % self.classes.len();
+                self.visited = false;
+            },
+            Discipline::WeightedFairQueueing => {
+                let mut best: Option<(usize, f64)> = None;
+                for (idx, q) in self.classes.iter().enumerate() {
+                    if let Some(head) = q.msgs.front() {
+                        match best {
+                            Some((_, tag)) if tag <= head.tag => {}
+                            _ => best = Some((idx, head.tag)),
+                        }
+                    }
+                }
+                best.map(|(idx, _)| idx)
+            }
+        }
+    }
+
+    fn dequeue(&mut self, now: u64) -> Option<Box<dyn Message>> {
+        let class = self.pick_class()?;
+        let q = &mut self.classes[class];
+        let entry = q.msgs.pop_front().unwrap();
+        q.sent += 1;
+        q.sent_bytes += entry.bytes;
+        q.waiting_time.add((now - entry.queued_at) as f64);
+        self.virtual_time = entry.tag;
+        Some(entry.msg)
+    }
+
+    fn set_weights(&mut self, weights: Vec<u64>) -> Result<(), Box<dyn std::error::Error>> {
+        if weights.len() != self.classes.len() {
+            return Err(format!(
+                "Scheduler {} has {} classes, got {} weights",
+                self.name,
+                self.classes.len(),
+                weights.len()
+            )
+            .into());
+        }
+        for (q, w) in self.classes.iter_mut().zip(weights) {
+            q.weight = w;
+        }
+        Ok(())
+    }
+}
+
+#[sim_module(
+    type_str = "SchedulerModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+    gate(id = TRIGG_GATE, name = "trigger", dir = input),
+)]
+impl Module for Scheduler {
+    fn handle_message(
+        &mut self,
+        msg: Box<dyn Message>,
+        gate: GateId,
+        port: PortId,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        let now = ctx.mctx.time.now();
+        match gate {
+            //queue the message, if some port signaled readyness schedule right away
+            IN_GATE => {
+                self.enqueue(msg, now);
+                if let Some(bufferd_port) = self.receive_ready.pop_front() {
+                    match self.dequeue(now) {
+                        Some(msg) => ctx.msgs_to_send.push_back((msg, OUT_GATE, bufferd_port)),
+                        None => self.receive_ready.push_front(bufferd_port),
+                    }
+                }
+            }
+            TRIGG_GATE => match self.dequeue(now) {
+                Some(msg) => ctx.msgs_to_send.push_back((msg, OUT_GATE, port)),
+                None => self.receive_ready.push_back(port),
+            },
+            OUT_GATE => panic!("Should not receive messages on OUT_GATE"),
+            _ => panic!("Should not receive messages on other gates"),
+        }
+
+        Ok(HandleResult {})
+    }
+
+    fn handle_timer_event(
+        &mut self,
+        _ev: &dyn Event,
+        _ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        panic!("Should never receive timer events")
+    }
+
+    //discipline is one of strict-priority, wrr, drr or wfq, weights a list like "4,2,1" with one
+    //weight per class, classifier flow or source, class_map a list of value:class pairs like
+    //"7:0,8:0,9:1", capacity_msgs a number or "none" and quantum a number of bytes
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "discipline" => {
+                self.discipline = match value {
+                    "strict-priority" => Discipline::StrictPriority,
+                    "wrr" => Discipline::WeightedRoundRobin,
+                    "drr" => Discipline::DeficitRoundRobin,
+                    "wfq" => Discipline::WeightedFairQueueing,
+                    _ => return Err(format!("Unknown discipline {}", value).into()),
+                };
+                self.current = 0;
+                self.served_in_round = 0;
+                self.visited = false;
+            }
+            "weights" => self.set_weights(parse_weights(value)?)?,
+            "classifier" => self.classifier = parse_classifier(value)?,
+            "class_map" => self.class_map = parse_class_map(value)?,
+            "capacity_msgs" => {
+                self.capacity_msgs = match value {
+                    "none" => None,
+                    v => Some(v.parse()?),
+                }
+            }
+            "quantum" => {
+                let quantum: u64 = value.parse()?;
+                if quantum == 0 {
+                    return Err("quantum must be greater than 0".into());
+                }
+                self.quantum = quantum;
+            }
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let name = self.name();
+        let mut results = Vec::new();
+        for (idx, q) in self.classes.iter().enumerate() {
+            let mut add = |key: &str, value: String| {
+                results.push((name.clone(), format!("class{}_{}", idx, key), value));
+            };
+            add("enqueued_msgs", q.enqueued.to_string());
+            add("dropped_msgs", q.dropped.to_string());
+            add("sent_msgs", q.sent.to_string());
+            add("sent_bytes", q.sent_bytes.to_string());
+            add("left_in_queue", q.msgs.len().to_string());
+            if q.waiting_time.count > 0 {
+                add(
+                    "mean_waiting_time",
+                    q.waiting_time.mean().round().to_string(),
+                );
+                add("max_waiting_time", q.waiting_time.max.to_string());
+            }
+        }
+
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::messages::msg;
+    use crate::core::testing::{new_test_env, TestEnv};
+
+    fn scheduler(env: &mut TestEnv, discipline: Discipline, weights: Vec<u64>) -> Scheduler {
+        Scheduler::register(&mut env.id_reg);
+        new_scheduler(&mut env.id_reg, "Scheduler".to_owned(), discipline, weights)
+    }
+
+    //n messages of the given size, classified by their flow which is the class
+    fn fill(env: &mut TestEnv, s: &mut Scheduler, class: u64, n: u64, bytes: u64) {
+        for seq in 0..n {
+            let mut m = msg::new_msg(
+                &mut env.id_reg,
+                Packet {
+                    flow: class,
+                    seq: seq,
+                },
+            );
+            m.meta.bit_length = bytes * 8;
+            s.enqueue(Box::new(m), 0);
+        }
+    }
+
+    //(messages, bytes) sent per class in the next n dequeues
+    fn serve(s: &mut Scheduler, n: usize) -> Vec<(u64, u64)> {
+        let mut sent = vec![(0, 0); s.classes.len()];
+        for _ in 0..n {
+            let m = s.dequeue(0).unwrap();
+            let class = m.downcast_ref::<Packet>().unwrap().payload.flow as usize;
+            sent[class].0 += 1;
+            sent[class].1 += bytes_of(m.as_ref());
+        }
+        sent
+    }
+
+    #[test]
+    fn strict_priority_serves_the_lowest_class_first() {
+        let mut env = new_test_env();
+        let mut s = scheduler(&mut env, Discipline::StrictPriority, vec![1, 1, 1]);
+        fill(&mut env, &mut s, 2, 5, 100);
+        fill(&mut env, &mut s, 1, 5, 100);
+        assert_eq!(serve(&mut s, 3), vec![(0, 0), (3, 300), (0, 0)]);
+        fill(&mut env, &mut s, 0, 2, 100);
+        assert_eq!(serve(&mut s, 6), vec![(2, 200), (2, 200), (2, 200)]);
+        assert!(s.dequeue(0).is_some());
+    }
+
+    #[test]
+    fn weighted_round_robin_shares_messages() {
+        let mut env = new_test_env();
+        let mut s = scheduler(&mut env, Discipline::WeightedRoundRobin, vec![1, 2, 3]);
+        for class in 0..3 {
+            fill(&mut env, &mut s, class, 100, 100);
+        }
+        let sent = serve(&mut s, 60);
+        assert_eq!(
+            sent.iter().map(|c| c.0).collect::<Vec<_>>(),
+            vec![10, 20, 30]
+        );
+    }
+
+    #[test]
+    fn deficit_round_robin_shares_bytes() {
+        let mut env = new_test_env();
+        let mut s = scheduler(&mut env, Discipline::DeficitRoundRobin, vec![1, 2]);
+        s.quantum = 1500;
+        //class 1 sends three times the messages of class 0 per byte
+        fill(&mut env, &mut s, 0, 100, 1500);
+        fill(&mut env, &mut s, 1, 300, 500);
+        let sent = serve(&mut s, 70);
+        assert_eq!(sent, vec![(10, 15000), (60, 30000)]);
+    }
+
+    #[test]
+    fn weighted_fair_queueing_shares_bytes() {
+        let mut env = new_test_env();
+        let mut s = scheduler(&mut env, Discipline::WeightedFairQueueing, vec![1, 3]);
+        fill(&mut env, &mut s, 0, 100, 1000);
+        fill(&mut env, &mut s, 1, 100, 500);
+        let sent = serve(&mut s, 70);
+        //1000 bytes of class 0 finish as late as 6 messages of class 1
+        assert_eq!(sent, vec![(10, 10000), (60, 30000)]);
+    }
+
+    #[test]
+    fn quantum_must_not_be_zero() {
+        let mut env = new_test_env();
+        let mut s = scheduler(&mut env, Discipline::DeficitRoundRobin, vec![1]);
+        assert!(s.set_parameter("quantum", "0").is_err());
+        assert_eq!(s.quantum, 1500);
+        assert!(s.set_parameter("quantum", "300").is_ok());
+    }
+}
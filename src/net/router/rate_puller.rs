@@ -6,7 +6,7 @@ use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
 use crate::core::messages::text_message;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::modules::module::{sim_module, HandleResult, Module};
 
 pub struct RatePuller {
     type_id: ModuleTypeId,
@@ -27,16 +27,12 @@ pub const IN_GATE: GateId = GateId(1);
 // send triggers to the buffer to request a new message from the buffer
 pub const TRIG_GATE: GateId = GateId(2);
 
-pub static TYPE_STR: &str = "RatePullerModule";
-
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
-}
-
 pub fn new(id_reg: &mut IdRegistrar, name: String, rate: u64) -> RatePuller {
     RatePuller {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
+        type_id: id_reg
+            .lookup_module_id(RatePuller::TYPE_STR.to_owned())
+            .unwrap(),
         name: name,
 
         rate: rate,
@@ -45,11 +41,13 @@ pub fn new(id_reg: &mut IdRegistrar, name: String, rate: u64) -> RatePuller {
     }
 }
 
+#[sim_module(
+    type_str = "RatePullerModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+    gate(id = TRIG_GATE, name = "trigger", dir = output),
+)]
 impl Module for RatePuller {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        vec![OUT_GATE, IN_GATE, TRIG_GATE]
-    }
-
     fn handle_message(
         &mut self,
         msg: Box<Message>,
@@ -105,21 +103,20 @@ impl Module for RatePuller {
         Ok(HandleResult {})
     }
 
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
-    }
-
-    fn module_id(&self) -> ModuleId {
-        self.id
-    }
-
-    fn name(&self) -> String {
-        self.name.clone()
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "rate" => {
+                self.rate = value.parse()?;
+                Ok(())
+            }
+            _ => Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
     }
 
     fn initialize(
         &mut self,
-        gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
+        _stage: u32,
+        gates: &std::collections::BTreeMap<GateId, std::collections::BTreeMap<PortId, Port>>,
         ctx: &mut EventHandleContext,
     ) {
         // initial request for a message
@@ -139,9 +136,4 @@ impl Module for RatePuller {
             ctx.msgs_to_send.push_back((sig, TRIG_GATE, *port));
         }
     }
-
-    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
-        //println!("Finalize RatePuller: {}", &self.name);
-        None
-    }
 }
@@ -1,4 +1,3 @@
-use crate::core::connection::connection::Port;
 use crate::core::connection::mesh::ConnectionKind;
 use crate::core::connection::simple_connection;
 use crate::core::contexts::EventHandleContext;
@@ -7,7 +6,7 @@ use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
 use crate::core::modules::container;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::modules::module::{sim_module, HandleResult, Module};
 use crate::core::runner;
 use crate::core::runner::Runner;
 
@@ -29,15 +28,17 @@ pub const OUT_GATE: GateId = GateId(100);
 //messages are received here, processed and sent to the out-buffer on the respective port
 pub const IN_GATE: GateId = GateId(200);
 
-pub static TYPE_STR: &str = "RouterModule";
-
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
-    queue::queue::register(id_reg);
-    container::register(id_reg);
-    rate_puller::register(id_reg);
-    splitter::register(id_reg);
-    simple_connection::register(id_reg);
+impl Router {
+    //registers the router and everything make_router builds it from
+    pub fn register_all(id_reg: &mut IdRegistrar) {
+        Router::register(id_reg);
+        queue::queue::Queue::register(id_reg);
+        container::ModuleContainer::register(id_reg);
+        rate_puller::RatePuller::register(id_reg);
+        splitter::Splitter::register(id_reg);
+        simple_connection::register(id_reg);
+        queue::scheduler::Scheduler::register(id_reg);
+    }
 }
 
 fn new(
@@ -47,7 +48,9 @@ fn new(
 ) -> Router {
     Router {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
+        type_id: id_reg
+            .lookup_module_id(Router::TYPE_STR.to_owned())
+            .unwrap(),
         name: name,
 
         routing_table: routing_table,
@@ -63,6 +66,26 @@ pub fn make_router(
     port_count: u64,
     name: String,
     routing_table: std::collections::HashMap<PortId, PortId>,
+) -> (ModuleId, runner::Tree<(String, ModuleId)>) {
+    make_router_with_buffers(
+        r,
+        id_reg,
+        port_count,
+        name,
+        routing_table,
+        &mut |id_reg, _| Box::new(queue::queue::new(id_reg, "Buffer".to_owned())),
+    )
+}
+
+//like make_router, but the buffer of each port is made by make_buffer(id_reg, port). The buffers
+//have to speak the protocol of Queue on its gates, eg a Queue with aqm or a Scheduler
+pub fn make_router_with_buffers(
+    r: &mut Runner,
+    id_reg: &mut IdRegistrar,
+    port_count: u64,
+    name: String,
+    routing_table: std::collections::HashMap<PortId, PortId>,
+    make_buffer: &mut dyn FnMut(&mut IdRegistrar, PortId) -> Box<dyn Module>,
 ) -> (ModuleId, runner::Tree<(String, ModuleId)>) {
     let container = Box::new(container::new_module_container(
         id_reg,
@@ -87,7 +110,7 @@ pub fn make_router(
     r.add_module(split).unwrap();
 
     for idx in 0..port_count {
-        let q = Box::new(queue::queue::new(id_reg, "Buffer".to_owned()));
+        let q = make_buffer(id_reg, PortId(idx));
         let queue_id = q.module_id();
 
         let rate = Box::new(rate_puller::new(id_reg, "RateLimiter".to_owned(), 1));
@@ -167,7 +190,7 @@ pub fn make_router(
             queue::queue::OUT_GATE,
             PortId(0),
             rate_id,
-            splitter::SPLIT_IN_GATE,
+            rate_puller::IN_GATE,
             PortId(0),
         )
         .unwrap();
@@ -191,11 +214,12 @@ pub fn make_router(
     )
 }
 
+#[sim_module(
+    type_str = "RouterModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
 impl Module for Router {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        vec![OUT_GATE, IN_GATE]
-    }
-
     fn handle_message(
         &mut self,
         msg: Box<Message>,
@@ -229,28 +253,4 @@ impl Module for Router {
     ) -> Result<HandleResult, Box<std::error::Error>> {
         panic!("Should never receive timer events")
     }
-
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
-    }
-
-    fn module_id(&self) -> ModuleId {
-        self.id
-    }
-
-    fn name(&self) -> String {
-        self.name.clone()
-    }
-
-    fn initialize(
-        &mut self,
-        _gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
-        _ctx: &mut EventHandleContext,
-    ) {
-    }
-
-    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
-        //println!("Finalize Roouter: {}", &self.name);
-        None
-    }
 }
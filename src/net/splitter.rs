@@ -1,10 +1,9 @@
-use crate::core::connection::connection::Port;
 use crate::core::contexts::EventHandleContext;
 use crate::core::events::event::Event;
 use crate::core::id_mngmnt::id_registrar::IdRegistrar;
 use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
 use crate::core::messages::message::Message;
-use crate::core::modules::module::{FinalizeResult, HandleResult, Module};
+use crate::core::modules::module::{sim_module, HandleResult, Module};
 
 pub struct Splitter {
     type_id: ModuleTypeId,
@@ -20,25 +19,23 @@ pub const SPLIT_IN_GATE: GateId = GateId(1);
 //messages come in here (0..n) ports
 pub const IN_OUT_GATE: GateId = GateId(2);
 
-pub static TYPE_STR: &str = "SplitModule";
-
-pub fn register(id_reg: &mut IdRegistrar) {
-    id_reg.register_type(TYPE_STR.to_owned());
-}
-
 pub fn new(id_reg: &mut IdRegistrar, name: String) -> Splitter {
     Splitter {
         id: id_reg.new_module_id(),
-        type_id: id_reg.lookup_module_id(TYPE_STR.to_owned()).unwrap(),
+        type_id: id_reg
+            .lookup_module_id(Splitter::TYPE_STR.to_owned())
+            .unwrap(),
         name: name,
     }
 }
 
+#[sim_module(
+    type_str = "SplitModule",
+    gate(id = SPLIT_OUT_GATE, name = "split_out", dir = output),
+    gate(id = SPLIT_IN_GATE, name = "split_in", dir = input),
+    gate(id = IN_OUT_GATE, name = "inout", dir = inout),
+)]
 impl Module for Splitter {
-    fn get_gate_ids(&self) -> Vec<GateId> {
-        vec![SPLIT_OUT_GATE, SPLIT_IN_GATE, IN_OUT_GATE]
-    }
-
     fn handle_message(
         &mut self,
         msg: Box<Message>,
@@ -67,28 +64,4 @@ impl Module for Splitter {
     ) -> Result<HandleResult, Box<std::error::Error>> {
         panic!("Should never receive timer events")
     }
-
-    fn module_type_id(&self) -> ModuleTypeId {
-        self.type_id
-    }
-
-    fn module_id(&self) -> ModuleId {
-        self.id
-    }
-
-    fn name(&self) -> String {
-        self.name.clone()
-    }
-
-    fn initialize(
-        &mut self,
-        _gates: &std::collections::HashMap<GateId, std::collections::HashMap<PortId, Port>>,
-        _ctx: &mut EventHandleContext,
-    ) {
-    }
-
-    fn finalize(&mut self, _ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
-        //println!("Finalize Splitter: {}", &self.name);
-        None
-    }
 }
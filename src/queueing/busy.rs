@@ -0,0 +1,86 @@
+use crate::core::statistics::{new_time_weighted, TimeWeighted};
+
+//How many customers a station serves over time, and for how long it served at least one
+pub struct BusyStats {
+    busy: TimeWeighted,
+    current: u64,
+    busy_since: u64,
+    busy_time: u64,
+}
+
+pub fn new_busy_stats() -> BusyStats {
+    BusyStats {
+        busy: new_time_weighted(0, 0.0),
+        current: 0,
+        busy_since: 0,
+        busy_time: 0,
+    }
+}
+
+impl BusyStats {
+    pub fn set(&mut self, now: u64, busy: u64) {
+        if self.current == 0 && busy > 0 {
+            self.busy_since = now;
+        }
+        if self.current > 0 && busy == 0 {
+            self.busy_time += now - self.busy_since;
+        }
+        self.current = busy;
+        self.busy.set(now, busy as f64);
+    }
+
+    pub fn current(&self) -> u64 {
+        self.current
+    }
+
+    //time at least one customer was in service
+    pub fn busy_time(&self, now: u64) -> u64 {
+        if self.current > 0 {
+            self.busy_time + now - self.busy_since
+        } else {
+            self.busy_time
+        }
+    }
+
+    //mean number of customers in service
+    pub fn mean_busy(&self, now: u64) -> f64 {
+        self.busy.mean(now)
+    }
+
+    //mean_busy, max_busy, busy_time and utilization. The utilization is the mean number of busy
+    //servers per server, or the fraction of time the station was busy if it has unlimited servers
+    pub fn results(
+        &self,
+        name: &str,
+        now: u64,
+        servers: Option<u64>,
+    ) -> Vec<(String, String, String)> {
+        let utilization = match servers {
+            Some(c) => self.mean_busy(now) / c as f64,
+            None if now > 0 => self.busy_time(now) as f64 / now as f64,
+            None => 0.0,
+        };
+        vec![
+            (
+                name.to_owned(),
+                "mean_busy".to_owned(),
+                self.mean_busy(now).to_string(),
+            ),
+            (
+                name.to_owned(),
+                "max_busy".to_owned(),
+                self.busy.max.to_string(),
+            ),
+            (
+                name.to_owned(),
+                "busy_time".to_owned(),
+                self.busy_time(now).to_string(),
+            ),
+            (
+                name.to_owned(),
+                "utilization".to_owned(),
+                utilization.to_string(),
+            ),
+        ]
+    }
+}
@@ -0,0 +1,121 @@
+use crate::core::connection::connection::Port;
+use crate::core::contexts::EventHandleContext;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::net::queue::scheduler::{parse_class_map, parse_classifier, Classifier};
+use crate::queueing::busy::{new_busy_stats, BusyStats};
+
+use std::collections::BTreeMap;
+
+//Sends every message on the port of OUT_GATE of its class, eg to give flows their own queues.
+//Classes are numbered through the connected ports in order, see Classifier::classify
+pub struct ClassifierModule {
+    type_id: ModuleTypeId,
+    id: ModuleId,
+    name: String,
+
+    pub classifier: Classifier,
+    pub class_map: BTreeMap<u64, usize>,
+
+    ports: Vec<PortId>,
+    //port -> messages sent on it
+    sent: BTreeMap<u64, u64>,
+    busy: BusyStats,
+}
+
+//messages get sent out here (0..n) ports, one per class
+pub const OUT_GATE: GateId = GateId(0);
+
+//messages come in here (0..n) ports
+pub const IN_GATE: GateId = GateId(1);
+
+pub fn new_classifier(
+    id_reg: &mut IdRegistrar,
+    name: String,
+    classifier: Classifier,
+) -> ClassifierModule {
+    ClassifierModule {
+        id: id_reg.new_module_id(),
+        type_id: id_reg
+            .lookup_module_id(ClassifierModule::TYPE_STR.to_owned())
+            .unwrap(),
+        name: name,
+
+        classifier: classifier,
+        class_map: BTreeMap::new(),
+
+        ports: Vec::new(),
+        sent: BTreeMap::new(),
+        busy: new_busy_stats(),
+    }
+}
+
+#[sim_module(
+    type_str = "ClassifierModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
+impl Module for ClassifierModule {
+    fn handle_message(
+        &mut self,
+        msg: Box<dyn Message>,
+        gate: GateId,
+        _port: PortId,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        if gate != IN_GATE {
+            panic!("Should not receive messages on other gates");
+        }
+        if self.ports.is_empty() {
+            return Err(format!("Classifier {} has no connected out ports", self.name).into());
+        }
+        let now = ctx.mctx.time.now();
+        self.busy.set(now, 1);
+
+        let class = self
+            .classifier
+            .classify(msg.as_ref(), &self.class_map, self.ports.len());
+        let port = self.ports[class];
+        *self.sent.entry(port.0).or_insert(0) += 1;
+        ctx.msgs_to_send.push_back((msg, OUT_GATE, port));
+        self.busy.set(now, 0);
+
+        Ok(HandleResult {})
+    }
+
+    fn initialize(
+        &mut self,
+        _stage: u32,
+        gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        _ctx: &mut EventHandleContext,
+    ) {
+        self.ports = match gates.get(&OUT_GATE) {
+            Some(ports) => ports.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+    }
+
+    //classifier takes flow or source, class_map a list of value:class pairs like "7:0,8:0,9:1"
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "classifier" => self.classifier = parse_classifier(value)?,
+            "class_map" => self.class_map = parse_class_map(value)?,
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let now = ctx.mctx.time.now();
+        let mut results = self.busy.results(&self.name(), now, None);
+        results.extend(
+            self.sent
+                .iter()
+                .map(|(port, count)| (self.name(), format!("sent_{}", port), count.to_string())),
+        );
+
+        Some(FinalizeResult { results: results })
+    }
+}
@@ -0,0 +1,145 @@
+use crate::core::contexts::EventHandleContext;
+use crate::core::events::ev;
+use crate::core::events::event::{Event, TimerEvent};
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::core::random::distribution::Distribution;
+use crate::core::statistics::{new_summary, Summary};
+use crate::queueing::busy::{new_busy_stats, BusyStats};
+
+use std::collections::BTreeMap;
+
+//An infinite server station: every message is held for a time drawn from delay, independent of
+//the others, then sent on OUT_GATE. Models think times or propagation without a connection
+pub struct Delay {
+    type_id: ModuleTypeId,
+    id: ModuleId,
+    name: String,
+
+    pub delay: Distribution,
+
+    //messages in the station by the token of their DelayDone event
+    delayed: BTreeMap<u64, Box<dyn Message>>,
+    next_token: u64,
+
+    busy: BusyStats,
+    delayed_msgs: u64,
+    delays: Summary,
+}
+
+//messages get sent out here after their delay
+pub const OUT_GATE: GateId = GateId(0);
+
+//messages come in here (0..n) ports
+pub const IN_GATE: GateId = GateId(1);
+
+//marks the end of the delay of the message with this token
+struct DelayDone {
+    token: u64,
+}
+
+pub fn new_delay(id_reg: &mut IdRegistrar, name: String, delay: Distribution) -> Delay {
+    Delay {
+        id: id_reg.new_module_id(),
+        type_id: id_reg.lookup_module_id(Delay::TYPE_STR.to_owned()).unwrap(),
+        name: name,
+
+        delay: delay,
+
+        delayed: BTreeMap::new(),
+        next_token: 0,
+
+        busy: new_busy_stats(),
+        delayed_msgs: 0,
+        delays: new_summary(),
+    }
+}
+
+#[sim_module(
+    type_str = "DelayModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
+impl Module for Delay {
+    fn handle_message(
+        &mut self,
+        msg: Box<dyn Message>,
+        gate: GateId,
+        _port: PortId,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        if gate != IN_GATE {
+            panic!("Should not receive messages on other gates");
+        }
+        let now = ctx.mctx.time.now();
+        let duration = ctx.random().sample_time(&self.delay);
+        self.delays.add(duration as f64);
+
+        let token = self.next_token;
+        self.next_token += 1;
+        self.delayed.insert(token, msg);
+        self.busy.set(now, self.delayed.len() as u64);
+
+        ctx.timer_queue.push(TimerEvent {
+            time: now + duration,
+            mod_id: self.id,
+            event: Box::new(ev::new_ev(ctx.mctx.id_reg, DelayDone { token: token })),
+        });
+
+        Ok(HandleResult {})
+    }
+
+    fn handle_timer_event(
+        &mut self,
+        ev: &dyn Event,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        let token = match ev.downcast_ref::<DelayDone>() {
+            Some(done) => done.payload.token,
+            None => panic!("Delay {} got an unknown timer event", self.name),
+        };
+        let msg = self.delayed.remove(&token).unwrap();
+        self.busy
+            .set(ctx.mctx.time.now(), self.delayed.len() as u64);
+        self.delayed_msgs += 1;
+        ctx.msgs_to_send.push_back((msg, OUT_GATE, PortId(0)));
+
+        Ok(HandleResult {})
+    }
+
+    //delay takes a distribution like "exponential(1ms)", messages already delayed keep their time
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "delay" => self.delay = value.parse()?,
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let now = ctx.mctx.time.now();
+        let name = self.name();
+        let mut results = self.busy.results(&name, now, None);
+        results.push((
+            name.clone(),
+            "delayed_msgs".to_owned(),
+            self.delayed_msgs.to_string(),
+        ));
+        results.push((
+            name.clone(),
+            "in_delay".to_owned(),
+            self.delayed.len().to_string(),
+        ));
+        if self.delays.count > 0 {
+            results.push((
+                name.clone(),
+                "mean_delay".to_owned(),
+                self.delays.mean().round().to_string(),
+            ));
+        }
+
+        Some(FinalizeResult { results: results })
+    }
+}
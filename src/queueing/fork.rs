@@ -0,0 +1,101 @@
+use crate::core::connection::connection::Port;
+use crate::core::contexts::EventHandleContext;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::queueing::busy::{new_busy_stats, BusyStats};
+
+use std::collections::BTreeMap;
+
+//Sends a copy of every message on each connected port of OUT_GATE. The copies get the same token
+//pushed onto the fork_token stack in their meta data, so a Join can put them back together.
+//Forking takes no time, the busy results are kept for comparison with the other stations
+pub struct Fork {
+    type_id: ModuleTypeId,
+    id: ModuleId,
+    name: String,
+
+    ports: Vec<PortId>,
+    forked_msgs: u64,
+    busy: BusyStats,
+}
+
+//the copies get sent out here (0..n) ports
+pub const OUT_GATE: GateId = GateId(0);
+
+//messages come in here (0..n) ports
+pub const IN_GATE: GateId = GateId(1);
+
+pub fn new_fork(id_reg: &mut IdRegistrar, name: String) -> Fork {
+    Fork {
+        id: id_reg.new_module_id(),
+        type_id: id_reg.lookup_module_id(Fork::TYPE_STR.to_owned()).unwrap(),
+        name: name,
+
+        ports: Vec::new(),
+        forked_msgs: 0,
+        busy: new_busy_stats(),
+    }
+}
+
+#[sim_module(
+    type_str = "ForkModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
+impl Module for Fork {
+    fn handle_message(
+        &mut self,
+        msg: Box<dyn Message>,
+        gate: GateId,
+        _port: PortId,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        if gate != IN_GATE {
+            panic!("Should not receive messages on other gates");
+        }
+        let now = ctx.mctx.time.now();
+        self.busy.set(now, 1);
+        let mut msg = msg;
+        msg.meta_mut().fork_token.push((self.id, self.forked_msgs));
+        self.forked_msgs += 1;
+        for port in &self.ports {
+            ctx.msgs_to_send
+                .push_back((msg.clone_msg(), OUT_GATE, *port));
+        }
+        self.busy.set(now, 0);
+
+        Ok(HandleResult {})
+    }
+
+    fn initialize(
+        &mut self,
+        _stage: u32,
+        gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        _ctx: &mut EventHandleContext,
+    ) {
+        self.ports = match gates.get(&OUT_GATE) {
+            Some(ports) => ports.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+    }
+
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let now = ctx.mctx.time.now();
+        let name = self.name();
+        let mut results = self.busy.results(&name, now, None);
+        results.push((
+            name.clone(),
+            "forked_msgs".to_owned(),
+            self.forked_msgs.to_string(),
+        ));
+        results.push((
+            name.clone(),
+            "sent_copies".to_owned(),
+            (self.forked_msgs * self.ports.len() as u64).to_string(),
+        ));
+
+        Some(FinalizeResult { results: results })
+    }
+}
@@ -0,0 +1,193 @@
+use crate::core::connection::connection::Port;
+use crate::core::contexts::EventHandleContext;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::core::statistics::{new_summary, Summary};
+use crate::queueing::busy::{new_busy_stats, BusyStats};
+
+use std::collections::BTreeMap;
+
+//Waits for parts copies of a message (by the last fork_token, see Fork) and sends the copy that
+//arrived last on OUT_GATE with that token popped, the others are discarded. Messages without a
+//fork_token are sent on unchanged. By default parts is the number of connected ports of IN_GATE.
+//Counts as busy while at least one message is waiting for its copies
+pub struct Join {
+    type_id: ModuleTypeId,
+    id: ModuleId,
+    name: String,
+
+    pub parts: Option<u64>,
+
+    //fork token -> copies that arrived so far, arrival time of the first one
+    pending: BTreeMap<(ModuleId, u64), (u64, u64)>,
+    unforked_msgs: u64,
+    expected: u64,
+
+    busy: BusyStats,
+    joined_msgs: u64,
+    //time from the first to the last copy
+    sync_time: Summary,
+}
+
+//joined messages get sent out here
+pub const OUT_GATE: GateId = GateId(0);
+
+//copies come in here (0..n) ports
+pub const IN_GATE: GateId = GateId(1);
+
+pub fn new_join(id_reg: &mut IdRegistrar, name: String) -> Join {
+    Join {
+        id: id_reg.new_module_id(),
+        type_id: id_reg.lookup_module_id(Join::TYPE_STR.to_owned()).unwrap(),
+        name: name,
+
+        parts: None,
+
+        pending: BTreeMap::new(),
+        unforked_msgs: 0,
+        expected: 1,
+
+        busy: new_busy_stats(),
+        joined_msgs: 0,
+        sync_time: new_summary(),
+    }
+}
+
+#[sim_module(
+    type_str = "JoinModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
+impl Module for Join {
+    fn handle_message(
+        &mut self,
+        msg: Box<dyn Message>,
+        gate: GateId,
+        _port: PortId,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        if gate != IN_GATE {
+            panic!("Should not receive messages on other gates");
+        }
+        let now = ctx.mctx.time.now();
+        let id = match msg.meta().fork_token.last() {
+            Some(token) => *token,
+            None => {
+                //nothing to wait for, count it so a missing Fork shows up in the results
+                self.unforked_msgs += 1;
+                ctx.msgs_to_send.push_back((msg, OUT_GATE, PortId(0)));
+                return Ok(HandleResult {});
+            }
+        };
+        let (arrived, first) = {
+            let entry = self.pending.entry(id).or_insert((0, now));
+            entry.0 += 1;
+            *entry
+        };
+
+        if arrived >= self.expected {
+            self.pending.remove(&id);
+            let mut msg = msg;
+            msg.meta_mut().fork_token.pop();
+            self.joined_msgs += 1;
+            self.sync_time.add((now - first) as f64);
+            ctx.msgs_to_send.push_back((msg, OUT_GATE, PortId(0)));
+        }
+        self.busy.set(now, self.pending.len() as u64);
+
+        Ok(HandleResult {})
+    }
+
+    fn initialize(
+        &mut self,
+        _stage: u32,
+        gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        _ctx: &mut EventHandleContext,
+    ) {
+        self.expected = match self.parts {
+            Some(parts) => parts,
+            None => gates.get(&IN_GATE).map_or(1, |ports| ports.len() as u64),
+        };
+    }
+
+    //parts takes the number of copies to wait for. Messages that are already waiting complete
+    //with the new number
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "parts" => {
+                let parts = value.parse()?;
+                self.parts = Some(parts);
+                self.expected = parts;
+            }
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let now = ctx.mctx.time.now();
+        let name = self.name();
+        let mut results = self.busy.results(&name, now, None);
+        results.push((
+            name.clone(),
+            "joined_msgs".to_owned(),
+            self.joined_msgs.to_string(),
+        ));
+        results.push((
+            name.clone(),
+            "unforked_msgs".to_owned(),
+            self.unforked_msgs.to_string(),
+        ));
+        results.push((
+            name.clone(),
+            "incomplete".to_owned(),
+            self.pending.len().to_string(),
+        ));
+        if self.sync_time.count > 0 {
+            results.push((
+                name.clone(),
+                "mean_sync_time".to_owned(),
+                self.sync_time.mean().round().to_string(),
+            ));
+        }
+
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    #[test]
+    fn joins_by_the_last_token_and_passes_unforked_messages() {
+        let mut env = new_test_env();
+        Join::register(&mut env.id_reg);
+        let mut j = new_join(&mut env.id_reg, "Join".to_owned());
+        j.set_parameter("parts", "2").unwrap();
+
+        let plain = env.msg(8);
+        env.with_ctx(|ctx| j.handle_message(plain, IN_GATE, PortId(0), ctx))
+            .unwrap();
+        let (msg, gate, port) = env.sent.pop_front().unwrap();
+        assert!(gate == OUT_GATE && port == PortId(0));
+        assert!(msg.meta().fork_token.is_empty());
+        assert_eq!(j.unforked_msgs, 1);
+
+        //copies of an inner fork 2 of a message forked by 1 before
+        let tokens = vec![(ModuleId(1), 4), (ModuleId(2), 0)];
+        for port in 0..2 {
+            let mut copy = env.msg(8);
+            copy.meta_mut().fork_token = tokens.clone();
+            env.with_ctx(|ctx| j.handle_message(copy, IN_GATE, PortId(port), ctx))
+                .unwrap();
+        }
+        let (msg, _, _) = env.sent.pop_front().unwrap();
+        assert!(env.sent.is_empty());
+        assert!(msg.meta().fork_token == vec![(ModuleId(1), 4)]);
+        assert!(j.pending.is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod busy;
+pub mod classifier;
+pub mod delay;
+pub mod fork;
+pub mod join;
+pub mod prob_router;
+pub mod server;
@@ -0,0 +1,220 @@
+use crate::core::connection::connection::Port;
+use crate::core::contexts::EventHandleContext;
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::queueing::busy::{new_busy_stats, BusyStats};
+
+use std::collections::BTreeMap;
+
+//Sends every message on one of the connected ports of OUT_GATE, port i with probability
+//probabilities[i] (relative to their sum), where port i is the i-th connected port in ascending
+//order. Without probabilities all ports are equally likely, otherwise there has to be exactly one
+//probability per connected port.
+//Used for the routing matrix of Jackson networks, an exit is a port connected to a Sink
+pub struct ProbRouter {
+    type_id: ModuleTypeId,
+    id: ModuleId,
+    name: String,
+
+    pub probabilities: Vec<f64>,
+
+    ports: Vec<PortId>,
+    initialized: bool,
+    //port -> messages sent on it
+    sent: BTreeMap<u64, u64>,
+    busy: BusyStats,
+}
+
+//messages get sent out here (0..n) ports
+pub const OUT_GATE: GateId = GateId(0);
+
+//messages come in here (0..n) ports
+pub const IN_GATE: GateId = GateId(1);
+
+pub fn new_prob_router(
+    id_reg: &mut IdRegistrar,
+    name: String,
+    probabilities: Vec<f64>,
+) -> ProbRouter {
+    if probabilities.iter().any(|p| p.is_nan() || *p < 0.0) {
+        panic!("Routing probabilities can not be negative");
+    }
+    if !probabilities.is_empty() && probabilities.iter().all(|p| *p == 0.0) {
+        panic!("Routing probabilities can not all be zero");
+    }
+    ProbRouter {
+        id: id_reg.new_module_id(),
+        type_id: id_reg
+            .lookup_module_id(ProbRouter::TYPE_STR.to_owned())
+            .unwrap(),
+        name: name,
+
+        probabilities: probabilities,
+
+        ports: Vec::new(),
+        initialized: false,
+        sent: BTreeMap::new(),
+        busy: new_busy_stats(),
+    }
+}
+
+impl ProbRouter {
+    fn check_probabilities(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.probabilities.is_empty() && self.probabilities.iter().all(|p| *p == 0.0) {
+            return Err(format!("ProbRouter {} has only zero probabilities", self.name).into());
+        }
+        //the ports are only known after initialize
+        if self.initialized
+            && !self.probabilities.is_empty()
+            && self.probabilities.len() != self.ports.len()
+        {
+            return Err(format!(
+                "ProbRouter {} has {} probabilities for {} connected out ports",
+                self.name,
+                self.probabilities.len(),
+                self.ports.len()
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[sim_module(
+    type_str = "ProbRouterModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+)]
+impl Module for ProbRouter {
+    fn handle_message(
+        &mut self,
+        msg: Box<dyn Message>,
+        gate: GateId,
+        _port: PortId,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        if gate != IN_GATE {
+            panic!("Should not receive messages on other gates");
+        }
+        if self.ports.is_empty() {
+            return Err(format!("ProbRouter {} has no connected out ports", self.name).into());
+        }
+        self.check_probabilities()?;
+        let now = ctx.mctx.time.now();
+        self.busy.set(now, 1);
+
+        let port = if self.probabilities.is_empty() {
+            let idx = ctx.random().uniform_int(0, self.ports.len() as u64);
+            self.ports[idx as usize]
+        } else {
+            let idx = ctx.random().weighted_index(&self.probabilities)?;
+            self.ports[idx]
+        };
+        *self.sent.entry(port.0).or_insert(0) += 1;
+        ctx.msgs_to_send.push_back((msg, OUT_GATE, port));
+        self.busy.set(now, 0);
+
+        Ok(HandleResult {})
+    }
+
+    fn initialize(
+        &mut self,
+        _stage: u32,
+        gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        _ctx: &mut EventHandleContext,
+    ) {
+        self.ports = match gates.get(&OUT_GATE) {
+            Some(ports) => ports.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+        self.initialized = true;
+        //messages are refused with the error until the probabilities are fixed
+        if let Err(e) = self.check_probabilities() {
+            println!("Error: {}", e);
+        }
+    }
+
+    //probabilities takes a list like "0.2,0.3,0.5", one entry per out port
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "probabilities" => {
+                let probabilities = value
+                    .split(',')
+                    .map(|p| p.trim().parse())
+                    .collect::<Result<Vec<f64>, _>>()?;
+                if probabilities.iter().any(|p| p.is_nan() || *p < 0.0) {
+                    return Err("Routing probabilities can not be negative".into());
+                }
+                let old = std::mem::replace(&mut self.probabilities, probabilities);
+                if let Err(e) = self.check_probabilities() {
+                    self.probabilities = old;
+                    return Err(e);
+                }
+            }
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let now = ctx.mctx.time.now();
+        let mut results = self.busy.results(&self.name(), now, None);
+        results.extend(
+            self.sent
+                .iter()
+                .map(|(port, count)| (self.name(), format!("sent_{}", port), count.to_string())),
+        );
+
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::{new_test_env, TestEnv};
+
+    //a router that has been initialized with ports 0..ports connected
+    fn router(env: &mut TestEnv, probabilities: Vec<f64>, ports: u64) -> ProbRouter {
+        ProbRouter::register(&mut env.id_reg);
+        let mut r = new_prob_router(&mut env.id_reg, "Router".to_owned(), probabilities);
+        r.ports = (0..ports).map(PortId).collect();
+        r.initialized = true;
+        r
+    }
+
+    #[test]
+    fn needs_one_probability_per_port() {
+        let mut env = new_test_env();
+        let mut r = router(&mut env, vec![0.5, 0.5], 3);
+        let msg = env.msg(8);
+        assert!(env
+            .with_ctx(|ctx| r.handle_message(msg, IN_GATE, PortId(0), ctx))
+            .is_err());
+        assert!(env.sent.is_empty());
+
+        assert!(r.set_parameter("probabilities", "0.2,0.8").is_err());
+        assert_eq!(r.probabilities, vec![0.5, 0.5]);
+        r.set_parameter("probabilities", "0,1,0").unwrap();
+        let msg = env.msg(8);
+        env.with_ctx(|ctx| r.handle_message(msg, IN_GATE, PortId(0), ctx))
+            .unwrap();
+        assert!(env.sent.pop_front().unwrap().2 == PortId(1));
+    }
+
+    #[test]
+    fn rejects_zero_and_nan_probabilities() {
+        let mut env = new_test_env();
+        let mut r = router(&mut env, Vec::new(), 2);
+        assert!(r.set_parameter("probabilities", "0,0").is_err());
+        assert!(r.set_parameter("probabilities", "NaN,1").is_err());
+        assert!(r.probabilities.is_empty());
+        assert!(std::panic::catch_unwind(|| {
+            let mut env = new_test_env();
+            router(&mut env, vec![0.0, 0.0], 2)
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use crate::core::connection::connection::Port;
+use crate::core::contexts::EventHandleContext;
+use crate::core::events::ev;
+use crate::core::events::event::{Event, TimerEvent};
+use crate::core::id_mngmnt::id_registrar::IdRegistrar;
+use crate::core::id_mngmnt::id_types::{GateId, ModuleId, ModuleTypeId, PortId};
+use crate::core::messages::message::Message;
+use crate::core::messages::text_message;
+use crate::core::modules::module::{sim_module, FinalizeResult, HandleResult, Module};
+use crate::core::random::distribution::Distribution;
+use crate::core::statistics::{new_summary, Summary};
+use crate::queueing::busy::{new_busy_stats, BusyStats};
+
+use std::collections::{BTreeMap, VecDeque};
+
+//A service station with servers parallel servers. Every message is held for a service time drawn
+//from service, then sent on OUT_GATE.
+//If a Queue is connected to TRIGG_GATE the server pulls from it: it asks for one message per free
+//server at the start and asks for the next one whenever a service ends. The Queue on port i of
+//TRIGG_GATE has to send to port i of IN_GATE, so its replies can be told apart from pushed messages.
+//Messages that arrive while all servers are busy wait in an unbounded line in the server, so it
+//can be fed directly as well.
+pub struct Server {
+    type_id: ModuleTypeId,
+    id: ModuleId,
+    name: String,
+
+    pub servers: u64,
+    pub service: Distribution,
+
+    //messages that arrived while all servers were busy, with their arrival time
+    line: VecDeque<(Box<dyn Message>, u64)>,
+    //messages in service by the token of their ServiceDone event
+    in_service: BTreeMap<u64, Box<dyn Message>>,
+    next_token: u64,
+    pull_ports: Vec<PortId>,
+    next_pull: usize,
+    //requests sent that no message arrived for yet, by port
+    outstanding_pulls: BTreeMap<PortId, u64>,
+
+    busy: BusyStats,
+    served: u64,
+    service_time: Summary,
+    line_waiting_time: Summary,
+}
+
+//served messages get sent out here
+pub const OUT_GATE: GateId = GateId(0);
+
+//messages to serve come in here (0..n) ports
+pub const IN_GATE: GateId = GateId(1);
+
+//requests for the next message are sent here, connect it to the trigger gate of a Queue
+pub const TRIGG_GATE: GateId = GateId(2);
+
+//marks the end of the service of the message with this token
+struct ServiceDone {
+    token: u64,
+}
+
+pub fn new_server(
+    id_reg: &mut IdRegistrar,
+    name: String,
+    servers: u64,
+    service: Distribution,
+) -> Server {
+    if servers == 0 {
+        panic!("A server station needs at least one server");
+    }
+    Server {
+        id: id_reg.new_module_id(),
+        type_id: id_reg
+            .lookup_module_id(Server::TYPE_STR.to_owned())
+            .unwrap(),
+        name: name,
+
+        servers: servers,
+        service: service,
+
+        line: VecDeque::new(),
+        in_service: BTreeMap::new(),
+        next_token: 0,
+        pull_ports: Vec::new(),
+        next_pull: 0,
+        outstanding_pulls: BTreeMap::new(),
+
+        busy: new_busy_stats(),
+        served: 0,
+        service_time: new_summary(),
+        line_waiting_time: new_summary(),
+    }
+}
+
+impl Server {
+    fn start_service(&mut self, msg: Box<dyn Message>, ctx: &mut EventHandleContext) {
+        let now = ctx.mctx.time.now();
+        let duration = ctx.random().sample_time(&self.service);
+        self.service_time.add(duration as f64);
+
+        let token = self.next_token;
+        self.next_token += 1;
+        self.in_service.insert(token, msg);
+        self.busy.set(now, self.in_service.len() as u64);
+
+        ctx.timer_queue.push(TimerEvent {
+            time: now + duration,
+            mod_id: self.id,
+            event: Box::new(ev::new_ev(ctx.mctx.id_reg, ServiceDone { token: token })),
+        });
+    }
+
+    //asks the connected queues for a message for every free server, in turns
+    fn pull(&mut self, ctx: &mut EventHandleContext) {
+        if self.pull_ports.is_empty() {
+            return;
+        }
+        let mut outstanding: u64 = self.outstanding_pulls.values().sum();
+        while self.in_service.len() as u64 + outstanding < self.servers {
+            let port = self.pull_ports[self.next_pull];
+            self.next_pull = (self.next_pull + 1) % self.pull_ports.len();
+            *self.outstanding_pulls.entry(port).or_insert(0) += 1;
+            outstanding += 1;
+
+            let sig = Box::new(text_message::new_text_msg(
+                ctx.mctx.id_reg,
+                "New Message Plz".to_owned(),
+            ));
+            ctx.msgs_to_send.push_back((sig, TRIGG_GATE, port));
+        }
+    }
+}
+
+#[sim_module(
+    type_str = "ServerModule",
+    gate(id = OUT_GATE, name = "out", dir = output),
+    gate(id = IN_GATE, name = "in", dir = input),
+    gate(id = TRIGG_GATE, name = "trigger", dir = output),
+)]
+impl Module for Server {
+    fn handle_message(
+        &mut self,
+        msg: Box<dyn Message>,
+        gate: GateId,
+        port: PortId,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        match gate {
+            IN_GATE => {
+                //pushed messages do not answer a pull
+                if let Some(outstanding) = self.outstanding_pulls.get_mut(&port) {
+                    *outstanding -= 1;
+                    if *outstanding == 0 {
+                        self.outstanding_pulls.remove(&port);
+                    }
+                }
+                if (self.in_service.len() as u64) < self.servers {
+                    self.start_service(msg, ctx);
+                } else {
+                    self.line.push_back((msg, ctx.mctx.time.now()));
+                }
+            }
+            OUT_GATE => panic!("Should not receive messages on OUT_GATE"),
+            TRIGG_GATE => panic!("Should not receive messages on TRIGG_GATE"),
+            _ => panic!("Should not receive messages on other gates"),
+        }
+
+        Ok(HandleResult {})
+    }
+
+    fn handle_timer_event(
+        &mut self,
+        ev: &dyn Event,
+        ctx: &mut EventHandleContext,
+    ) -> Result<HandleResult, Box<dyn std::error::Error>> {
+        let token = match ev.downcast_ref::<ServiceDone>() {
+            Some(done) => done.payload.token,
+            None => panic!("Server {} got an unknown timer event", self.name),
+        };
+        let now = ctx.mctx.time.now();
+        let msg = self.in_service.remove(&token).unwrap();
+        self.busy.set(now, self.in_service.len() as u64);
+        self.served += 1;
+        ctx.msgs_to_send.push_back((msg, OUT_GATE, PortId(0)));
+
+        if (self.in_service.len() as u64) < self.servers {
+            if let Some((next, arrived)) = self.line.pop_front() {
+                self.line_waiting_time.add((now - arrived) as f64);
+                self.start_service(next, ctx);
+            }
+        }
+        self.pull(ctx);
+
+        Ok(HandleResult {})
+    }
+
+    fn initialize(
+        &mut self,
+        _stage: u32,
+        gates: &BTreeMap<GateId, BTreeMap<PortId, Port>>,
+        ctx: &mut EventHandleContext,
+    ) {
+        self.pull_ports = match gates.get(&TRIGG_GATE) {
+            Some(ports) => ports.keys().cloned().collect(),
+            None => Vec::new(),
+        };
+        self.pull(ctx);
+    }
+
+    //servers takes a number, service a distribution like "exponential(1ms)". Changed numbers of
+    //servers take effect when the next service ends, services in progress are finished
+    fn set_parameter(&mut self, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match key {
+            "servers" => {
+                let servers: u64 = value.parse()?;
+                if servers == 0 {
+                    return Err("A server station needs at least one server".into());
+                }
+                self.servers = servers;
+            }
+            "service" => self.service = value.parse()?,
+            _ => return Err(format!("Module {} has no parameter {}", self.name, key).into()),
+        }
+        Ok(())
+    }
+
+    fn finalize(&mut self, ctx: &mut EventHandleContext) -> Option<FinalizeResult> {
+        let now = ctx.mctx.time.now();
+        let name = self.name();
+        let mut results = self.busy.results(&name, now, Some(self.servers));
+        results.push((
+            name.clone(),
+            "served_msgs".to_owned(),
+            self.served.to_string(),
+        ));
+        results.push((
+            name.clone(),
+            "in_service".to_owned(),
+            self.in_service.len().to_string(),
+        ));
+        results.push((
+            name.clone(),
+            "left_in_line".to_owned(),
+            self.line.len().to_string(),
+        ));
+        if self.service_time.count > 0 {
+            results.push((
+                name.clone(),
+                "mean_service_time".to_owned(),
+                self.service_time.mean().round().to_string(),
+            ));
+        }
+        if self.line_waiting_time.count > 0 {
+            results.push((
+                name.clone(),
+                "mean_line_waiting_time".to_owned(),
+                self.line_waiting_time.mean().round().to_string(),
+            ));
+        }
+
+        Some(FinalizeResult { results: results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::new_test_env;
+
+    #[test]
+    fn pushed_messages_do_not_answer_pulls() {
+        let mut env = new_test_env();
+        Server::register(&mut env.id_reg);
+        text_message::register(&mut env.id_reg);
+        let mut s = new_server(
+            &mut env.id_reg,
+            "Server".to_owned(),
+            1,
+            "constant(1us)".parse().unwrap(),
+        );
+        s.pull_ports = vec![PortId(0)];
+        env.with_ctx(|ctx| s.pull(ctx));
+        assert_eq!(env.sent.len(), 1);
+        env.sent.clear();
+
+        //pushed on port 1 while the pull on port 0 is still open
+        let msg = env.msg(8);
+        env.with_ctx(|ctx| s.handle_message(msg, IN_GATE, PortId(1), ctx))
+            .unwrap();
+        let done = env.timers.pop().unwrap();
+        env.set_time(done.time);
+        env.with_ctx(|ctx| s.handle_timer_event(done.event.as_ref(), ctx))
+            .unwrap();
+        //the served message, but no second pull
+        assert_eq!(env.sent.len(), 1);
+        assert!(env.sent[0].1 == OUT_GATE);
+
+        //the reply frees the pull, the next one goes out once it is served
+        let reply = env.msg(8);
+        env.with_ctx(|ctx| s.handle_message(reply, IN_GATE, PortId(0), ctx))
+            .unwrap();
+        assert!(s.outstanding_pulls.is_empty());
+        let done = env.timers.pop().unwrap();
+        env.set_time(done.time);
+        env.with_ctx(|ctx| s.handle_timer_event(done.event.as_ref(), ctx))
+            .unwrap();
+        assert!(env.sent.iter().any(|(_, gate, _)| *gate == TRIGG_GATE));
+    }
+}
@@ -0,0 +1,127 @@
+extern crate sim;
+
+use sim::core::connection::mesh;
+use sim::core::connection::simple_connection;
+use sim::core::events::text_event;
+use sim::core::id_mngmnt::id_registrar::IdRegistrar;
+use sim::core::id_mngmnt::id_types::PortId;
+use sim::core::messages::text_message;
+use sim::core::modules::{simple_module, sink};
+use sim::core::runner;
+use sim::core::trace::TraceEntry;
+use sim::net::router;
+
+//a source that sends to three sinks directly and to one through a router, over connections with
+//random delays and drops, so the trace depends on the order of the random draws
+fn run(seed: [u8; 16]) -> Vec<TraceEntry> {
+    let mut r = runner::new_runner(seed);
+    let mut id_reg = IdRegistrar {
+        last_id: 0,
+        last_type_id: 0,
+        type_ids: std::collections::HashMap::new(),
+        type_ids_reverse: std::collections::HashMap::new(),
+        rust_type_ids: std::collections::HashMap::new(),
+    };
+    simple_module::SimpleModule::register(&mut id_reg);
+    sink::Sink::register(&mut id_reg);
+    text_event::register(&mut id_reg);
+    text_message::register(&mut id_reg);
+    simple_connection::register(&mut id_reg);
+    router::router::Router::register_all(&mut id_reg);
+
+    let source = Box::new(simple_module::new_simple_module(
+        &mut id_reg,
+        "Source".to_owned(),
+    ));
+    let source_id = source.id;
+    r.add_module(source).unwrap();
+    r.add_to_tree(runner::Tree::Leaf(("Source".to_owned(), source_id)));
+
+    for idx in 0..4 {
+        let snk = Box::new(sink::new_sink(&mut id_reg, format!("Sink{}", idx)));
+        let sink_id = snk.id;
+        r.add_module(snk).unwrap();
+        r.add_to_tree(runner::Tree::Leaf((format!("Sink{}", idx), sink_id)));
+
+        if idx < 3 {
+            r.connect_modules(
+                Box::new(simple_connection::new_simple_connection(
+                    &mut id_reg,
+                    1,
+                    20,
+                    1000,
+                )),
+                mesh::ConnectionKind::Onedirectional,
+                source_id,
+                simple_module::OUT_GATE,
+                PortId(idx),
+                sink_id,
+                sink::IN_GATE,
+                PortId(0),
+            )
+            .unwrap();
+            continue;
+        }
+
+        let mut routing = std::collections::HashMap::new();
+        routing.insert(PortId(0), PortId(1));
+        let (router_id, tree) =
+            router::router::make_router(&mut r, &mut id_reg, 2, "Router".to_owned(), routing);
+        r.add_to_tree(tree);
+
+        r.connect_modules(
+            Box::new(simple_connection::new_simple_connection(
+                &mut id_reg,
+                1,
+                20,
+                1000,
+            )),
+            mesh::ConnectionKind::Onedirectional,
+            source_id,
+            simple_module::OUT_GATE,
+            PortId(idx),
+            router_id,
+            router::router::ROUTER_GATE_OUTER,
+            PortId(0),
+        )
+        .unwrap();
+        r.connect_modules(
+            Box::new(simple_connection::new_simple_connection(
+                &mut id_reg,
+                1,
+                20,
+                1000,
+            )),
+            mesh::ConnectionKind::Onedirectional,
+            router_id,
+            router::router::ROUTER_GATE_OUTER,
+            PortId(1),
+            sink_id,
+            sink::IN_GATE,
+            PortId(0),
+        )
+        .unwrap();
+    }
+
+    r.enable_trace();
+    r.run(&mut id_reg, 200).unwrap();
+    r.trace().to_vec()
+}
+
+#[test]
+fn same_seed_same_trace() {
+    let seed = [7; 16];
+    let first = run(seed);
+    let second = run(seed);
+
+    assert!(first.len() > 100);
+    assert_eq!(first.len(), second.len());
+    for (idx, (a, b)) in first.iter().zip(&second).enumerate() {
+        assert_eq!(a, b, "traces differ at entry {}", idx);
+    }
+}
+
+#[test]
+fn different_seed_different_trace() {
+    assert_ne!(run([7; 16]), run([8; 16]));
+}
@@ -0,0 +1,417 @@
+extern crate sim;
+
+use sim::core::connection::mesh;
+use sim::core::connection::simple_connection;
+use sim::core::id_mngmnt::id_registrar::IdRegistrar;
+use sim::core::id_mngmnt::id_types::{GateId, ModuleId, PortId};
+use sim::core::modules::module::Module;
+use sim::core::modules::{sink, source};
+use sim::core::runner;
+use sim::net::queue::scheduler::Classifier;
+use sim::queueing::{classifier, delay, fork, join, prob_router, server};
+
+fn id_registrar() -> IdRegistrar {
+    IdRegistrar {
+        last_id: 0,
+        last_type_id: 0,
+        type_ids: std::collections::HashMap::new(),
+        type_ids_reverse: std::collections::HashMap::new(),
+        rust_type_ids: std::collections::HashMap::new(),
+    }
+}
+
+//adds the module at the top level of the tree
+fn add(r: &mut runner::Runner, name: &str, module: Box<dyn Module>) -> ModuleId {
+    let id = module.module_id();
+    r.add_module(module).unwrap();
+    r.add_to_tree(runner::Tree::Leaf((name.to_owned(), id)));
+    id
+}
+
+fn connect(
+    r: &mut runner::Runner,
+    id_reg: &mut IdRegistrar,
+    from: (ModuleId, GateId, u64),
+    to: (ModuleId, GateId),
+) {
+    connect_to(r, id_reg, from, (to.0, to.1, 0));
+}
+
+fn connect_to(
+    r: &mut runner::Runner,
+    id_reg: &mut IdRegistrar,
+    from: (ModuleId, GateId, u64),
+    to: (ModuleId, GateId, u64),
+) {
+    r.connect_modules(
+        Box::new(simple_connection::new_simple_connection(id_reg, 0, 0, 0)),
+        mesh::ConnectionKind::Onedirectional,
+        from.0,
+        from.1,
+        PortId(from.2),
+        to.0,
+        to.1,
+        PortId(to.2),
+    )
+    .unwrap();
+}
+
+fn result(r: &runner::Runner, module: &str, field: &str) -> f64 {
+    r.results()
+        .iter()
+        .find(|(m, f, _)| m.ends_with(module) && f == field)
+        .map(|(_, _, v)| v.parse().unwrap())
+        .unwrap()
+}
+
+//poisson arrivals every 10us on average into a single server with a mean service time of 7us
+#[test]
+fn mm1_utilization_is_arrival_over_service_rate() {
+    let mut r = runner::new_runner([7; 16]);
+    let mut id_reg = id_registrar();
+    source::Source::register(&mut id_reg);
+    server::Server::register(&mut id_reg);
+    sink::Sink::register(&mut id_reg);
+    simple_connection::register(&mut id_reg);
+
+    let src = Box::new(source::new_source(
+        &mut id_reg,
+        "Source".to_owned(),
+        "exponential(10us)".parse().unwrap(),
+        "constant(100)".parse().unwrap(),
+    ));
+    let srv = Box::new(server::new_server(
+        &mut id_reg,
+        "Server".to_owned(),
+        1,
+        "exponential(7us)".parse().unwrap(),
+    ));
+    let snk = Box::new(sink::new_sink(&mut id_reg, "Sink".to_owned()));
+    let (src_id, srv_id, snk_id) = (src.id, srv.module_id(), snk.id);
+    r.add_module(src).unwrap();
+    r.add_module(srv).unwrap();
+    r.add_module(snk).unwrap();
+    r.add_to_tree(runner::Tree::Leaf(("Source".to_owned(), src_id)));
+    r.add_to_tree(runner::Tree::Leaf(("Server".to_owned(), srv_id)));
+    r.add_to_tree(runner::Tree::Leaf(("Sink".to_owned(), snk_id)));
+
+    connect(
+        &mut r,
+        &mut id_reg,
+        (src_id, source::OUT_GATE, 0),
+        (srv_id, server::IN_GATE),
+    );
+    connect(
+        &mut r,
+        &mut id_reg,
+        (srv_id, server::OUT_GATE, 0),
+        (snk_id, sink::IN_GATE),
+    );
+
+    r.run(&mut id_reg, 1_000_000_000).unwrap();
+
+    let utilization = result(&r, "Server", "utilization");
+    assert!(
+        (utilization - 0.7).abs() < 0.02,
+        "utilization {}",
+        utilization
+    );
+    let service = result(&r, "Server", "mean_service_time");
+    assert!((service - 7000.0).abs() < 200.0, "service time {}", service);
+}
+
+//the probabilities belong to the connected ports in ascending order, not to port numbers
+#[test]
+fn prob_router_uses_the_connected_ports() {
+    let mut r = runner::new_runner([7; 16]);
+    let mut id_reg = id_registrar();
+    source::Source::register(&mut id_reg);
+    prob_router::ProbRouter::register(&mut id_reg);
+    sink::Sink::register(&mut id_reg);
+    simple_connection::register(&mut id_reg);
+
+    let mut src = Box::new(source::new_source(
+        &mut id_reg,
+        "Source".to_owned(),
+        "constant(1us)".parse().unwrap(),
+        "constant(100)".parse().unwrap(),
+    ));
+    src.max_count = Some(1000);
+    let router = Box::new(prob_router::new_prob_router(
+        &mut id_reg,
+        "Router".to_owned(),
+        vec![1.0, 0.0, 3.0],
+    ));
+    let (src_id, router_id) = (src.id, router.module_id());
+    r.add_module(src).unwrap();
+    r.add_module(router).unwrap();
+    r.add_to_tree(runner::Tree::Leaf(("Source".to_owned(), src_id)));
+    r.add_to_tree(runner::Tree::Leaf(("Router".to_owned(), router_id)));
+    connect(
+        &mut r,
+        &mut id_reg,
+        (src_id, source::OUT_GATE, 0),
+        (router_id, prob_router::IN_GATE),
+    );
+
+    for port in &[2, 5, 7] {
+        let snk = Box::new(sink::new_sink(&mut id_reg, format!("Sink{}", port)));
+        let snk_id = snk.id;
+        r.add_module(snk).unwrap();
+        r.add_to_tree(runner::Tree::Leaf((format!("Sink{}", port), snk_id)));
+        connect(
+            &mut r,
+            &mut id_reg,
+            (router_id, prob_router::OUT_GATE, *port),
+            (snk_id, sink::IN_GATE),
+        );
+    }
+
+    r.run(&mut id_reg, 1_000_000_000).unwrap();
+
+    let (to_2, to_7) = (
+        result(&r, "Router", "sent_2"),
+        result(&r, "Router", "sent_7"),
+    );
+    assert_eq!(to_2 + to_7, 1000.0);
+    assert!(to_7 > 700.0 && to_7 < 800.0, "{} to port 7", to_7);
+    assert!(r.results().iter().all(|(_, f, _)| f != "sent_5"));
+}
+
+//a source with a fixed number of messages
+fn source(
+    id_reg: &mut IdRegistrar,
+    name: &str,
+    interarrival: &str,
+    count: u64,
+) -> Box<source::Source> {
+    let mut src = Box::new(source::new_source(
+        id_reg,
+        name.to_owned(),
+        interarrival.parse().unwrap(),
+        "constant(100)".parse().unwrap(),
+    ));
+    src.max_count = Some(count);
+    src
+}
+
+//poisson arrivals every 10us on average into 3 servers with a mean service time of 24us, so
+//a = 2.4 servers are busy on average and a message waits with probability 0.647 (Erlang C)
+#[test]
+fn mmc_utilization_and_waiting_time() {
+    let mut r = runner::new_runner([7; 16]);
+    let mut id_reg = id_registrar();
+    source::Source::register(&mut id_reg);
+    server::Server::register(&mut id_reg);
+    sink::Sink::register(&mut id_reg);
+    simple_connection::register(&mut id_reg);
+
+    let src = Box::new(source::new_source(
+        &mut id_reg,
+        "Source".to_owned(),
+        "exponential(10us)".parse().unwrap(),
+        "constant(100)".parse().unwrap(),
+    ));
+    let srv = Box::new(server::new_server(
+        &mut id_reg,
+        "Server".to_owned(),
+        3,
+        "exponential(24us)".parse().unwrap(),
+    ));
+    let snk = Box::new(sink::new_sink(&mut id_reg, "Sink".to_owned()));
+    let src_id = add(&mut r, "Source", src);
+    let srv_id = add(&mut r, "Server", srv);
+    let snk_id = add(&mut r, "Sink", snk);
+    connect(
+        &mut r,
+        &mut id_reg,
+        (src_id, source::OUT_GATE, 0),
+        (srv_id, server::IN_GATE),
+    );
+    connect(
+        &mut r,
+        &mut id_reg,
+        (srv_id, server::OUT_GATE, 0),
+        (snk_id, sink::IN_GATE),
+    );
+
+    r.run(&mut id_reg, 2_000_000_000).unwrap();
+
+    let utilization = result(&r, "Server", "utilization");
+    assert!(
+        (utilization - 0.8).abs() < 0.03,
+        "utilization {}",
+        utilization
+    );
+    let mean_busy = result(&r, "Server", "mean_busy");
+    assert!((mean_busy - 2.4).abs() < 0.1, "mean busy {}", mean_busy);
+    assert_eq!(result(&r, "Server", "max_busy"), 3.0);
+    //only messages that waited are counted, they wait 1 / (c * mu - lambda) = 40us on average
+    let waiting = result(&r, "Server", "mean_line_waiting_time");
+    assert!(
+        (waiting - 40000.0).abs() < 4000.0,
+        "waiting time {}",
+        waiting
+    );
+}
+
+//by Little's law 5 messages are in the station on average, each for exactly 50us
+#[test]
+fn delay_holds_every_message_for_its_own_time() {
+    let mut r = runner::new_runner([7; 16]);
+    let mut id_reg = id_registrar();
+    source::Source::register(&mut id_reg);
+    delay::Delay::register(&mut id_reg);
+    sink::Sink::register(&mut id_reg);
+    simple_connection::register(&mut id_reg);
+
+    let src = source(&mut id_reg, "Source", "exponential(10us)", 50_000);
+    let dly = Box::new(delay::new_delay(
+        &mut id_reg,
+        "Delay".to_owned(),
+        "constant(50us)".parse().unwrap(),
+    ));
+    let snk = Box::new(sink::new_sink(&mut id_reg, "Sink".to_owned()));
+    let src_id = add(&mut r, "Source", src);
+    let dly_id = add(&mut r, "Delay", dly);
+    let snk_id = add(&mut r, "Sink", snk);
+    connect(
+        &mut r,
+        &mut id_reg,
+        (src_id, source::OUT_GATE, 0),
+        (dly_id, delay::IN_GATE),
+    );
+    connect(
+        &mut r,
+        &mut id_reg,
+        (dly_id, delay::OUT_GATE, 0),
+        (snk_id, sink::IN_GATE),
+    );
+
+    r.run(&mut id_reg, 1_000_000_000).unwrap();
+
+    assert_eq!(result(&r, "Delay", "delayed_msgs"), 50_000.0);
+    assert_eq!(result(&r, "Delay", "in_delay"), 0.0);
+    assert_eq!(result(&r, "Delay", "mean_delay"), 50_000.0);
+    assert_eq!(result(&r, "Sink", "sunk_msgs"), 50_000.0);
+    //the run ends with the last message, so it is averaged over the time with arrivals
+    let mean_busy = result(&r, "Delay", "mean_busy");
+    assert!((mean_busy - 5.0).abs() < 0.2, "mean busy {}", mean_busy);
+}
+
+//flow 5 is mapped to class 0, flow 3 goes to class 3 % 2 = 1. Classes are the connected ports
+#[test]
+fn classifier_sends_each_flow_to_its_port() {
+    let mut r = runner::new_runner([7; 16]);
+    let mut id_reg = id_registrar();
+    source::Source::register(&mut id_reg);
+    classifier::ClassifierModule::register(&mut id_reg);
+    sink::Sink::register(&mut id_reg);
+    simple_connection::register(&mut id_reg);
+
+    let mut a = source(&mut id_reg, "A", "constant(1us)", 10);
+    a.flow = 5;
+    let mut b = source(&mut id_reg, "B", "constant(1us)", 20);
+    b.flow = 3;
+    let mut cls = Box::new(classifier::new_classifier(
+        &mut id_reg,
+        "Classifier".to_owned(),
+        Classifier::Flow,
+    ));
+    cls.set_parameter("class_map", "5:0").unwrap();
+    let a_id = add(&mut r, "A", a);
+    let b_id = add(&mut r, "B", b);
+    let cls_id = add(&mut r, "Classifier", cls);
+    connect_to(
+        &mut r,
+        &mut id_reg,
+        (a_id, source::OUT_GATE, 0),
+        (cls_id, classifier::IN_GATE, 0),
+    );
+    connect_to(
+        &mut r,
+        &mut id_reg,
+        (b_id, source::OUT_GATE, 0),
+        (cls_id, classifier::IN_GATE, 1),
+    );
+    for port in &[3, 8] {
+        let snk = Box::new(sink::new_sink(&mut id_reg, format!("Sink{}", port)));
+        let snk_id = add(&mut r, &format!("Sink{}", port), snk);
+        connect(
+            &mut r,
+            &mut id_reg,
+            (cls_id, classifier::OUT_GATE, *port),
+            (snk_id, sink::IN_GATE),
+        );
+    }
+
+    r.run(&mut id_reg, 1_000_000_000).unwrap();
+
+    assert_eq!(result(&r, "Classifier", "sent_3"), 10.0);
+    assert_eq!(result(&r, "Classifier", "sent_8"), 20.0);
+    assert_eq!(result(&r, "Sink3", "sunk_msgs"), 10.0);
+    assert_eq!(result(&r, "Sink8", "sunk_msgs"), 20.0);
+    //classifying takes no time
+    assert_eq!(result(&r, "Classifier", "max_busy"), 1.0);
+    assert_eq!(result(&r, "Classifier", "busy_time"), 0.0);
+}
+
+//Source -> F1 -> F2 -> J2 -> J1 -> Sink, with F1 -> J1 directly and F2 -> Delay -> J2.
+//The inner pair joins by its own token and leaves the outer one for J1
+#[test]
+fn nested_fork_and_join() {
+    let mut r = runner::new_runner([7; 16]);
+    let mut id_reg = id_registrar();
+    source::Source::register(&mut id_reg);
+    fork::Fork::register(&mut id_reg);
+    join::Join::register(&mut id_reg);
+    delay::Delay::register(&mut id_reg);
+    sink::Sink::register(&mut id_reg);
+    simple_connection::register(&mut id_reg);
+
+    let src = source(&mut id_reg, "Source", "constant(1us)", 100);
+    let f1 = Box::new(fork::new_fork(&mut id_reg, "F1".to_owned()));
+    let f2 = Box::new(fork::new_fork(&mut id_reg, "F2".to_owned()));
+    let dly = Box::new(delay::new_delay(
+        &mut id_reg,
+        "Delay".to_owned(),
+        "constant(3us)".parse().unwrap(),
+    ));
+    let j2 = Box::new(join::new_join(&mut id_reg, "J2".to_owned()));
+    let j1 = Box::new(join::new_join(&mut id_reg, "J1".to_owned()));
+    let snk = Box::new(sink::new_sink(&mut id_reg, "Sink".to_owned()));
+    let src_id = add(&mut r, "Source", src);
+    let f1_id = add(&mut r, "F1", f1);
+    let f2_id = add(&mut r, "F2", f2);
+    let dly_id = add(&mut r, "Delay", dly);
+    let j2_id = add(&mut r, "J2", j2);
+    let j1_id = add(&mut r, "J1", j1);
+    let snk_id = add(&mut r, "Sink", snk);
+
+    let links = vec![
+        ((src_id, source::OUT_GATE, 0), (f1_id, fork::IN_GATE, 0)),
+        ((f1_id, fork::OUT_GATE, 0), (f2_id, fork::IN_GATE, 0)),
+        ((f1_id, fork::OUT_GATE, 1), (j1_id, join::IN_GATE, 1)),
+        ((f2_id, fork::OUT_GATE, 0), (j2_id, join::IN_GATE, 0)),
+        ((f2_id, fork::OUT_GATE, 1), (dly_id, delay::IN_GATE, 0)),
+        ((dly_id, delay::OUT_GATE, 0), (j2_id, join::IN_GATE, 1)),
+        ((j2_id, join::OUT_GATE, 0), (j1_id, join::IN_GATE, 0)),
+        ((j1_id, join::OUT_GATE, 0), (snk_id, sink::IN_GATE, 0)),
+    ];
+    for (from, to) in links {
+        connect_to(&mut r, &mut id_reg, from, to);
+    }
+
+    r.run(&mut id_reg, 1_000_000_000).unwrap();
+
+    for join in &["J1", "J2"] {
+        assert_eq!(result(&r, join, "joined_msgs"), 100.0);
+        assert_eq!(result(&r, join, "unforked_msgs"), 0.0);
+        assert_eq!(result(&r, join, "incomplete"), 0.0);
+        assert_eq!(result(&r, join, "mean_sync_time"), 3000.0);
+    }
+    assert_eq!(result(&r, "Sink", "sunk_msgs"), 100.0);
+    assert_eq!(result(&r, "F1", "sent_copies"), 200.0);
+    assert_eq!(result(&r, "F2", "max_busy"), 1.0);
+    assert_eq!(result(&r, "F2", "utilization"), 0.0);
+}